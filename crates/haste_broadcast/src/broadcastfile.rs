@@ -2,7 +2,8 @@ use std::io::{self, Read, Seek, SeekFrom};
 
 use haste_core::demofile::DEMO_RECORD_BUFFER_SIZE;
 use haste_core::demostream::{
-    CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError,
+    CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError, ScanError,
+    SeekableDemoStream,
 };
 use valveprotos::common::{CDemoClassInfo, CDemoFullPacket, CDemoPacket, CDemoSendTables};
 
@@ -45,21 +46,8 @@ impl<R: Read + Seek> DemoStream for BroadcastFile<R> {
     // stream ops
     // ----
 
-    /// delegated from [`std::io::Seek`].
-    #[inline]
-    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
-        self.rdr.seek(pos)
-    }
-
-    /// delegated from [`std::io::Seek`].
-    ///
-    /// # note
-    ///
-    /// be aware that this method can be quite expensive. it might be best to make sure not to call
-    /// it too frequently.
-    #[inline]
-    fn stream_position(&mut self) -> Result<u64, io::Error> {
-        self.rdr.stream_position()
+    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
+        Ok(self.stream_position()? == self.stream_len()?)
     }
 
     // cmd header
@@ -69,6 +57,12 @@ impl<R: Read + Seek> DemoStream for BroadcastFile<R> {
         read_cmd_header(&mut self.rdr)
     }
 
+    #[inline]
+    fn unread_cmd_header(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.seek(SeekFrom::Current(-(cmd_header.size as i64)))
+            .map(|_| ())
+    }
+
     // cmd body
     // ----
 
@@ -100,14 +94,45 @@ impl<R: Read + Seek> DemoStream for BroadcastFile<R> {
         decode_cmd_full_packet(data)
     }
 
+    #[inline]
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.seek(SeekFrom::Current(cmd_header.body_size as i64))
+            .map(|_| ())
+    }
+
     // other
     // ----
 
     fn start_position(&self) -> u64 {
         0
     }
+}
+
+impl<R: Read + Seek> SeekableDemoStream for BroadcastFile<R> {
+    // stream ops
+    // ----
+
+    /// delegated from [`std::io::Seek`].
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
+        self.rdr.seek(pos)
+    }
+
+    /// delegated from [`std::io::Seek`].
+    ///
+    /// # note
+    ///
+    /// be aware that this method can be quite expensive. it might be best to make sure not to call
+    /// it too frequently.
+    #[inline]
+    fn stream_position(&mut self) -> Result<u64, io::Error> {
+        self.rdr.stream_position()
+    }
+
+    // other
+    // ----
 
-    fn total_ticks(&mut self) -> Result<i32, anyhow::Error> {
+    fn total_ticks(&mut self) -> Result<i32, ScanError> {
         if self.total_ticks.is_none() {
             self.total_ticks = Some(scan_for_last_tick(self)?);
         }
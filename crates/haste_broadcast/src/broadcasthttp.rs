@@ -1,12 +1,13 @@
 use std::error::Error;
-use std::io::{self, BufRead, Cursor, Seek, SeekFrom, Write};
+use std::io::{self, BufRead, Cursor, Seek, SeekFrom};
 use std::marker::PhantomData;
 use std::time::{Duration, Instant};
 
 use bytes::buf::Reader;
 use bytes::{Buf, Bytes};
 use haste_core::demostream::{
-    CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError,
+    CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError, ScanError,
+    SeekableDemoStream,
 };
 use serde::Deserialize;
 use valveprotos::common::{CDemoClassInfo, CDemoFullPacket, CDemoPacket, CDemoSendTables};
@@ -212,11 +213,8 @@ enum StreamState {
     },
 }
 
-enum StreamBuffer {
-    Last(Option<Reader<Bytes>>),
-    Seekable(Cursor<Vec<u8>>),
-}
-
+/// live broadcast. only the last packet is kept; it can be parsed, but not seeked. see
+/// [`BufferedBroadcastHttp`] for a seekable alternative.
 pub struct BroadcastHttp<'client, C: HttpClient + 'client> {
     client: BroadcasHttpClient<'client, C>,
     stream_fragment: i32,
@@ -224,8 +222,9 @@ pub struct BroadcastHttp<'client, C: HttpClient + 'client> {
     signup_fragment: i32,
     sync_response: SyncResponse,
     stream_state: StreamState,
-    stream_buffer: StreamBuffer,
-    total_ticks: Option<i32>,
+    packet: Option<Reader<Bytes>>,
+    // NOTE: the next read_cmd_header returns it; see DemoStream::unread_cmd_header.
+    unread_cmd_header: Option<CmdHeader>,
 }
 
 impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
@@ -244,25 +243,36 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
             signup_fragment: sync_response.signup_fragment,
             sync_response,
             stream_state: StreamState::Start,
-            stream_buffer: StreamBuffer::Last(None),
-            total_ticks: None,
+            packet: None,
+            unread_cmd_header: None,
         })
     }
 
-    /// buffer all packets. this enables seeking on [`DemoStream`].
-    pub async fn start_streaming_and_buffer(
-        http_client: C,
-        base_url: impl Into<String>,
-    ) -> Result<Self, BroadcastHttpClientError<C::Error>> {
-        let mut this = Self::start_streaming(http_client, base_url).await?;
-        this.stream_buffer = StreamBuffer::Seekable(Cursor::default());
-        Ok(this)
-    }
-
     pub fn sync_response(&self) -> &SyncResponse {
         &self.sync_response
     }
 
+    /// number of the fragment that will be requested next.
+    pub fn stream_fragment(&self) -> i32 {
+        self.stream_fragment
+    }
+
+    /// returns true if the stream has passed signup and full fragments, and is receiving delta
+    /// fragments.
+    pub fn is_streaming_deltaframes(&self) -> bool {
+        matches!(self.stream_state, StreamState::Deltaframes { .. })
+    }
+
+    /// fetches full fragment. this does not affect the state of the stream; can be used to
+    /// produce periodic full packets when converting the stream into a demo file, see
+    /// [`crate::DemoConverter::write_full_fragment`].
+    pub async fn get_full_fragment(
+        &self,
+        fragment: i32,
+    ) -> Result<Bytes, BroadcastHttpClientError<C::Error>> {
+        self.client.get_fragment(fragment, FragmentType::Full).await
+    }
+
     async fn handle_start(&mut self) -> Result<Bytes, BroadcastHttpClientError<C::Error>> {
         // bool CDemoStreamHttp::OnSync( int nResync )
         // DevMsg( "Broadcast: Buffering stream tick %d fragment %d signup fragment %d\n", m_SyncResponse.nStartTick, m_SyncResponse.nSignupFragment, m_SyncResponse.nSignupFragment );
@@ -367,44 +377,14 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
         }
     }
 
-    // TODO: can a consumer pass buffer to http client to read body into to avoid allocations / or
-    // what is the alternative?
-    pub async fn next_packet(
-        &mut self,
-    ) -> Option<Result<Bytes, BroadcastHttpClientError<C::Error>>> {
+    async fn fetch_packet(&mut self) -> Option<Result<Bytes, BroadcastHttpClientError<C::Error>>> {
         match match self.stream_state {
             StreamState::Stop => return None,
             StreamState::Start => self.handle_start().await,
             StreamState::Fullframe => self.handle_fullframe().await,
             StreamState::Deltaframes { .. } => self.handle_deltaframes().await,
         } {
-            Ok(packet) => {
-                match self.stream_buffer {
-                    StreamBuffer::Last(ref mut value) => {
-                        use bytes::Buf;
-
-                        *value = Some(
-                            packet
-                                // NOTE: clone is not cloning underlying bytes, but just increases
-                                // ref count.
-                                .clone()
-                                .reader(),
-                        );
-                    }
-                    StreamBuffer::Seekable(ref mut cursor) => {
-                        cursor
-                            .write_all(packet.as_ref())
-                            // TODO: this should not panic. probably it's fine. there are very few
-                            // things that could go wrong with Vec<u8> in rust.
-                            .expect("could not buffer");
-                        // invalidate last tick so that it can be re-scanned if needed.
-                        self.total_ticks = None;
-                    }
-                }
-
-                Some(Ok(packet))
-            }
-
+            Ok(packet) => Some(Ok(packet)),
             Err(err) => {
                 self.stream_state = StreamState::Stop;
                 match err {
@@ -415,6 +395,86 @@ impl<'client, C: HttpClient + 'client> BroadcastHttp<'client, C> {
             }
         }
     }
+
+    // TODO: can a consumer pass buffer to http client to read body into to avoid allocations / or
+    // what is the alternative?
+    //
+    /// fetches the next packet and replaces the previous one with it.
+    ///
+    /// NOTE: cmd header that was unread, but not read again, belongs to the previous packet; it is
+    /// dropped.
+    pub async fn next_packet(
+        &mut self,
+    ) -> Option<Result<Bytes, BroadcastHttpClientError<C::Error>>> {
+        let packet = self.fetch_packet().await?;
+        if let Ok(ref packet) = packet {
+            // NOTE: clone is not cloning underlying bytes, but just increases ref count.
+            self.packet = Some(packet.clone().reader());
+            self.unread_cmd_header = None;
+        }
+        Some(packet)
+    }
+}
+
+// ----
+// buffered broadcast http
+
+/// live broadcast that buffers all packets. this enables seeking on [`DemoStream`] (see
+/// [`SeekableDemoStream`]).
+pub struct BufferedBroadcastHttp<'client, C: HttpClient + 'client> {
+    broadcast: BroadcastHttp<'client, C>,
+    buffer: Cursor<Vec<u8>>,
+    total_ticks: Option<i32>,
+}
+
+impl<'client, C: HttpClient + 'client> BufferedBroadcastHttp<'client, C> {
+    pub async fn start_streaming(
+        http_client: C,
+        base_url: impl Into<String>,
+    ) -> Result<Self, BroadcastHttpClientError<C::Error>> {
+        Ok(Self {
+            broadcast: BroadcastHttp::start_streaming(http_client, base_url).await?,
+            buffer: Cursor::default(),
+            total_ticks: None,
+        })
+    }
+
+    /// see [`BroadcastHttp::sync_response`].
+    pub fn sync_response(&self) -> &SyncResponse {
+        self.broadcast.sync_response()
+    }
+
+    /// see [`BroadcastHttp::stream_fragment`].
+    pub fn stream_fragment(&self) -> i32 {
+        self.broadcast.stream_fragment()
+    }
+
+    /// see [`BroadcastHttp::is_streaming_deltaframes`].
+    pub fn is_streaming_deltaframes(&self) -> bool {
+        self.broadcast.is_streaming_deltaframes()
+    }
+
+    /// see [`BroadcastHttp::get_full_fragment`].
+    pub async fn get_full_fragment(
+        &self,
+        fragment: i32,
+    ) -> Result<Bytes, BroadcastHttpClientError<C::Error>> {
+        self.broadcast.get_full_fragment(fragment).await
+    }
+
+    /// fetches the next packet and appends it to the buffer.
+    pub async fn next_packet(
+        &mut self,
+    ) -> Option<Result<Bytes, BroadcastHttpClientError<C::Error>>> {
+        let packet = self.broadcast.fetch_packet().await?;
+        if let Ok(ref packet) = packet {
+            // NOTE: the packet is appended regardless of the read position.
+            self.buffer.get_mut().extend_from_slice(packet);
+            // invalidate last tick so that it can be re-scanned if needed.
+            self.total_ticks = None;
+        }
+        Some(packet)
+    }
 }
 
 // ----
@@ -428,111 +488,151 @@ macro_rules! no_packet_panic {
     };
 }
 
-// NOTE: this is a developer error, it's is okay to panic.
-macro_rules! not_seekable_panic {
-    () => {
-        panic!("attempted invoking seek-related operation on BroadcastHttp constructed not with `start_streaming_and_buffer`")
-    };
-}
-
 impl<'client, C: HttpClient + 'client> DemoStream for BroadcastHttp<'client, C> {
     // stream ops
     // ----
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`. othwerwise
-    /// delegated to [`std::io::Cursor`].
-    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(ref mut c) => c.seek(pos),
+    /// panics if `next_packet` never succeded.
+    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
+        if self.unread_cmd_header.is_some() {
+            return Ok(false);
+        }
+        match self.packet {
+            None => no_packet_panic!(),
+            Some(ref r) => Ok(!r.get_ref().has_remaining()),
         }
     }
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`.
-    fn stream_position(&mut self) -> Result<u64, io::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(ref mut c) => Ok(c.position()),
+    // cmd header
+    // ----
+
+    /// panics if `next_packet` never succeded.
+    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
+        if let Some(cmd_header) = self.unread_cmd_header.take() {
+            return Ok(cmd_header);
+        }
+        match self.packet {
+            None => no_packet_panic!(),
+            Some(ref mut r) => read_cmd_header(r),
         }
     }
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`.
-    fn stream_len(&mut self) -> Result<u64, io::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(ref c) => Ok(c.get_ref().len() as u64),
-        }
+    fn unread_cmd_header(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.unread_cmd_header = Some(cmd_header.clone());
+        Ok(())
     }
 
+    // cmd
+    // ----
+
     /// panics if `next_packet` never succeded.
-    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(None) => no_packet_panic!(),
-            StreamBuffer::Last(Some(ref r)) => Ok(!r.get_ref().has_remaining()),
-            StreamBuffer::Seekable(ref c) => Ok(c.position() as usize >= c.get_ref().len()),
+    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
+        let Some(ref mut r) = self.packet else {
+            no_packet_panic!()
+        };
+
+        let size = cmd_header.body_size as usize;
+        let bytes = r.get_mut();
+
+        // it probably could be possible that body of the response was not transferred /
+        // read correctly?
+        if bytes.remaining() < size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
         }
+
+        // SAFETY: this is safe because lifetime of the returned slice is tied to the
+        // lifetime of r (if i'm not missing anything, am i?).
+        let data = unsafe {
+            // NOTE: start is 0 because Reader's advance will increase start position of
+            // the underlying slice
+            let ptr = bytes.as_ref()[0..size].as_ptr();
+            std::slice::from_raw_parts(ptr, size)
+        };
+        bytes.advance(size);
+        Ok(data)
     }
 
-    // cmd header
-    // ----
+    #[inline(always)]
+    fn decode_cmd_send_tables(data: &[u8]) -> Result<CDemoSendTables, DecodeCmdError> {
+        decode_cmd_send_tables(data)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_class_info(data: &[u8]) -> Result<CDemoClassInfo, DecodeCmdError> {
+        decode_cmd_class_info(data)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_packet(data: &[u8]) -> Result<CDemoPacket, DecodeCmdError> {
+        decode_cmd_packet(data)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_full_packet(data: &[u8]) -> Result<CDemoFullPacket, DecodeCmdError> {
+        decode_cmd_full_packet(data)
+    }
 
     /// panics if `next_packet` never succeded.
-    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
-        match self.stream_buffer {
-            StreamBuffer::Last(None) => no_packet_panic!(),
-            StreamBuffer::Last(Some(ref mut r)) => read_cmd_header(r),
-            StreamBuffer::Seekable(ref mut c) => read_cmd_header(c),
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        let Some(ref mut r) = self.packet else {
+            no_packet_panic!()
+        };
+
+        let size = cmd_header.body_size as usize;
+        let bytes = r.get_mut();
+        if bytes.remaining() < size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
         }
+        bytes.advance(size);
+        Ok(())
     }
 
-    // cmd
+    // other
     // ----
 
-    /// panics if `next_packet` never succeded.
-    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
-        match self.stream_buffer {
-            StreamBuffer::Last(None) => no_packet_panic!(),
+    fn start_position(&self) -> u64 {
+        0
+    }
+}
 
-            StreamBuffer::Last(Some(ref mut r)) => {
-                use bytes::Buf;
+impl<'client, C: HttpClient + 'client> DemoStream for BufferedBroadcastHttp<'client, C> {
+    // stream ops
+    // ----
 
-                let size = cmd_header.body_size as usize;
-                let bytes = r.get_mut();
+    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
+        Ok(self.buffer.position() as usize >= self.buffer.get_ref().len())
+    }
 
-                // it probably could be possible that body of the response was not transferred /
-                // read correctly?
-                if bytes.remaining() < size {
-                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
-                }
+    // cmd header
+    // ----
 
-                // SAFETY: this is safe because lifetime of the returned slice is tied to the
-                // lifetime of r (if i'm not missing anything, am i?).
-                let data = unsafe {
-                    // NOTE: start is 0 because Reader's advance will increase start position of
-                    // the underlying slice
-                    let ptr = bytes.as_ref()[0..size].as_ptr();
-                    std::slice::from_raw_parts(ptr, size)
-                };
-                bytes.advance(size);
-                Ok(data)
-            }
+    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
+        read_cmd_header(&mut self.buffer)
+    }
 
-            StreamBuffer::Seekable(ref mut c) => {
-                let size = cmd_header.body_size as usize;
-                let pos = c.position() as usize;
+    fn unread_cmd_header(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.buffer
+            .seek(SeekFrom::Current(-(cmd_header.size as i64)))
+            .map(|_| ())
+    }
 
-                // it probably could be possible that body of the response was not transferred /
-                // read correctly?
-                let remaining = c.get_ref().len() - pos;
-                if remaining < size {
-                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
-                }
+    // cmd
+    // ----
 
-                // NOTE: Cursor's advance will not discard data from the underlying Vec<u8>
-                c.consume(size);
-                Ok(&c.get_ref()[pos..pos + size])
-            }
+    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
+        let size = cmd_header.body_size as usize;
+        let pos = self.buffer.position() as usize;
+
+        // it probably could be possible that body of the response was not transferred /
+        // read correctly?
+        let remaining = self.buffer.get_ref().len().saturating_sub(pos);
+        if remaining < size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
         }
+
+        // NOTE: Cursor's advance will not discard data from the underlying Vec<u8>
+        self.buffer.consume(size);
+        Ok(&self.buffer.get_ref()[pos..pos + size])
     }
 
     #[inline(always)]
@@ -555,27 +655,109 @@ impl<'client, C: HttpClient + 'client> DemoStream for BroadcastHttp<'client, C>
         decode_cmd_full_packet(data)
     }
 
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.buffer
+            .seek(SeekFrom::Current(cmd_header.body_size as i64))
+            .map(|_| ())
+    }
+
     // other
     // ----
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`.
     fn start_position(&self) -> u64 {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(_) => 0,
+        0
+    }
+}
+
+impl<'client, C: HttpClient + 'client> SeekableDemoStream for BufferedBroadcastHttp<'client, C> {
+    // stream ops
+    // ----
+
+    /// delegated to [`std::io::Cursor`].
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
+        self.buffer.seek(pos)
+    }
+
+    fn stream_position(&mut self) -> Result<u64, io::Error> {
+        Ok(self.buffer.position())
+    }
+
+    fn stream_len(&mut self) -> Result<u64, io::Error> {
+        Ok(self.buffer.get_ref().len() as u64)
+    }
+
+    // other
+    // ----
+
+    fn total_ticks(&mut self) -> Result<i32, ScanError> {
+        if let Some(total_ticks) = self.total_ticks {
+            return Ok(total_ticks);
         }
+        let total_ticks = scan_for_last_tick(self)?;
+        Ok(*self.total_ticks.insert(total_ticks))
     }
+}
 
-    /// panics if [`BroadcastHttp`] was not constructed with `start_reading_and_buffer`.
-    fn total_ticks(&mut self) -> Result<i32, anyhow::Error> {
-        match self.stream_buffer {
-            StreamBuffer::Last(_) => not_seekable_panic!(),
-            StreamBuffer::Seekable(_) => {
-                if self.total_ticks.is_none() {
-                    self.total_ticks = Some(scan_for_last_tick(self)?);
-                }
-                Ok(self.total_ticks.unwrap())
-            }
+#[cfg(test)]
+mod test {
+    use valveprotos::common::EDemoCommands;
+
+    use super::*;
+    use crate::testutil::{make_fake_http_client, BASE_URL, TICKS_PER_FRAGMENT};
+
+    #[test]
+    fn test_unread_cmd_header() -> anyhow::Result<()> {
+        let mut demo_stream = pollster::block_on(BroadcastHttp::start_streaming(
+            make_fake_http_client(),
+            BASE_URL,
+        ))?;
+        // NOTE: start, full and delta fragments.
+        for _ in 0..3 {
+            pollster::block_on(demo_stream.next_packet()).transpose()?;
+        }
+
+        let cmd_header = demo_stream.read_cmd_header()?;
+        assert_eq!(cmd_header.tick, TICKS_PER_FRAGMENT);
+        demo_stream.unread_cmd_header(&cmd_header)?;
+        assert_eq!(demo_stream.read_cmd_header()?.tick, cmd_header.tick);
+
+        // NOTE: unread cmd header of the last cmd in the packet is not the end of the stream.
+        for _ in 1..TICKS_PER_FRAGMENT {
+            demo_stream.skip_cmd(&cmd_header)?;
+            demo_stream.read_cmd_header()?;
         }
+        demo_stream.skip_cmd(&cmd_header)?;
+        assert!(demo_stream.is_at_eof()?);
+        demo_stream.unread_cmd_header(&cmd_header)?;
+        assert!(!demo_stream.is_at_eof()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffered_seek() -> anyhow::Result<()> {
+        let mut demo_stream = pollster::block_on(BufferedBroadcastHttp::start_streaming(
+            make_fake_http_client(),
+            BASE_URL,
+        ))?;
+        // NOTE: start, full and delta fragments.
+        for _ in 0..3 {
+            pollster::block_on(demo_stream.next_packet()).transpose()?;
+        }
+
+        let cmd_header = demo_stream.read_cmd_header()?;
+        assert_eq!(cmd_header.cmd, EDemoCommands::DemSendTables);
+        assert_eq!(demo_stream.total_ticks()?, 2 * TICKS_PER_FRAGMENT - 1);
+        // NOTE: total_ticks restores the position.
+        demo_stream.unread_cmd_header(&cmd_header)?;
+        assert_eq!(demo_stream.stream_position()?, 0);
+
+        // NOTE: packet that arrives while reading is appended after the buffered ones.
+        demo_stream.seek(SeekFrom::End(0))?;
+        pollster::block_on(demo_stream.next_packet()).transpose()?;
+        let cmd_header = demo_stream.read_cmd_header()?;
+        assert_eq!(cmd_header.tick, 2 * TICKS_PER_FRAGMENT);
+
+        Ok(())
     }
 }
@@ -0,0 +1,366 @@
+use std::io::{Cursor, Seek, Write};
+use std::marker::PhantomData;
+
+use anyhow::Result;
+use haste_core::demofile::DemoWriter;
+use haste_core::demostream::{CmdHeader, DemoStream};
+use haste_core::parser::{Context, Parser, Visitor};
+use valveprotos::common::{
+    CDemoFileHeader, CDemoFileInfo, CDemoFullPacket, CDemoPacket, CDemoStringTables, CDemoSyncTick,
+    EDemoCommands,
+};
+
+use crate::broadcasthttp::{BroadcastHttp, BroadcastHttpClientError};
+use crate::demostream::read_cmd_header;
+use crate::httpclient::HttpClient;
+
+// NOTE: broadcasts are not demo files:
+// - they do not have DemFileHeader, DemSyncTick and DemFileInfo cmds.
+// - DemSendTables and DemPacket cmds are not encoded as protobuf messages.
+// - full fragments are not delivered as DemFullPacket, but as a regular DemPacket that contains
+// full (non-delta) entity snapshot.
+//
+// conversion is driven by the Parser; it is needed to keep track of string tables that must be
+// stored along with full packets (seeking relies on them).
+//
+// NOTE: full packets can't be synthesized from the parser's state (entities would need to be
+// encoded), they can only be made of full fragments. recorded broadcasts contain only one full
+// fragment; live broadcasts allow to request them periodically, see convert_live_to_demo.
+
+const FULLPACKETS_VERSION: i32 = 2;
+
+/// returns file header that is written when the broadcast does not provide one.
+pub fn default_file_header() -> CDemoFileHeader {
+    CDemoFileHeader {
+        demo_file_stamp: "PBDEMS2".to_string(),
+        fullpackets_version: Some(FULLPACKETS_VERSION),
+        ..Default::default()
+    }
+}
+
+/// [`DemoConverter`] is a [`Visitor`] that re-emits commands of a broadcast stream as a `PBDEMS2`
+/// demo file.
+///
+/// the first packet after signon (which comes from the signup full fragment) is written as
+/// `DemFullPacket`. broadcast recordings do not contain other full fragments, more can be written
+/// with [`DemoConverter::write_full_fragment`] whenever [`DemoConverter::is_full_packet_due`]
+/// (see [`convert_live_to_demo`]).
+pub struct DemoConverter<D: DemoStream, W: Write + Seek> {
+    demo_writer: DemoWriter<W>,
+    file_header: Option<CDemoFileHeader>,
+    did_write_sync_tick: bool,
+    full_packet_interval: Option<i32>,
+    last_full_packet_tick: Option<i32>,
+    // NOTE: broadcast ticks do not start at 0; playback ticks are counted from the first tick
+    // after signon.
+    first_tick: Option<i32>,
+    last_tick: i32,
+    tick_interval: f32,
+    playback_frames: i32,
+    _phantom: PhantomData<fn(&D)>,
+}
+
+impl<D: DemoStream, W: Write + Seek> DemoConverter<D, W> {
+    pub fn new(wtr: W, file_header: CDemoFileHeader) -> Result<Self> {
+        Ok(Self {
+            demo_writer: DemoWriter::start_writing(wtr)?,
+            file_header: Some(file_header),
+            did_write_sync_tick: false,
+            full_packet_interval: None,
+            last_full_packet_tick: None,
+            first_tick: None,
+            last_tick: -1,
+            tick_interval: 0.0,
+            playback_frames: 0,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// sets how many ticks apart full packets are supposed to be written. defaults to
+    /// [`Context::full_packet_interval`] (which matches the interval of demo files).
+    pub fn with_full_packet_interval(mut self, full_packet_interval: i32) -> Self {
+        self.full_packet_interval = Some(full_packet_interval);
+        self
+    }
+
+    /// returns true if a full fragment is supposed to be written (with
+    /// [`DemoConverter::write_full_fragment`]) before the next delta fragment.
+    pub fn is_full_packet_due(&self, ctx: &Context) -> bool {
+        let full_packet_interval = self
+            .full_packet_interval
+            .unwrap_or_else(|| ctx.full_packet_interval());
+        full_packet_interval > 0
+            && self
+                .last_full_packet_tick
+                .is_some_and(|tick| ctx.tick() - tick >= full_packet_interval)
+    }
+
+    fn write_file_header(&mut self) -> Result<()> {
+        if let Some(file_header) = self.file_header.take() {
+            self.demo_writer.write_cmd_message(
+                EDemoCommands::DemFileHeader,
+                -1,
+                &file_header,
+                false,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_sync_tick(&mut self) -> Result<()> {
+        if !self.did_write_sync_tick {
+            self.demo_writer.write_cmd_message(
+                EDemoCommands::DemSyncTick,
+                -1,
+                &CDemoSyncTick {},
+                false,
+            )?;
+            self.did_write_sync_tick = true;
+        }
+        Ok(())
+    }
+
+    fn write_full_packet(
+        &mut self,
+        string_table: Option<CDemoStringTables>,
+        tick: i32,
+        packet: CDemoPacket,
+    ) -> Result<()> {
+        let full_packet = CDemoFullPacket {
+            string_table,
+            packet: Some(packet),
+        };
+        self.demo_writer.write_cmd_message(
+            EDemoCommands::DemFullPacket,
+            tick,
+            &full_packet,
+            true,
+        )?;
+        self.last_full_packet_tick = Some(tick);
+        Ok(())
+    }
+
+    /// writes contents of a full fragment (`/<fragment>/full`) as `DemFullPacket`.
+    ///
+    /// the fragment must be written right before the delta fragment with the same number is
+    /// handled. `string_table` is a snapshot of the current string tables (see
+    /// [`haste_core::stringtables::StringTableContainer::to_full_update`]).
+    pub fn write_full_fragment(
+        &mut self,
+        string_table: Option<CDemoStringTables>,
+        data: &[u8],
+    ) -> Result<()> {
+        let mut cursor = Cursor::new(data);
+        while (cursor.position() as usize) < data.len() {
+            let cmd_header = read_cmd_header(&mut cursor)?;
+            let start = cursor.position() as usize;
+            let end = start + cmd_header.body_size as usize;
+            cursor.set_position(end as u64);
+
+            if cmd_header.cmd == EDemoCommands::DemPacket {
+                let packet = D::decode_cmd_packet(&data[start..end])?;
+                return self.write_full_packet(string_table, cmd_header.tick, packet);
+            }
+        }
+        Ok(())
+    }
+
+    /// writes `DemStop`, `DemFileInfo` and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.write_file_header()?;
+        self.demo_writer
+            .write_cmd(EDemoCommands::DemStop, self.last_tick, &[], false)?;
+
+        let playback_ticks = self
+            .first_tick
+            .map_or(0, |first_tick| self.last_tick - first_tick);
+        let file_info = CDemoFileInfo {
+            playback_time: Some(playback_ticks as f32 * self.tick_interval),
+            playback_ticks: Some(playback_ticks),
+            playback_frames: Some(self.playback_frames),
+            ..Default::default()
+        };
+        Ok(self.demo_writer.finish(&file_info)?)
+    }
+}
+
+impl<D: DemoStream, W: Write + Seek> Visitor for DemoConverter<D, W> {
+    fn on_cmd(&mut self, ctx: &Context, cmd_header: &CmdHeader, data: &[u8]) -> Result<()> {
+        if cmd_header.cmd == EDemoCommands::DemFileHeader {
+            self.file_header = None;
+        } else {
+            self.write_file_header()?;
+        }
+
+        // NOTE: all signon cmds are written with tick -1 (as in demo files), otherwise
+        // Parser::run_to_tick would not be able to initialize from the converted file.
+        let tick = if self.did_write_sync_tick {
+            cmd_header.tick
+        } else {
+            -1
+        };
+
+        match cmd_header.cmd {
+            EDemoCommands::DemSyncTick => self.write_sync_tick()?,
+            EDemoCommands::DemSendTables => {
+                let cmd = D::decode_cmd_send_tables(data)?;
+                self.demo_writer
+                    .write_cmd_message(cmd_header.cmd, tick, &cmd, true)?;
+            }
+            EDemoCommands::DemSignonPacket => {
+                let cmd = D::decode_cmd_packet(data)?;
+                self.demo_writer
+                    .write_cmd_message(cmd_header.cmd, tick, &cmd, true)?;
+            }
+            EDemoCommands::DemPacket => {
+                self.write_sync_tick()?;
+                let cmd = D::decode_cmd_packet(data)?;
+                // NOTE: first packet after signon is the full fragment. it is written twice, as
+                // in demo files: full packets are only used for seeking, sequential parsing
+                // ignores them.
+                if self.last_full_packet_tick.is_none() {
+                    let string_table = ctx
+                        .string_tables()
+                        .map(|string_tables| string_tables.to_full_update());
+                    self.write_full_packet(string_table, cmd_header.tick, cmd.clone())?;
+                }
+                self.first_tick.get_or_insert(cmd_header.tick);
+                self.demo_writer
+                    .write_cmd_message(cmd_header.cmd, cmd_header.tick, &cmd, true)?;
+                self.playback_frames += 1;
+            }
+            // NOTE: stop is written by finish.
+            EDemoCommands::DemStop => {}
+            _ => self
+                .demo_writer
+                .write_cmd(cmd_header.cmd, tick, data, false)?,
+        }
+
+        if self.did_write_sync_tick {
+            self.last_tick = self.last_tick.max(cmd_header.tick);
+        }
+        self.tick_interval = ctx.tick_interval();
+
+        Ok(())
+    }
+}
+
+/// reads the whole broadcast stream and writes it as a `PBDEMS2` demo file into `wtr`.
+pub fn convert_to_demo<D: DemoStream, W: Write + Seek>(demo_stream: D, wtr: W) -> Result<W> {
+    let converter = DemoConverter::<D, W>::new(wtr, default_file_header())?;
+    let mut parser = Parser::from_stream_with_visitor(demo_stream, converter)?;
+    parser.run_to_end()?;
+    parser.into_visitor().finish()
+}
+
+/// reads a live broadcast until it ends and writes it as a `PBDEMS2` demo file into the
+/// converter's writer. full fragments are requested whenever
+/// [`DemoConverter::is_full_packet_due`], thus the demo can be seeked.
+pub async fn convert_live_to_demo<'client, C, W>(
+    demo_stream: BroadcastHttp<'client, C>,
+    converter: DemoConverter<BroadcastHttp<'client, C>, W>,
+) -> Result<W>
+where
+    C: HttpClient + 'client,
+    W: Write + Seek,
+{
+    let mut parser = Parser::from_stream_with_visitor(demo_stream, converter)?;
+
+    loop {
+        let demo_stream = parser.demo_stream();
+        if demo_stream.is_streaming_deltaframes()
+            && parser.visitor().is_full_packet_due(parser.context())
+        {
+            // NOTE: the full fragment must be written before the delta fragment with the same
+            // number is handled.
+            match demo_stream
+                .get_full_fragment(demo_stream.stream_fragment())
+                .await
+            {
+                Ok(full) => {
+                    let string_table = parser
+                        .context()
+                        .string_tables()
+                        .map(|string_tables| string_tables.to_full_update());
+                    parser
+                        .visitor_mut()
+                        .write_full_fragment(string_table, &full)?;
+                }
+                // NOTE: the fragment may not be available (yet); it'll be requested again before
+                // the next delta fragment.
+                Err(BroadcastHttpClientError::StatusCode(http::StatusCode::NOT_FOUND)) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        match parser.demo_stream_mut().next_packet().await {
+            Some(Ok(_)) => parser.run_to_end()?,
+            Some(Err(err)) => return Err(err.into()),
+            None => break,
+        }
+    }
+
+    parser.into_visitor().finish()
+}
+
+#[cfg(test)]
+mod test {
+    use haste_core::demofile::DemoFile;
+    use haste_core::demostream::SeekableDemoStream;
+
+    use super::*;
+    use crate::testutil::{make_fake_http_client, BASE_URL, FRAGMENT_COUNT, TICKS_PER_FRAGMENT};
+
+    #[derive(Default)]
+    struct PacketTickCollector {
+        ticks: Vec<i32>,
+    }
+
+    impl Visitor for PacketTickCollector {
+        fn on_cmd(&mut self, _ctx: &Context, cmd_header: &CmdHeader, _data: &[u8]) -> Result<()> {
+            if cmd_header.cmd == EDemoCommands::DemPacket {
+                self.ticks.push(cmd_header.tick);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_convert_live_to_demo() -> anyhow::Result<()> {
+        let demo_stream = pollster::block_on(BroadcastHttp::start_streaming(
+            make_fake_http_client(),
+            BASE_URL,
+        ))?;
+        let converter = DemoConverter::new(Cursor::new(Vec::new()), default_file_header())?
+            .with_full_packet_interval(2 * TICKS_PER_FRAGMENT);
+        let data = pollster::block_on(convert_live_to_demo(demo_stream, converter))?.into_inner();
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        // NOTE: ticks are counted from the first tick after signon.
+        assert_eq!(
+            demo_file.total_ticks()?,
+            FRAGMENT_COUNT * TICKS_PER_FRAGMENT - 1
+        );
+
+        let mut parser =
+            Parser::from_stream_with_visitor(demo_file, PacketTickCollector::default())?;
+        parser.run_to_tick(75)?;
+
+        let keyframe_ticks: Vec<i32> = parser
+            .keyframe_index()
+            .map(|keyframe_index| {
+                keyframe_index
+                    .iter()
+                    .map(|keyframe| keyframe.tick)
+                    .collect()
+            })
+            .unwrap_or_default();
+        assert_eq!(keyframe_ticks, vec![10, 40, 70, 100]);
+        assert_eq!(parser.context().tick(), 75);
+        // NOTE: the state was restored from the full packet at tick 70; earlier packets were not
+        // handled.
+        assert_eq!(parser.visitor().ticks, (70..=75).collect::<Vec<i32>>());
+
+        Ok(())
+    }
+}
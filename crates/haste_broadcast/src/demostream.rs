@@ -1,6 +1,8 @@
 use std::io::{Read, SeekFrom};
 
-use haste_core::demostream::{CmdHeader, DecodeCmdError, DemoStream, ReadCmdHeaderError};
+use haste_core::demostream::{
+    CmdHeader, DecodeCmdError, ReadCmdHeaderError, ScanError, SeekableDemoStream,
+};
 use prost::Message;
 use valveprotos::common::{
     CDemoClassInfo, CDemoFullPacket, CDemoPacket, CDemoSendTables, EDemoCommands,
@@ -32,18 +34,23 @@ pub(crate) fn read_cmd_header<R: Read>(mut rdr: R) -> Result<CmdHeader, ReadCmdH
         )
     };
 
+    // NOTE: data that ends before the header is the end of the stream; data that ends after the
+    // first byte of the header is truncated.
     let (tick, tick_n) = {
-        rdr.read_exact(&mut buf)?;
+        rdr.read_exact(&mut buf)
+            .map_err(ReadCmdHeaderError::incomplete)?;
         (u32::from_le_bytes(buf) as i32, size_of::<u32>())
     };
 
     let (_unknown, unknown_n) = {
-        rdr.read_exact(&mut buf[..1])?;
+        rdr.read_exact(&mut buf[..1])
+            .map_err(ReadCmdHeaderError::incomplete)?;
         (buf[0], size_of::<u8>())
     };
 
     let (body_size, body_size_n) = {
-        rdr.read_exact(&mut buf)?;
+        rdr.read_exact(&mut buf)
+            .map_err(ReadCmdHeaderError::incomplete)?;
         (u32::from_le_bytes(buf), size_of::<u32>())
     };
 
@@ -91,7 +98,9 @@ pub(crate) fn decode_cmd_full_packet(_data: &[u8]) -> Result<CDemoFullPacket, De
 // other
 // ----
 
-pub(crate) fn scan_for_last_tick(demo_stream: &mut impl DemoStream) -> Result<i32, anyhow::Error> {
+pub(crate) fn scan_for_last_tick(
+    demo_stream: &mut impl SeekableDemoStream,
+) -> Result<i32, ScanError> {
     let mut last_tick: i32 = -1;
     let backup = demo_stream.stream_position()?;
     loop {
@@ -1,8 +1,14 @@
 mod broadcastfile;
 mod broadcasthttp;
+mod convert;
 pub(crate) mod demostream;
 mod httpclient;
+#[cfg(test)]
+mod testutil;
 
 pub use broadcastfile::BroadcastFile;
-pub use broadcasthttp::{default_headers, BroadcastHttp, BroadcastHttpClientError};
+pub use broadcasthttp::{
+    default_headers, BroadcastHttp, BroadcastHttpClientError, BufferedBroadcastHttp,
+};
+pub use convert::{convert_live_to_demo, convert_to_demo, default_file_header, DemoConverter};
 pub use httpclient::HttpClient;
@@ -0,0 +1,94 @@
+// NOTE: helpers for building test data; a fake http client that serves a live broadcast.
+
+use std::collections::HashMap;
+use std::io;
+
+use bytes::Bytes;
+use prost::Message;
+use valveprotos::common::{CDemoClassInfo, CsvcMsgFlattenedSerializer, EDemoCommands};
+
+use crate::httpclient::HttpClient;
+
+pub(crate) const BASE_URL: &str = "http://broadcast.test/tv/1";
+pub(crate) const FRAGMENT_COUNT: i32 = 10;
+pub(crate) const TICKS_PER_FRAGMENT: i32 = 10;
+
+// NOTE: serves fragments of a live broadcast; everything else is not found.
+pub(crate) struct FakeHttpClient {
+    responses: HashMap<String, Bytes>,
+}
+
+impl HttpClient for FakeHttpClient {
+    type Error = io::Error;
+
+    async fn execute(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<Result<Bytes, Self::Error>>, Self::Error> {
+        let response = match self.responses.get(&request.uri().to_string()) {
+            Some(body) => http::Response::builder().body(Ok(body.clone())),
+            None => http::Response::builder()
+                .status(http::StatusCode::NOT_FOUND)
+                .body(Ok(Bytes::new())),
+        };
+        response.map_err(io::Error::other)
+    }
+}
+
+// NOTE: see read_cmd_header for the layout.
+pub(crate) fn write_cmd(fragment: &mut Vec<u8>, cmd: EDemoCommands, tick: i32, body: &[u8]) {
+    fragment.push(cmd as u8);
+    fragment.extend_from_slice(&(tick as u32).to_le_bytes());
+    fragment.push(0);
+    fragment.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    fragment.extend_from_slice(body);
+}
+
+// NOTE: fragment n covers ticks [n * 10, n * 10 + 9]; full fragment n is a snapshot at n * 10.
+pub(crate) fn make_fake_http_client() -> FakeHttpClient {
+    let mut responses = HashMap::new();
+
+    let sync = serde_json::json!({
+        "tick": TICKS_PER_FRAGMENT,
+        "endtick": 2 * TICKS_PER_FRAGMENT - 1,
+        "maxtick": (FRAGMENT_COUNT + 1) * TICKS_PER_FRAGMENT - 1,
+        "rtdelay": 0.0,
+        "rcvage": 0.0,
+        "fragment": 1,
+        "signup_fragment": 0,
+        "tps": 64,
+        "keyframe_interval": 0,
+        "map": "test",
+        "protocol": 5,
+    });
+    responses.insert(format!("{BASE_URL}/sync"), Bytes::from(sync.to_string()));
+
+    let mut start = Vec::new();
+    // NOTE: send tables are prefixed with 4 bytes, see decode_cmd_send_tables.
+    let mut send_tables = vec![0; 4];
+    send_tables.extend(CsvcMsgFlattenedSerializer::default().encode_length_delimited_to_vec());
+    write_cmd(&mut start, EDemoCommands::DemSendTables, 0, &send_tables);
+    write_cmd(
+        &mut start,
+        EDemoCommands::DemClassInfo,
+        0,
+        &CDemoClassInfo::default().encode_to_vec(),
+    );
+    responses.insert(format!("{BASE_URL}/0/start"), Bytes::from(start));
+
+    for fragment in 1..=FRAGMENT_COUNT {
+        let first_tick = fragment * TICKS_PER_FRAGMENT;
+
+        let mut full = Vec::new();
+        write_cmd(&mut full, EDemoCommands::DemPacket, first_tick, &[]);
+        responses.insert(format!("{BASE_URL}/{fragment}/full"), Bytes::from(full));
+
+        let mut delta = Vec::new();
+        for tick in first_tick..first_tick + TICKS_PER_FRAGMENT {
+            write_cmd(&mut delta, EDemoCommands::DemPacket, tick, &[]);
+        }
+        responses.insert(format!("{BASE_URL}/{fragment}/delta"), Bytes::from(delta));
+    }
+
+    FakeHttpClient { responses }
+}
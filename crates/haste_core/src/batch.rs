@@ -0,0 +1,207 @@
+use std::any::Any;
+use std::fs::File;
+use std::io::BufReader;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::Result;
+
+use crate::demofile::DemoFile;
+use crate::parser::{Parser, Visitor};
+use crate::serializercache::SerializerCache;
+
+// NOTE: parsers of all files share a serializer cache; replays of the same game build carry
+// identical send tables, thus they are parsed only once.
+
+#[derive(thiserror::Error, Debug)]
+pub enum BatchError {
+    #[error("parser panicked: {message}")]
+    Panicked { message: String },
+    #[error("worker thread panicked before the file was parsed")]
+    WorkerPanicked,
+}
+
+/// outcome of parsing a single file.
+#[derive(Debug)]
+pub struct BatchItem<V> {
+    pub path: PathBuf,
+    /// visitor that was used to parse the file, or an error that stopped parsing.
+    pub result: Result<V>,
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default()
+}
+
+/// parses many demo files on a pool of threads.
+pub struct BatchRunner {
+    thread_count: usize,
+    serializer_cache: Arc<SerializerCache>,
+}
+
+impl Default for BatchRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchRunner {
+    /// thread count defaults to the number of available cores.
+    pub fn new() -> Self {
+        Self {
+            thread_count: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            serializer_cache: Arc::new(SerializerCache::new()),
+        }
+    }
+
+    pub fn with_thread_count(mut self, thread_count: usize) -> Self {
+        self.thread_count = thread_count.max(1);
+        self
+    }
+
+    /// use the given serializer cache (for example one that is backed by a directory, or one
+    /// that is shared with other runners) instead of a fresh in-memory one.
+    pub fn with_serializer_cache(mut self, serializer_cache: Arc<SerializerCache>) -> Self {
+        self.serializer_cache = serializer_cache;
+        self
+    }
+
+    /// parses each file to the end with a visitor produced by `make_visitor`. results are
+    /// returned in the order of `paths`; failure (or panic) of one file does not affect others.
+    pub fn run<P, V, M>(&self, paths: &[P], make_visitor: M) -> Vec<BatchItem<V>>
+    where
+        P: AsRef<Path> + Sync,
+        V: Visitor + Send,
+        M: Fn(&Path) -> V + Sync,
+    {
+        let next = AtomicUsize::new(0);
+
+        let results: Vec<(usize, BatchItem<V>)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.thread_count.min(paths.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(path) = paths.get(index).map(AsRef::as_ref) else {
+                                break;
+                            };
+                            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                                parse_file(path, make_visitor(path), &self.serializer_cache)
+                            }))
+                            .unwrap_or_else(|payload| {
+                                Err(BatchError::Panicked {
+                                    message: panic_message(payload),
+                                }
+                                .into())
+                            });
+                            results.push((
+                                index,
+                                BatchItem {
+                                    path: path.to_path_buf(),
+                                    result,
+                                },
+                            ));
+                        }
+                        results
+                    })
+                })
+                .collect();
+
+            // NOTE: panics are caught per file; workers themselves are not expected to panic, but
+            // if one does - results that it collected are lost.
+            handles
+                .into_iter()
+                .filter_map(|handle| handle.join().ok())
+                .flatten()
+                .collect()
+        });
+
+        // NOTE: each path was claimed by some worker; paths without results were claimed by a
+        // worker that panicked.
+        let mut items: Vec<Option<BatchItem<V>>> = (0..paths.len()).map(|_| None).collect();
+        for (index, item) in results {
+            if let Some(slot) = items.get_mut(index) {
+                *slot = Some(item);
+            }
+        }
+        items
+            .into_iter()
+            .zip(paths.iter())
+            .map(|(item, path)| {
+                item.unwrap_or_else(|| BatchItem {
+                    path: path.as_ref().to_path_buf(),
+                    result: Err(BatchError::WorkerPanicked.into()),
+                })
+            })
+            .collect()
+    }
+}
+
+fn parse_file<V: Visitor>(
+    path: &Path,
+    visitor: V,
+    serializer_cache: &Arc<SerializerCache>,
+) -> Result<V> {
+    let file = File::open(path)?;
+    let buf_reader = BufReader::new(file);
+    let demo_file = DemoFile::start_reading(buf_reader)?;
+
+    let mut parser = Parser::from_stream_with_visitor(demo_file, visitor)?;
+    parser.set_serializer_cache(Some(serializer_cache.clone()));
+    parser.run_to_end()?;
+    Ok(parser.into_visitor())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Context;
+    use crate::testutil::{self, TempDir};
+
+    #[derive(Default)]
+    struct TickCounter {
+        ticks: usize,
+    }
+
+    impl Visitor for TickCounter {
+        fn on_tick_end(&mut self, _ctx: &Context) -> Result<()> {
+            self.ticks += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_collects_errors() -> anyhow::Result<()> {
+        let dir = TempDir::new("batch")?;
+        let valid = dir.path().join("valid.dem");
+        let missing = dir.path().join("missing.dem");
+        let corrupt = dir.path().join("corrupt.dem");
+        std::fs::write(&valid, testutil::make_packets_demo(0..10, None)?)?;
+        std::fs::write(&corrupt, b"not a demo")?;
+        let paths = [valid.clone(), missing, corrupt, valid];
+
+        let items = BatchRunner::new()
+            .with_thread_count(2)
+            .run(&paths, |_path| TickCounter::default());
+
+        assert_eq!(items.len(), paths.len());
+        for (item, path) in items.iter().zip(paths.iter()) {
+            assert_eq!(&item.path, path);
+        }
+        assert_eq!(items[0].result.as_ref().map(|v| v.ticks).ok(), Some(10));
+        assert!(items[1].result.is_err());
+        assert!(items[2].result.is_err());
+        assert_eq!(items[3].result.as_ref().map(|v| v.ticks).ok(), Some(10));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use valveprotos::dota2::CMsgDotaCombatLogEntry;
+
+use crate::stringtables::{StringTable, StringTableContainer};
+
+// NOTE: combat log entries are delivered either one per message (DOTA_UM_CombatLogDataHLTV) or in
+// bulk (DOTA_UM_CombatLogBulkData). names (of units, abilities, items, modifiers) are not sent
+// along with the entries, instead they are indices into CombatLogNames string table.
+//
+// combat log types are defined in dota_shared_enums.proto (DOTA_COMBATLOG_TYPES); only the
+// interesting ones are mapped here, the rest are exposed as CombatLogKind::Other.
+
+pub const COMBAT_LOG_NAMES_TABLE_NAME: &str = "CombatLogNames";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombatLogKind {
+    Damage,
+    Heal,
+    ModifierAdd,
+    ModifierRemove,
+    Death,
+    Ability,
+    Item,
+    Gold,
+    Xp,
+    Purchase,
+    Other(i32),
+}
+
+impl From<i32> for CombatLogKind {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Self::Damage,
+            1 => Self::Heal,
+            2 => Self::ModifierAdd,
+            3 => Self::ModifierRemove,
+            4 => Self::Death,
+            5 => Self::Ability,
+            6 => Self::Item,
+            8 => Self::Gold,
+            10 => Self::Xp,
+            11 => Self::Purchase,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+/// combat log entry with names resolved against CombatLogNames string table.
+///
+/// names are `None` if they are not set, or if the string table does not contain them (yet).
+#[derive(Debug, Clone)]
+pub struct CombatLogEntry<'a> {
+    pub kind: CombatLogKind,
+    /// game time in seconds.
+    pub game_time: f32,
+    pub attacker_name: Option<&'a str>,
+    pub target_name: Option<&'a str>,
+    pub target_source_name: Option<&'a str>,
+    pub damage_source_name: Option<&'a str>,
+    /// ability, item or modifier name.
+    pub inflictor_name: Option<&'a str>,
+    /// damage, heal, gold or xp amount. for purchases this is index of the item's name, see
+    /// [`CombatLogEntry::value_name`].
+    pub value: u32,
+    /// name that [`CombatLogEntry::value`] refers to (purchased item).
+    pub value_name: Option<&'a str>,
+    pub is_attacker_hero: bool,
+    pub is_attacker_illusion: bool,
+    pub is_target_hero: bool,
+    pub is_target_illusion: bool,
+    /// target's health after the entry was applied.
+    pub health: i32,
+    pub raw: &'a CMsgDotaCombatLogEntry,
+}
+
+#[inline]
+fn resolve_name(names: Option<&StringTable>, index: Option<u32>) -> Option<&str> {
+    let item = names?.get_item(&(index? as i32))?;
+    item.string
+        .as_ref()
+        .and_then(|string| std::str::from_utf8(string).ok())
+}
+
+impl<'a> CombatLogEntry<'a> {
+    pub fn resolve(
+        string_tables: &'a StringTableContainer,
+        raw: &'a CMsgDotaCombatLogEntry,
+    ) -> Self {
+        let names = string_tables.find_table(COMBAT_LOG_NAMES_TABLE_NAME);
+        let kind = CombatLogKind::from(raw.r#type.unwrap_or(-1));
+
+        Self {
+            kind,
+            game_time: raw.timestamp(),
+            attacker_name: resolve_name(names, raw.attacker_name),
+            target_name: resolve_name(names, raw.target_name),
+            target_source_name: resolve_name(names, raw.target_source_name),
+            damage_source_name: resolve_name(names, raw.damage_source_name),
+            inflictor_name: resolve_name(names, raw.inflictor_name),
+            value: raw.value(),
+            value_name: match kind {
+                CombatLogKind::Purchase => resolve_name(names, raw.value),
+                _ => None,
+            },
+            is_attacker_hero: raw.is_attacker_hero(),
+            is_attacker_illusion: raw.is_attacker_illusion(),
+            is_target_hero: raw.is_target_hero(),
+            is_target_illusion: raw.is_target_illusion(),
+            health: raw.health(),
+            raw,
+        }
+    }
+}
+
+/// resolves all entries of a bulk message (DOTA_UM_CombatLogBulkData).
+pub fn resolve_entries<'a>(
+    string_tables: &'a StringTableContainer,
+    entries: &'a [CMsgDotaCombatLogEntry],
+) -> impl Iterator<Item = CombatLogEntry<'a>> + 'a {
+    entries
+        .iter()
+        .map(move |raw| CombatLogEntry::resolve(string_tables, raw))
+}
+
+#[cfg(test)]
+mod test {
+    use valveprotos::common::{CDemoStringTables, c_demo_string_tables};
+
+    use super::*;
+
+    fn make_string_tables(names: &[&str]) -> StringTableContainer {
+        let mut string_tables = StringTableContainer::default();
+        string_tables.create_string_table_mut(COMBAT_LOG_NAMES_TABLE_NAME, false, 0, 0, 0, true);
+        string_tables.do_full_update(&CDemoStringTables {
+            tables: vec![c_demo_string_tables::TableT {
+                table_name: Some(COMBAT_LOG_NAMES_TABLE_NAME.to_string()),
+                items: names
+                    .iter()
+                    .map(|name| c_demo_string_tables::ItemsT {
+                        str: Some(name.to_string()),
+                        data: None,
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+        });
+        string_tables
+    }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(CombatLogKind::from(0), CombatLogKind::Damage);
+        assert_eq!(CombatLogKind::from(4), CombatLogKind::Death);
+        assert_eq!(CombatLogKind::from(11), CombatLogKind::Purchase);
+        // NOTE: 7 (DOTA_COMBATLOG_LOCATION) is not mapped.
+        assert_eq!(CombatLogKind::from(7), CombatLogKind::Other(7));
+        assert_eq!(CombatLogKind::from(-1), CombatLogKind::Other(-1));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let string_tables =
+            make_string_tables(&["npc_dota_hero_axe", "npc_dota_hero_lina", "item_blink"]);
+
+        let damage = CMsgDotaCombatLogEntry {
+            r#type: Some(0),
+            attacker_name: Some(0),
+            target_name: Some(1),
+            // NOTE: index that is missing in the string table.
+            inflictor_name: Some(42),
+            value: Some(2),
+            timestamp: Some(12.5),
+            ..Default::default()
+        };
+        let purchase = CMsgDotaCombatLogEntry {
+            r#type: Some(11),
+            target_name: Some(1),
+            value: Some(2),
+            ..Default::default()
+        };
+        let entries = [damage, purchase];
+        let entries: Vec<CombatLogEntry> = resolve_entries(&string_tables, &entries).collect();
+
+        assert_eq!(entries[0].kind, CombatLogKind::Damage);
+        assert_eq!(entries[0].game_time, 12.5);
+        assert_eq!(entries[0].attacker_name, Some("npc_dota_hero_axe"));
+        assert_eq!(entries[0].target_name, Some("npc_dota_hero_lina"));
+        assert_eq!(entries[0].inflictor_name, None);
+        assert_eq!(entries[0].damage_source_name, None);
+        assert_eq!(entries[0].value, 2);
+        // NOTE: value is a name index only for purchases.
+        assert_eq!(entries[0].value_name, None);
+
+        assert_eq!(entries[1].kind, CombatLogKind::Purchase);
+        assert_eq!(entries[1].value_name, Some("item_blink"));
+
+        // NOTE: names can't be resolved without the string table.
+        let empty_string_tables = StringTableContainer::default();
+        let entry = CombatLogEntry::resolve(&empty_string_tables, entries[0].raw);
+        assert_eq!(entry.attacker_name, None);
+    }
+}
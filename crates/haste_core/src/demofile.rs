@@ -1,4 +1,4 @@
-use std::io::{self, Read, Seek, SeekFrom};
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
 
 use prost::Message;
 use valveprotos::common::{
@@ -7,7 +7,10 @@ use valveprotos::common::{
 use valveprotos::prost;
 use varint;
 
-use crate::demostream::{CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError};
+use crate::demostream::{
+    CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError, ScanError,
+    SeekableDemoStream,
+};
 
 // #define DEMO_RECORD_BUFFER_SIZE 2*1024*1024
 //
@@ -90,18 +93,100 @@ impl<R: Read + Seek> DemoFile<R> {
         &self.demo_header
     }
 
-    pub fn file_info(&mut self) -> Result<&CDemoFileInfo, anyhow::Error> {
-        if self.file_info.is_none() {
-            let backup = self.stream_position()?;
+    pub fn file_info(&mut self) -> Result<&CDemoFileInfo, ScanError> {
+        let file_info = match self.file_info.take() {
+            Some(file_info) => file_info,
+            None => {
+                let backup = self.stream_position()?;
 
-            self.seek(SeekFrom::Start(self.demo_header.fileinfo_offset as u64))?;
-            let cmd_header = self.read_cmd_header()?;
-            self.file_info = Some(CDemoFileInfo::decode(self.read_cmd(&cmd_header)?)?);
+                self.seek(SeekFrom::Start(self.demo_header.fileinfo_offset as u64))?;
+                let cmd_header = self.read_cmd_header()?;
+                let file_info = CDemoFileInfo::decode(self.read_cmd(&cmd_header)?)
+                    .map_err(DecodeCmdError::from)?;
 
-            self.seek(SeekFrom::Start(backup))?;
-        }
+                self.seek(SeekFrom::Start(backup))?;
+                file_info
+            }
+        };
+
+        Ok(self.file_info.insert(file_info))
+    }
+}
+
+fn read_cmd_header<R: Read>(mut rdr: R) -> Result<CmdHeader, ReadCmdHeaderError> {
+    // NOTE: data that ends before the header is the end of the stream; data that ends after the
+    // first byte of the header is truncated.
+    let mut first = [0u8; 1];
+    rdr.read_exact(&mut first)?;
+    let mut rdr = first.as_slice().chain(rdr);
+
+    let (cmd, cmd_n, body_compressed) = {
+        let (cmd_raw, n) =
+            varint::read_uvarint32(&mut rdr).map_err(ReadCmdHeaderError::incomplete)?;
+
+        const DEM_IS_COMPRESSED: u32 = EDemoCommands::DemIsCompressed as u32;
+        let body_compressed = cmd_raw & DEM_IS_COMPRESSED == DEM_IS_COMPRESSED;
+
+        let cmd = if body_compressed {
+            cmd_raw & !DEM_IS_COMPRESSED
+        } else {
+            cmd_raw
+        };
+
+        (
+            EDemoCommands::try_from(cmd as i32).map_err(|_| ReadCmdHeaderError::UnknownCmd {
+                raw: cmd_raw,
+                uncompressed: cmd,
+            })?,
+            n,
+            body_compressed,
+        )
+    };
+
+    let (tick, tick_n) = {
+        let (tick, n) = varint::read_uvarint32(&mut rdr).map_err(ReadCmdHeaderError::incomplete)?;
+        // NOTE: tick is set to u32::MAX before before all pre-game initialization messages are
+        // sent.
+        // ticks everywhere are represented as i32, casting u32::MAX to i32 is okay because
+        // bits in u32::MAX == bits in -1 i32.
+        let tick = tick as i32;
+        (tick, n)
+    };
+
+    let (body_size, body_size_n) =
+        varint::read_uvarint32(&mut rdr).map_err(ReadCmdHeaderError::incomplete)?;
+
+    Ok(CmdHeader {
+        cmd,
+        body_compressed,
+        tick,
+        body_size,
+        size: (cmd_n + tick_n + body_size_n) as u8,
+    })
+}
 
-        Ok(self.file_info.as_ref().expect("file info have been read"))
+fn read_cmd<'b, R: Read>(
+    mut rdr: R,
+    buf: &'b mut [u8],
+    cmd_header: &CmdHeader,
+) -> Result<&'b [u8], ReadCmdError> {
+    // NOTE: body size of a damaged cmd header can be anything.
+    if cmd_header.body_size as usize > buf.len() {
+        return Err(ReadCmdError::BodyTooLarge {
+            body_size: cmd_header.body_size,
+        });
+    }
+    let (left, right) = buf.split_at_mut(cmd_header.body_size as usize);
+    rdr.read_exact(left)?;
+
+    if cmd_header.body_compressed {
+        let decompress_len = snap::raw::decompress_len(left)?;
+        snap::raw::Decoder::new().decompress(left, right)?;
+        // NOTE: we need to slice stuff up, because prost's decode can't
+        // determine when to stop.
+        Ok(&right[..decompress_len])
+    } else {
+        Ok(left)
     }
 }
 
@@ -109,6 +194,70 @@ impl<R: Read + Seek> DemoStream for DemoFile<R> {
     // stream ops
     // ----
 
+    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
+        Ok(self.stream_position()? == self.stream_len()?)
+    }
+
+    // cmd header
+    // ----
+
+    #[inline]
+    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
+        read_cmd_header(&mut self.rdr)
+    }
+
+    #[inline]
+    fn unread_cmd_header(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.seek(SeekFrom::Current(-(cmd_header.size as i64)))
+            .map(|_| ())
+    }
+
+    // cmd body
+    // ----
+
+    #[inline]
+    fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
+        read_cmd(&mut self.rdr, &mut self.buf, cmd_header)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_send_tables(data: &[u8]) -> Result<CDemoSendTables, DecodeCmdError> {
+        CDemoSendTables::decode(data).map_err(DecodeCmdError::DecodeProtobufError)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_class_info(data: &[u8]) -> Result<CDemoClassInfo, DecodeCmdError> {
+        CDemoClassInfo::decode(data).map_err(DecodeCmdError::DecodeProtobufError)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_packet(data: &[u8]) -> Result<CDemoPacket, DecodeCmdError> {
+        CDemoPacket::decode(data).map_err(DecodeCmdError::DecodeProtobufError)
+    }
+
+    #[inline(always)]
+    fn decode_cmd_full_packet(data: &[u8]) -> Result<CDemoFullPacket, DecodeCmdError> {
+        CDemoFullPacket::decode(data).map_err(DecodeCmdError::DecodeProtobufError)
+    }
+
+    #[inline]
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.seek(SeekFrom::Current(cmd_header.body_size as i64))
+            .map(|_| ())
+    }
+
+    // other
+    // ----
+
+    fn start_position(&self) -> u64 {
+        size_of::<DemoHeader>() as u64
+    }
+}
+
+impl<R: Read + Seek> SeekableDemoStream for DemoFile<R> {
+    // stream ops
+    // ----
+
     /// delegated from [`std::io::Seek`].
     #[inline]
     fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error> {
@@ -126,71 +275,83 @@ impl<R: Read + Seek> DemoStream for DemoFile<R> {
         self.rdr.stream_position()
     }
 
-    // cmd header
+    // other
     // ----
 
-    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
-        let (cmd, cmd_n, body_compressed) = {
-            let (cmd_raw, n) = varint::read_uvarint32(&mut self.rdr)?;
-
-            const DEM_IS_COMPRESSED: u32 = EDemoCommands::DemIsCompressed as u32;
-            let body_compressed = cmd_raw & DEM_IS_COMPRESSED == DEM_IS_COMPRESSED;
-
-            let cmd = if body_compressed {
-                cmd_raw & !DEM_IS_COMPRESSED
-            } else {
-                cmd_raw
-            };
-
-            (
-                EDemoCommands::try_from(cmd as i32).map_err(|_| {
-                    ReadCmdHeaderError::UnknownCmd {
-                        raw: cmd_raw,
-                        uncompressed: cmd,
-                    }
-                })?,
-                n,
-                body_compressed,
-            )
-        };
+    fn total_ticks(&mut self) -> Result<i32, ScanError> {
+        self.file_info().map(|file_info| file_info.playback_ticks())
+    }
+}
 
-        let (tick, tick_n) = {
-            let (tick, n) = varint::read_uvarint32(&mut self.rdr)?;
-            // NOTE: tick is set to u32::MAX before before all pre-game initialization messages are
-            // sent.
-            // ticks everywhere are represented as i32, casting u32::MAX to i32 is okay because
-            // bits in u32::MAX == bits in -1 i32.
-            let tick = tick as i32;
-            (tick, n)
-        };
+// forward-only reading
+// ----
 
-        let (body_size, body_size_n) = varint::read_uvarint32(&mut self.rdr)?;
+/// forward-only counterpart of [`DemoFile`] that reads from any [`BufRead`]er (stdin, a pipe, a
+/// decompressor, a network stream); the demo does not need to be loaded into memory first. it can
+/// be parsed from start to end, but not seeked.
+///
+/// NOTE: the reader must be buffered; buffering is needed to tell whether the end of the stream
+/// was reached. wrap unbuffered readers into [`std::io::BufReader`].
+#[derive(Debug)]
+pub struct DemoReader<R: BufRead> {
+    rdr: R,
+    buf: Vec<u8>,
+    demo_header: DemoHeader,
+    // NOTE: the next read_cmd_header returns it; see DemoStream::unread_cmd_header.
+    unread_cmd_header: Option<CmdHeader>,
+}
 
-        Ok(CmdHeader {
-            cmd,
-            body_compressed,
-            tick,
-            body_size,
-            size: (cmd_n + tick_n + body_size_n) as u8,
+impl<R: BufRead> DemoReader<R> {
+    /// creates a new [`DemoReader`] instance from the given reader.
+    pub fn start_reading(mut rdr: R) -> Result<Self, DemoHeaderError> {
+        let demo_header = read_demo_header(&mut rdr)?;
+        Ok(Self {
+            rdr,
+            buf: vec![0u8; DEMO_RECORD_BUFFER_SIZE],
+            demo_header,
+            unread_cmd_header: None,
         })
     }
 
+    #[inline]
+    pub fn demo_header(&self) -> &DemoHeader {
+        &self.demo_header
+    }
+}
+
+impl<R: BufRead> DemoStream for DemoReader<R> {
+    // stream ops
+    // ----
+
+    /// blocks until more data is available or the underlying reader reaches its end.
+    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
+        if self.unread_cmd_header.is_some() {
+            return Ok(false);
+        }
+        Ok(self.rdr.fill_buf()?.is_empty())
+    }
+
+    // cmd header
+    // ----
+
+    fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError> {
+        match self.unread_cmd_header.take() {
+            Some(cmd_header) => Ok(cmd_header),
+            None => read_cmd_header(&mut self.rdr),
+        }
+    }
+
+    fn unread_cmd_header(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        self.unread_cmd_header = Some(cmd_header.clone());
+        Ok(())
+    }
+
     // cmd body
     // ----
 
+    #[inline]
     fn read_cmd(&mut self, cmd_header: &CmdHeader) -> Result<&[u8], ReadCmdError> {
-        let (left, right) = self.buf.split_at_mut(cmd_header.body_size as usize);
-        self.rdr.read_exact(left)?;
-
-        if cmd_header.body_compressed {
-            let decompress_len = snap::raw::decompress_len(left)?;
-            snap::raw::Decoder::new().decompress(left, right)?;
-            // NOTE: we need to slice stuff up, because prost's decode can't
-            // determine when to stop.
-            Ok(&right[..decompress_len])
-        } else {
-            Ok(left)
-        }
+        read_cmd(&mut self.rdr, &mut self.buf, cmd_header)
     }
 
     #[inline(always)]
@@ -213,14 +374,290 @@ impl<R: Read + Seek> DemoStream for DemoFile<R> {
         CDemoFullPacket::decode(data).map_err(DecodeCmdError::DecodeProtobufError)
     }
 
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
+        let body_size = cmd_header.body_size as u64;
+        let n = io::copy(&mut (&mut self.rdr).take(body_size), &mut io::sink())?;
+        if n < body_size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
     // other
     // ----
 
     fn start_position(&self) -> u64 {
         size_of::<DemoHeader>() as u64
     }
+}
 
-    fn total_ticks(&mut self) -> Result<i32, anyhow::Error> {
-        self.file_info().map(|file_info| file_info.playback_ticks())
+// writing
+// ----
+
+#[derive(thiserror::Error, Debug)]
+pub enum WriteCmdError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    CompressError(#[from] snap::Error),
+}
+
+const MAX_VARINT32_BYTES: usize = 5;
+
+// NOTE: varint crate provides only readers; this is a mirror of read_uvarint32 (protobuf-style
+// base 128 varint).
+fn write_uvarint32<W: Write>(mut wtr: W, mut value: u32) -> Result<usize, io::Error> {
+    let mut buf = [0u8; MAX_VARINT32_BYTES];
+    let mut n = 0;
+    while value >= 0x80 {
+        buf[n] = (value as u8) | 0x80;
+        value >>= 7;
+        n += 1;
+    }
+    buf[n] = value as u8;
+    n += 1;
+    wtr.write_all(&buf[..n])?;
+    Ok(n)
+}
+
+fn write_demo_header<W: Write>(mut wtr: W, demo_header: &DemoHeader) -> Result<(), io::Error> {
+    wtr.write_all(&demo_header.demofilestamp)?;
+    wtr.write_all(&demo_header.fileinfo_offset.to_le_bytes())?;
+    wtr.write_all(&demo_header.spawngroups_offset.to_le_bytes())?;
+    Ok(())
+}
+
+/// [`DemoWriter`] writes `PBDEMS2` demo files that can be read by [`DemoFile`] (and by the game
+/// client).
+///
+/// the header is written with zero offsets first, and is patched by [`DemoWriter::finish`] once
+/// offsets of `DEM_FileInfo` (and `DEM_SpawnGroups`, if it was written) are known. offsets are
+/// relative to the header; the writer does not need to be at the start of the stream.
+///
+/// NOTE: `DEM_Stop` is not written automatically; it is expected to be written (or re-emitted)
+/// before finishing.
+#[derive(Debug)]
+pub struct DemoWriter<W: Write + Seek> {
+    wtr: W,
+    buf: Vec<u8>,
+    // NOTE: position of the header in the stream.
+    start_position: u64,
+    // NOTE: position is tracked manually to avoid calling Seek::stream_position after each cmd.
+    position: u64,
+    spawngroups_offset: i32,
+    last_tick: i32,
+}
+
+impl<W: Write + Seek> DemoWriter<W> {
+    /// creates a new [`DemoWriter`] and writes a placeholder header.
+    ///
+    /// # performance note
+    ///
+    /// for optimal performance make sure to provide a writer that implements buffering (for
+    /// example [`std::io::BufWriter`]).
+    pub fn start_writing(mut wtr: W) -> Result<Self, io::Error> {
+        let start_position = wtr.stream_position()?;
+        write_demo_header(
+            &mut wtr,
+            &DemoHeader {
+                demofilestamp: DEMO_HEADER_ID,
+                fileinfo_offset: 0,
+                spawngroups_offset: 0,
+            },
+        )?;
+        Ok(Self {
+            wtr,
+            buf: Vec::new(),
+            start_position,
+            position: start_position + size_of::<DemoHeader>() as u64,
+            spawngroups_offset: 0,
+            last_tick: -1,
+        })
+    }
+
+    /// writes a cmd header followed by the body. `data` must be uncompressed; if `compress` is
+    /// true the body will be compressed with snappy.
+    pub fn write_cmd(
+        &mut self,
+        cmd: EDemoCommands,
+        tick: i32,
+        data: &[u8],
+        compress: bool,
+    ) -> Result<(), WriteCmdError> {
+        if cmd == EDemoCommands::DemSpawnGroups {
+            self.spawngroups_offset = self.relative_position();
+        }
+        self.last_tick = tick;
+
+        let body = if compress {
+            let max_compress_len = snap::raw::max_compress_len(data.len());
+            if self.buf.len() < max_compress_len {
+                self.buf.resize(max_compress_len, 0);
+            }
+            let n = snap::raw::Encoder::new().compress(data, &mut self.buf)?;
+            &self.buf[..n]
+        } else {
+            data
+        };
+
+        let mut cmd_raw = cmd as u32;
+        if compress {
+            cmd_raw |= EDemoCommands::DemIsCompressed as u32;
+        }
+
+        let mut n = write_uvarint32(&mut self.wtr, cmd_raw)?;
+        // NOTE: see read_cmd_header, negative ticks are written as u32.
+        n += write_uvarint32(&mut self.wtr, tick as u32)?;
+        n += write_uvarint32(&mut self.wtr, body.len() as u32)?;
+        self.wtr.write_all(body)?;
+
+        self.position += (n + body.len()) as u64;
+        Ok(())
+    }
+
+    /// encodes the message and writes it as a cmd. see [`DemoWriter::write_cmd`].
+    pub fn write_cmd_message<M: Message>(
+        &mut self,
+        cmd: EDemoCommands,
+        tick: i32,
+        msg: &M,
+        compress: bool,
+    ) -> Result<(), WriteCmdError> {
+        let data = msg.encode_to_vec();
+        self.write_cmd(cmd, tick, &data, compress)
+    }
+
+    #[inline]
+    fn relative_position(&self) -> i32 {
+        (self.position - self.start_position) as i32
+    }
+
+    /// writes `DEM_FileInfo`, patches the header and returns the underlying writer.
+    pub fn finish(mut self, file_info: &CDemoFileInfo) -> Result<W, WriteCmdError> {
+        let fileinfo_offset = self.relative_position();
+        self.write_cmd_message(EDemoCommands::DemFileInfo, self.last_tick, file_info, false)?;
+
+        let end = self.position;
+        self.wtr.seek(SeekFrom::Start(
+            self.start_position + DEMO_HEADER_ID_SIZE as u64,
+        ))?;
+        self.wtr.write_all(&fileinfo_offset.to_le_bytes())?;
+        self.wtr.write_all(&self.spawngroups_offset.to_le_bytes())?;
+        self.wtr.seek(SeekFrom::Start(end))?;
+        self.wtr.flush()?;
+
+        Ok(self.wtr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_write_read_roundtrip() -> anyhow::Result<()> {
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        let packet = CDemoPacket {
+            data: Some(b"Out, out, brief candle!".repeat(64)),
+        };
+        demo_writer.write_cmd_message(EDemoCommands::DemPacket, -1, &packet, false)?;
+        demo_writer.write_cmd_message(EDemoCommands::DemPacket, 300, &packet, true)?;
+        demo_writer.write_cmd(EDemoCommands::DemStop, 300, &[], false)?;
+        let file_info = CDemoFileInfo {
+            playback_ticks: Some(300),
+            ..Default::default()
+        };
+        let cursor = demo_writer.finish(&file_info)?;
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(cursor.into_inner()))?;
+        assert_eq!(demo_file.total_ticks()?, 300);
+
+        demo_file.seek(SeekFrom::Start(demo_file.start_position()))?;
+        for (tick, compressed) in [(-1, false), (300, true)] {
+            let cmd_header = demo_file.read_cmd_header()?;
+            assert_eq!(cmd_header.cmd, EDemoCommands::DemPacket);
+            assert_eq!(cmd_header.tick, tick);
+            assert_eq!(cmd_header.body_compressed, compressed);
+            let data = demo_file.read_cmd(&cmd_header)?;
+            assert_eq!(CDemoPacket::decode(data)?, packet);
+        }
+        let cmd_header = demo_file.read_cmd_header()?;
+        assert_eq!(cmd_header.cmd, EDemoCommands::DemStop);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_after_prefix() -> anyhow::Result<()> {
+        const PREFIX: &[u8] = b"not a demo";
+
+        let mut cursor = Cursor::new(Vec::new());
+        cursor.write_all(PREFIX)?;
+        let mut demo_writer = DemoWriter::start_writing(cursor)?;
+        demo_writer.write_cmd(EDemoCommands::DemSpawnGroups, -1, &[], false)?;
+        demo_writer.write_cmd_message(
+            EDemoCommands::DemPacket,
+            300,
+            &CDemoPacket::default(),
+            false,
+        )?;
+        let file_info = CDemoFileInfo {
+            playback_ticks: Some(300),
+            ..Default::default()
+        };
+        let data = demo_writer.finish(&file_info)?.into_inner();
+        assert_eq!(&data[..PREFIX.len()], PREFIX);
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(&data[PREFIX.len()..]))?;
+        assert_eq!(
+            demo_file.demo_header().spawngroups_offset as u64,
+            demo_file.start_position()
+        );
+        assert_eq!(demo_file.total_ticks()?, 300);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_demo_reader() -> anyhow::Result<()> {
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        let packet = CDemoPacket {
+            data: Some(b"Tomorrow, and tomorrow, and tomorrow".repeat(64)),
+        };
+        for tick in 0..3 {
+            demo_writer.write_cmd_message(EDemoCommands::DemPacket, tick, &packet, tick == 1)?;
+        }
+        let data = demo_writer.finish(&CDemoFileInfo::default())?.into_inner();
+
+        // NOTE: slice is Read, but not Seek.
+        let mut demo_reader = DemoReader::start_reading(data.as_slice())?;
+
+        let cmd_header = demo_reader.read_cmd_header()?;
+        assert_eq!(cmd_header.tick, 0);
+        demo_reader.skip_cmd(&cmd_header)?;
+
+        let cmd_header = demo_reader.read_cmd_header()?;
+        demo_reader.unread_cmd_header(&cmd_header)?;
+        assert!(!demo_reader.is_at_eof()?);
+        let cmd_header = demo_reader.read_cmd_header()?;
+        assert_eq!(cmd_header.tick, 1);
+        assert!(cmd_header.body_compressed);
+        assert_eq!(
+            CDemoPacket::decode(demo_reader.read_cmd(&cmd_header)?)?,
+            packet
+        );
+
+        let cmd_header = demo_reader.read_cmd_header()?;
+        assert_eq!(cmd_header.tick, 2);
+        demo_reader.skip_cmd(&cmd_header)?;
+
+        let cmd_header = demo_reader.read_cmd_header()?;
+        assert_eq!(cmd_header.cmd, EDemoCommands::DemFileInfo);
+        demo_reader.skip_cmd(&cmd_header)?;
+        assert!(demo_reader.is_at_eof()?);
+
+        Ok(())
     }
 }
@@ -26,6 +26,17 @@ pub enum ReadCmdHeaderError {
     ReadVarintError(#[from] varint::ReadVarintError),
     #[error("unknown cmd (raw {raw}; uncompressed {uncompressed})")]
     UnknownCmd { raw: u32, uncompressed: u32 },
+    /// data ended (or is damaged) within the header. as opposed to the end of the stream, which is
+    /// reported as [`ReadCmdHeaderError::IoError`] from reading the first byte.
+    #[error("cmd header is incomplete: {0}")]
+    Incomplete(Box<ReadCmdHeaderError>),
+}
+
+impl ReadCmdHeaderError {
+    /// wraps an error that occurred after the first byte of the header was read.
+    pub fn incomplete(err: impl Into<ReadCmdHeaderError>) -> Self {
+        Self::Incomplete(Box::new(err.into()))
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -34,6 +45,8 @@ pub enum ReadCmdError {
     IoError(#[from] io::Error),
     #[error(transparent)]
     DecompressError(#[from] snap::Error),
+    #[error("cmd body size ({body_size}) exceeds buffer size")]
+    BodyTooLarge { body_size: u32 },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -42,47 +55,36 @@ pub enum DecodeCmdError {
     DecodeProtobufError(#[from] prost::DecodeError),
 }
 
-// TODO: is there a way to restrict (idk if this is a correct word) DemoStream trait so that it'll
-// require seek ops to be implemented only if the underlying thing binds to io::Seek trait? thus
-// making so that Parser will not provide methods (such as run_to_tick) that need seeking
-// capabilities?
+/// error of operations that read ahead of the current position and seek back (e.g.
+/// [`DemoStream::total_ticks`]).
+#[derive(thiserror::Error, Debug)]
+pub enum ScanError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    ReadCmdHeaderError(#[from] ReadCmdHeaderError),
+    #[error(transparent)]
+    ReadCmdError(#[from] ReadCmdError),
+    #[error(transparent)]
+    DecodeCmdError(#[from] DecodeCmdError),
+}
 
+/// forward-only stream of cmds. it is enough to parse a demo from start to end (see
+/// [`crate::parser::Parser::run_to_end`]); seeking requires [`SeekableDemoStream`].
 pub trait DemoStream {
     // stream ops
     // ----
 
-    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error>;
-
-    fn stream_position(&mut self) -> Result<u64, io::Error>;
-
-    /// reimplementation of nightly [`std::io::Seek::stream_len`].
-    fn stream_len(&mut self) -> Result<u64, io::Error> {
-        let old_pos = self.stream_position()?;
-        let len = self.seek(SeekFrom::End(0))?;
-
-        // avoid seeking a third time when we were already at the end of the
-        // stream. the branch is usually way cheaper than a seek operation.
-        if old_pos != len {
-            self.seek(SeekFrom::Start(old_pos))?;
-        }
-
-        Ok(len)
-    }
-
-    fn is_at_eof(&mut self) -> Result<bool, io::Error> {
-        Ok(self.stream_position()? == self.stream_len()?)
-    }
+    fn is_at_eof(&mut self) -> Result<bool, io::Error>;
 
     // cmd header
     // ----
 
     fn read_cmd_header(&mut self) -> Result<CmdHeader, ReadCmdHeaderError>;
 
-    #[inline]
-    fn unread_cmd_header(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
-        self.seek(SeekFrom::Current(-(cmd_header.size as i64)))
-            .map(|_| ())
-    }
+    /// makes the next [`DemoStream::read_cmd_header`] return the given cmd header (which must be
+    /// the one that was read the most recently) again.
+    fn unread_cmd_header(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error>;
 
     // cmd
     // ----
@@ -113,17 +115,41 @@ pub trait DemoStream {
     // Max
     // IsCompressed (flag)
 
-    #[inline]
-    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error> {
-        self.seek(SeekFrom::Current(cmd_header.body_size as i64))
-            .map(|_| ())
-    }
+    fn skip_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), io::Error>;
 
     // other
     // ----
 
+    /// position of the first cmd.
     fn start_position(&self) -> u64;
+}
+
+/// stream that can be repositioned; needed to seek (see [`crate::parser::Parser::run_to_tick`]),
+/// to build keyframe indexes and to take snapshots.
+pub trait SeekableDemoStream: DemoStream {
+    // stream ops
+    // ----
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, io::Error>;
+
+    fn stream_position(&mut self) -> Result<u64, io::Error>;
+
+    /// reimplementation of nightly [`std::io::Seek::stream_len`].
+    fn stream_len(&mut self) -> Result<u64, io::Error> {
+        let old_pos = self.stream_position()?;
+        let len = self.seek(SeekFrom::End(0))?;
+
+        // avoid seeking a third time when we were already at the end of the
+        // stream. the branch is usually way cheaper than a seek operation.
+        if old_pos != len {
+            self.seek(SeekFrom::Start(old_pos))?;
+        }
+
+        Ok(len)
+    }
+
+    // other
+    // ----
 
-    // TODO: how not cool is it to rely on anyhow here?
-    fn total_ticks(&mut self) -> Result<i32, anyhow::Error>;
+    fn total_ticks(&mut self) -> Result<i32, ScanError>;
 }
@@ -1,12 +1,13 @@
 use std::collections::hash_map;
 use std::fmt::{self, Binary};
 use std::hash::BuildHasherDefault;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use nohash::NoHashMap;
 
 use crate::bitreader::{BitReader, BitReaderOverflowError};
 use crate::entityclasses::EntityClasses;
+use crate::entityview::StructView;
 use crate::fielddecoder::FieldDecodeContext;
 use crate::fieldpath::{self, FieldPath};
 use crate::fieldvalue::{FieldValue, FieldValueConversionError};
@@ -23,6 +24,25 @@ pub enum GetValueError {
     FieldValueConversionError(#[from] FieldValueConversionError),
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum EntityParseError {
+    #[error(transparent)]
+    BitReaderOverflowError(#[from] BitReaderOverflowError),
+    // NOTE: there's no way to tell how many bits value of undecodable field occupies, thus the
+    // rest of the update can't be parsed.
+    #[error("entity #{index} received update of undecodable field {path:?}")]
+    UndecodableField { index: i32, path: FieldPath },
+    // NOTE: errors below are caused by damaged data.
+    #[error("entity #{index} received update of non-existent field {path:?}")]
+    UnknownField { index: i32, path: FieldPath },
+    #[error("entity #{index} does not exist")]
+    UnknownEntity { index: i32 },
+    #[error("entity class #{class_id} does not exist")]
+    UnknownClass { class_id: i32 },
+    #[error("there's no serializer for entity class #{class_id}")]
+    MissingSerializer { class_id: i32 },
+}
+
 // public/const.h (adjusted)
 
 const MAX_EDICT_BITS: u32 = 14;
@@ -57,7 +77,7 @@ pub fn ehandle_to_index(handle: u32) -> i32 {
 // m_Index = iEntry | (iSerialNumber << NUM_SERIAL_NUM_SHIFT_BITS);
 
 // NOTE: rust want that coord_from_cell is never used, but that is because there are no default
-// features that indicate otherwise (both deadlock and dota2 features are not active by default).
+// features that indicate otherwise (cs2, deadlock and dota2 features are not active by default).
 #[allow(dead_code)]
 /// given a cell and an offset in that cell, reconstruct the world coord.
 ///
@@ -70,6 +90,25 @@ fn coord_from_cell(cell_width: u32, max_coord: u32, cell: u16, vec: f32) -> f32
     r
 }
 
+#[cfg(feature = "cs2")]
+mod cs2 {
+    // NOTE: cs2 uses the same cell bits and max coord as csgo did.
+    //
+    // game/shared/shareddefs.h
+    const CELL_BASEENTITY_ORIGIN_CELL_BITS: u32 = 9;
+    const CELL_WIDTH: u32 = 1 << CELL_BASEENTITY_ORIGIN_CELL_BITS;
+    // public/worldsize.h
+    const MAX_COORD_INTEGER: u32 = 16384;
+
+    /// given a cell and an offset in that cell, reconstruct the world coord.
+    pub fn coord_from_cell(cell: u16, vec: f32) -> f32 {
+        super::coord_from_cell(CELL_WIDTH, MAX_COORD_INTEGER, cell, vec)
+    }
+}
+
+#[cfg(feature = "cs2")]
+pub use cs2::coord_from_cell as cs2_coord_from_cell;
+
 #[cfg(feature = "deadlock")]
 mod deadlock {
     // in replay that i'm fiddling with (3843940_683350910.dem) CBodyComponent.m_vecY of
@@ -138,19 +177,41 @@ pub use dota2::coord_from_cell as dota2_coord_from_cell;
 pub const fn fkey_from_path(path: &[&str]) -> u64 {
     assert!(path.len() > 0, "invalid path");
 
-    let seed = fxhash::hash_bytes(path[0].as_bytes());
-    let mut hash = seed;
+    let mut hash = child_field_key(None, fxhash::hash_bytes(path[0].as_bytes()));
 
     let mut i = 1;
     while i < path.len() {
-        let part = fxhash::hash_bytes(path[i].as_bytes());
-        hash = fxhash::add_u64_to_hash(hash, part);
+        hash = child_field_key(Some(hash), fxhash::hash_bytes(path[i].as_bytes()));
         i += 1;
     }
 
     hash
 }
 
+/// key of a field with the given name hash; `parent_key` is the key of the field that contains it,
+/// `None` for top-level fields.
+///
+/// NOTE: this (along with [`element_field_key`]) is the only place that defines how field keys
+/// are derived; [`Entity::parse`], [`fkey_from_path`], [`crate::entityview`] and
+/// [`crate::flattenedserializers::FlattenedSerializerContainer::resolve_key`] rely on it.
+#[inline(always)]
+pub(crate) const fn child_field_key(parent_key: Option<u64>, name_hash: u64) -> u64 {
+    match parent_key {
+        Some(parent_key) => fxhash::add_u64_to_hash(parent_key, name_hash),
+        None => name_hash,
+    }
+}
+
+/// key of an element of a dynamic array.
+//
+// NOTE: it's sort of weird to hash index, yup. but it simplifies things when "user" builds a key
+// that has numbers / it makes it so that there's no need to check whether part of a key needs to
+// be hashed or not - just hash all parts.
+#[inline(always)]
+pub(crate) const fn element_field_key(array_key: u64, index: usize) -> u64 {
+    fxhash::add_u64_to_hash(array_key, fxhash::add_u64_to_hash(0, index as u64))
+}
+
 // csgo srcs:
 // - CL_ParseDeltaHeader in engine/client.cpp.
 // - DetermineUpdateType in engine/client.cpp
@@ -197,6 +258,130 @@ impl DeltaHeader {
     }
 }
 
+/// lifecycle event of an entity; see [`crate::parser::Visitor::on_entity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityEvent {
+    /// entity was created, or re-created when it came back into pvs.
+    ///
+    /// `from_baseline` is true when initial state of the entity was taken from the instance
+    /// baseline; it is false when there was no baseline for entity's class.
+    Created {
+        from_baseline: bool,
+    },
+    Updated,
+    /// entity left pvs. it is not removed, it keeps its last known state and may be re-created
+    /// later.
+    LeftPvs,
+    /// entity is about to be deleted. it is still accessible through
+    /// [`crate::parser::Context::entities`] while the event is being handled.
+    Deleted,
+}
+
+/// a field that was decoded during an entity update. see
+/// [`crate::parser::Visitor::on_entity`].
+#[derive(Debug, Clone)]
+pub struct UpdatedField {
+    pub key: u64,
+    pub path: FieldPath,
+    /// value that the field had before the update; `None` if the field did not exist before.
+    ///
+    /// NOTE: for freshly created entities previous values are values from the instance baseline.
+    pub prev_value: Option<FieldValue>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum FieldFilter {
+    All,
+    // NOTE: keys are sorted; lists are expected to be short, binary search is good enough.
+    Keys(Vec<u64>),
+}
+
+impl FieldFilter {
+    #[inline(always)]
+    fn contains(&self, key: &u64) -> bool {
+        match self {
+            Self::All => true,
+            Self::Keys(keys) => keys.binary_search(key).is_ok(),
+        }
+    }
+
+    fn extend(&mut self, field_keys: &[u64]) {
+        if let Self::Keys(keys) = self {
+            keys.extend_from_slice(field_keys);
+            keys.sort_unstable();
+            keys.dedup();
+        }
+    }
+}
+
+/// describes entities and fields that are of interest.
+///
+/// field values still need to be decoded (the wire format does not allow to skip them), but
+/// values of fields that are not of interest are not stored, and entities that are not of
+/// interest are not dispatched to [`crate::parser::Visitor::on_entity`].
+#[derive(Debug, Clone, Default)]
+pub struct EntityFilter {
+    serializers: NoHashMap<u64, FieldFilter>,
+    // NOTE: applies to entities whose serializers are not listed explicitly.
+    fallback: Option<FieldFilter>,
+}
+
+impl EntityFilter {
+    /// keep all fields of entities with the given serializer name hash.
+    pub fn with_serializer(mut self, serializer_name_hash: u64) -> Self {
+        self.serializers
+            .insert(serializer_name_hash, FieldFilter::All);
+        self
+    }
+
+    /// keep only the given fields (see [`fkey_from_path`]) of entities with the given serializer
+    /// name hash.
+    pub fn with_serializer_fields(mut self, serializer_name_hash: u64, field_keys: &[u64]) -> Self {
+        self.serializers
+            .entry(serializer_name_hash)
+            .or_insert_with(|| FieldFilter::Keys(Vec::with_capacity(field_keys.len())))
+            .extend(field_keys);
+        self
+    }
+
+    /// keep the given fields (see [`fkey_from_path`]) of entities of any serializer that was not
+    /// registered with [`EntityFilter::with_serializer`] or
+    /// [`EntityFilter::with_serializer_fields`].
+    pub fn with_fields(mut self, field_keys: &[u64]) -> Self {
+        self.fallback
+            .get_or_insert_with(|| FieldFilter::Keys(Vec::with_capacity(field_keys.len())))
+            .extend(field_keys);
+        self
+    }
+
+    #[inline]
+    fn field_filter(&self, serializer_name_hash: u64) -> Option<&FieldFilter> {
+        self.serializers
+            .get(&serializer_name_hash)
+            .or(self.fallback.as_ref())
+    }
+
+    #[inline]
+    pub fn is_entity_wanted(&self, serializer_name_hash: u64) -> bool {
+        self.field_filter(serializer_name_hash).is_some()
+    }
+
+    #[inline]
+    pub fn is_field_wanted(&self, serializer_name_hash: u64, field_key: u64) -> bool {
+        self.field_filter(serializer_name_hash)
+            .is_some_and(|field_filter| field_filter.contains(&field_key))
+    }
+}
+
+// NOTE: None means that the entity is not of interest at all.
+#[inline]
+fn field_filter(filter: &Option<EntityFilter>, serializer_name_hash: u64) -> Option<&FieldFilter> {
+    match filter {
+        Some(filter) => filter.field_filter(serializer_name_hash),
+        None => Some(&FieldFilter::All),
+    }
+}
+
 #[derive(Debug, Clone)]
 struct EntityField {
     #[cfg(feature = "preserve-metadata")]
@@ -209,18 +394,37 @@ struct EntityField {
 pub struct Entity {
     index: i32,
     fields: NoHashMap<u64, EntityField>,
-    serializer: Rc<FlattenedSerializer>,
+    serializer: Arc<FlattenedSerializer>,
 }
 
 impl Entity {
-    fn parse(
+    pub(crate) fn new(index: i32, serializer: Arc<FlattenedSerializer>) -> Self {
+        Self {
+            index,
+            fields: NoHashMap::with_capacity_and_hasher(
+                serializer.fields.len(),
+                BuildHasherDefault::default(),
+            ),
+            serializer,
+        }
+    }
+
+    pub(crate) fn parse(
         &mut self,
         field_decode_ctx: &mut FieldDecodeContext,
         br: &mut BitReader,
         fps: &mut [FieldPath],
-    ) -> Result<(), BitReaderOverflowError> {
+        updated_fields: &mut Vec<UpdatedField>,
+        field_filter: Option<&FieldFilter>,
+    ) -> Result<(), EntityParseError> {
         // eprintln!("-- {:?}", self.serializer.serializer_name);
 
+        let index = self.index;
+        let unknown_field = |fp: &FieldPath| EntityParseError::UnknownField {
+            index,
+            path: fp.clone(),
+        };
+
         unsafe {
             let fp_count = fieldpath::read_field_paths(br, fps);
             for i in 0..fp_count {
@@ -231,35 +435,50 @@ impl Entity {
                 // NOTE: this loop performes much better then the unrolled
                 // version of it, probably because a bunch of ifs cause a bunch
                 // of branch misses and branch missles are disasterous.
-                let mut field = self.serializer.get_child_unchecked(fp.get_unchecked(0));
-                // NOTE: field.var_name.hash is a "seed" for field_key_hash.
-                let mut field_key = field.var_name.hash;
+                let mut field = self
+                    .serializer
+                    .get_child(fp.get_unchecked(0))
+                    .ok_or_else(|| unknown_field(fp))?;
+                let mut field_key = child_field_key(None, field.var_name.hash);
                 for i in 1..=fp.last() {
                     if field.is_dynamic_array() {
-                        field = field.get_child_unchecked(0);
-                        // NOTE: it's sort of weird to hash index, yup. but it simplifies things
-                        // when "user" builds a key that has numbers / it makes it so that there's
-                        // no need to check whether part of a key needs to be hashed or not - just
-                        // hash all parts.
-                        field_key = fxhash::add_u64_to_hash(
-                            field_key,
-                            fxhash::add_u64_to_hash(0, fp.get_unchecked(i) as u64),
-                        );
+                        field = field.get_child(0).ok_or_else(|| unknown_field(fp))?;
+                        field_key = element_field_key(field_key, fp.get_unchecked(i));
                     } else {
-                        field = field.get_child_unchecked(fp.get_unchecked(i));
-                        field_key = fxhash::add_u64_to_hash(field_key, field.var_name.hash);
+                        // NOTE: undecodable fields don't have children.
+                        if field.is_undecodable() {
+                            return Err(EntityParseError::UndecodableField {
+                                index,
+                                path: fp.clone(),
+                            });
+                        }
+                        field = field
+                            .get_child(fp.get_unchecked(i))
+                            .ok_or_else(|| unknown_field(fp))?;
+                        field_key = child_field_key(Some(field_key), field.var_name.hash);
                     };
                 }
 
+                if field.is_undecodable() {
+                    return Err(EntityParseError::UndecodableField {
+                        index,
+                        path: fp.clone(),
+                    });
+                }
+
                 // eprint!("{:?} {:?} ", field.var_name, field.var_type);
 
                 let field_value = field.metadata.decoder.decode(field_decode_ctx, br);
 
                 // eprintln!(" -> {:?}", &field_value);
 
-                match self.fields.entry(field_key) {
+                if !field_filter.is_some_and(|field_filter| field_filter.contains(&field_key)) {
+                    continue;
+                }
+
+                let prev_value = match self.fields.entry(field_key) {
                     hash_map::Entry::Occupied(mut oe) => {
-                        oe.get_mut().value = field_value;
+                        Some(std::mem::replace(&mut oe.get_mut().value, field_value))
                     }
                     hash_map::Entry::Vacant(ve) => {
                         ve.insert(EntityField {
@@ -267,8 +486,15 @@ impl Entity {
                             path: fp.clone(),
                             value: field_value,
                         });
+                        None
                     }
-                }
+                };
+
+                updated_fields.push(UpdatedField {
+                    key: field_key,
+                    path: fp.clone(),
+                    prev_value,
+                });
             }
 
             // dbg!(&self.field_values);
@@ -285,6 +511,11 @@ impl Entity {
         self.fields.iter().map(|(key, ef)| (key, &ef.value))
     }
 
+    /// get a reference to the value of the field with the provided key.
+    pub fn get(&self, key: &u64) -> Option<&FieldValue> {
+        self.fields.get(key).map(|ef| &ef.value)
+    }
+
     /// get the value of the field with the provided key, and attempt to convert it.
     ///
     /// this is a variant of "getter" returns None on conversion error, intended to be used for
@@ -332,6 +563,12 @@ impl Entity {
         self.serializer.serializer_name.hash == rhs
     }
 
+    /// returns a view of entity's state as a tree of nested serializers, dynamic arrays and
+    /// values. see [`StructView`].
+    pub fn view(&self) -> StructView<'_> {
+        StructView::new(self)
+    }
+
     pub fn get_serializer_field(&self, path: &FieldPath) -> Option<&FlattenedSerializerField> {
         let first = path.get(0).and_then(|i| self.serializer.get_child(i));
         path.iter().skip(1).fold(first, |field, i| {
@@ -342,13 +579,125 @@ impl Entity {
     pub fn index(&self) -> i32 {
         self.index
     }
+
+    pub(crate) fn approx_size(&self) -> usize {
+        size_of::<(i32, Self)>() + self.fields.capacity() * size_of::<(u64, EntityField)>()
+    }
+}
+
+// serde...
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Entity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entity = SerializeEntity {
+            entity: self,
+            serializers: None,
+        };
+        serde::Serialize::serialize(&entity, serializer)
+    }
+}
+
+/// fields are keyed by dotted path (e.g. `CBodyComponent.m_cellX`) when names can be resolved
+/// (with serializers, see [`FlattenedSerializerContainer::resolve_key`], or when preserve-metadata
+/// feature is enabled), otherwise by field key.
+#[cfg(feature = "serde")]
+struct SerializeEntity<'a> {
+    entity: &'a Entity,
+    serializers: Option<&'a FlattenedSerializerContainer>,
+}
+
+#[cfg(feature = "serde")]
+impl SerializeEntity<'_> {
+    fn field_name(&self, key: u64, field: &EntityField) -> Option<String> {
+        let name = self
+            .serializers
+            .and_then(|serializers| serializers.resolve_key(&self.entity.serializer, key))
+            .map(|resolved_field| resolved_field.name);
+        // NOTE: keys of dynamic array elements can't be resolved, but paths can.
+        #[cfg(feature = "preserve-metadata")]
+        let name = name.or_else(|| field_name(&self.entity.serializer, &field.path));
+        #[cfg(not(feature = "preserve-metadata"))]
+        let _ = field;
+        name
+    }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for SerializeEntity<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{SerializeMap, SerializeStruct};
+
+        struct Fields<'a>(&'a SerializeEntity<'a>);
+
+        impl serde::Serialize for Fields<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                // NOTE: sort to get stable output.
+                let mut fields: Vec<(&u64, &EntityField)> = self.0.entity.fields.iter().collect();
+                fields.sort_unstable_by_key(|(key, _)| **key);
+
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (key, field) in fields {
+                    match self.0.field_name(*key, field) {
+                        Some(name) => map.serialize_entry(&name, &field.value)?,
+                        None => map.serialize_entry(key, &field.value)?,
+                    }
+                }
+                map.end()
+            }
+        }
+
+        let serializer_name = &self.entity.serializer.serializer_name;
+        let mut state = serializer.serialize_struct("Entity", 3)?;
+        state.serialize_field("index", &self.entity.index)?;
+        match self
+            .serializers
+            .and_then(|serializers| serializers.resolve_symbol(serializer_name.hash))
+        {
+            Some(name) => state.serialize_field("serializer", name)?,
+            None => state.serialize_field("serializer", serializer_name)?,
+        }
+        state.serialize_field("fields", &Fields(self))?;
+        state.end()
+    }
+}
+
+// NOTE: this walks the path the same way Entity::parse does.
+#[cfg(all(feature = "serde", feature = "preserve-metadata"))]
+fn field_name(serializer: &FlattenedSerializer, path: &FieldPath) -> Option<String> {
+    let mut field = serializer.get_child(path.get(0)?)?;
+    let mut name = field.var_name.str.to_string();
+    // NOTE: see FlattenedSerializerContainer::resolve_path.
+    let mut is_element = false;
+    for i in 1..=path.last() {
+        let index = path.get(i)?;
+        let is_array = !is_element && (field.is_dynamic_array() || field.is_fixed_array());
+        if is_array {
+            field = field.get_child(if field.is_fixed_array() { index } else { 0 })?;
+            name.push('.');
+            name.push_str(&index.to_string());
+        } else {
+            field = field.get_child(index)?;
+            name.push('.');
+            name.push_str(&field.var_name.str);
+        }
+        is_element = is_array;
+    }
+    Some(name)
+}
+
+// NOTE: 8192 is an arbitrary value that is double the previous one which was 4096 came out of
+// printing out count of fps collected per "run". (sort -nr can be handy)
+const FIELD_PATHS_LEN: usize = 8192;
+
 #[derive(Debug)]
 pub struct EntityContainer {
     // NOTE: hashbrown hashmap with no hash performs better then Vec.
-    entities: NoHashMap<i32, Entity>,
-    baseline_entities: NoHashMap<i32, Entity>,
+    //
+    // NOTE: entities and baseline entities are shared with clones of the container (and
+    // snapshots); they are copied on write.
+    entities: NoHashMap<i32, Arc<Entity>>,
+    baseline_entities: NoHashMap<i32, Arc<Entity>>,
 
     // NOTE: it might be tempting to introduce a "wrapper" struct, something like FieldPathReader
     // and turn read_field_path function into a method, but that's just suggar with no practical
@@ -356,7 +705,14 @@ pub struct EntityContainer {
     // atm pointer to field_paths vec is being passed arround - that's 1 level. with theoretical
     // FieldPathsReader there would be 2 levels of indirection (at least as i imagine it right
     // now).
+    //
+    // NOTE: field paths are a scratch buffer, it is allocated on first use; clones of the
+    // container do not carry it.
     field_paths: Vec<FieldPath>,
+    // NOTE: fields that were decoded during the most recent create / update. the vec is reused
+    // across updates to avoid re-allocations.
+    updated_fields: Vec<UpdatedField>,
+    filter: Option<EntityFilter>,
 }
 
 impl EntityContainer {
@@ -372,9 +728,16 @@ impl EntityContainer {
                 BuildHasherDefault::default(),
             ),
 
-            // NOTE: 8192 is an arbitrary value that is double the previous one which was 4096 came
-            // out of printing out count of fps collected per "run". (sort -nr can be handy)
-            field_paths: vec![FieldPath::default(); 8192],
+            field_paths: Vec::new(),
+            updated_fields: Vec::with_capacity(1024),
+            filter: None,
+        }
+    }
+
+    #[inline(always)]
+    fn ensure_field_paths(&mut self) {
+        if self.field_paths.is_empty() {
+            self.field_paths = vec![FieldPath::default(); FIELD_PATHS_LEN];
         }
     }
 
@@ -386,79 +749,102 @@ impl EntityContainer {
         entity_classes: &EntityClasses,
         instance_baseline: &InstanceBaseline,
         serializers: &FlattenedSerializerContainer,
-    ) -> Result<&Entity, BitReaderOverflowError> {
+    ) -> Result<(&Entity, bool), EntityParseError> {
         let class_id = br.read_ubit64(entity_classes.bits) as i32;
         let _serial = br.read_ubit64(NUM_SERIAL_NUM_BITS as usize);
         let _unknown = br.read_uvarint32();
 
-        let class_info = unsafe { entity_classes.by_id_unckecked(class_id) };
-        let serializer =
-            unsafe { serializers.by_name_hash_unckecked(class_info.network_name_hash) };
+        let class_info = entity_classes
+            .by_id(class_id)
+            .ok_or(EntityParseError::UnknownClass { class_id })?;
+        let serializer = serializers
+            .by_name_hash(class_info.network_name_hash)
+            .ok_or(EntityParseError::MissingSerializer { class_id })?;
 
-        let mut entity = match self.baseline_entities.entry(class_id) {
+        self.ensure_field_paths();
+        let (mut entity, from_baseline) = match self.baseline_entities.entry(class_id) {
             hash_map::Entry::Occupied(oe) => {
-                let mut entity = oe.get().clone();
+                let mut entity = Entity::clone(oe.get());
                 entity.index = index;
-                entity
+                (entity, true)
             }
             hash_map::Entry::Vacant(ve) => {
-                let mut entity = Entity {
-                    index,
-                    fields: NoHashMap::with_capacity_and_hasher(
-                        serializer.fields.len(),
-                        BuildHasherDefault::default(),
-                    ),
-                    serializer,
-                };
-                let baseline_data = unsafe { instance_baseline.by_id_unchecked(class_id) };
-
-                let mut baseline_br = BitReader::new(baseline_data);
-                entity.parse(field_decode_ctx, &mut baseline_br, &mut self.field_paths)?;
-                baseline_br.is_overflowed()?;
-
-                ve.insert(entity).clone()
+                let mut entity = Entity::new(index, serializer);
+                match instance_baseline.by_id(class_id) {
+                    Some(baseline_data) => {
+                        let mut baseline_br = BitReader::new(baseline_data);
+                        entity.parse(
+                            field_decode_ctx,
+                            &mut baseline_br,
+                            &mut self.field_paths,
+                            &mut self.updated_fields,
+                            field_filter(&self.filter, class_info.network_name_hash),
+                        )?;
+                        baseline_br.is_overflowed()?;
+
+                        (Entity::clone(ve.insert(Arc::new(entity))), true)
+                    }
+                    // NOTE: baseline-less entity is not cached; baseline for its class may arrive
+                    // later.
+                    None => (entity, false),
+                }
             }
         };
 
-        entity.parse(field_decode_ctx, br, &mut self.field_paths)?;
-
-        self.entities.insert(index, entity);
-        // SAFETY: the entity was just inserted ^, it's safe.
-        Ok(unsafe { self.entities.get(&index).unwrap_unchecked() })
+        // NOTE: baseline fields are not interesting; only fields that were sent with the entity
+        // itself are.
+        self.updated_fields.clear();
+        entity.parse(
+            field_decode_ctx,
+            br,
+            &mut self.field_paths,
+            &mut self.updated_fields,
+            field_filter(&self.filter, class_info.network_name_hash),
+        )?;
+
+        let entity = match self.entities.entry(index) {
+            hash_map::Entry::Occupied(mut oe) => {
+                oe.insert(Arc::new(entity));
+                oe.into_mut()
+            }
+            hash_map::Entry::Vacant(ve) => ve.insert(Arc::new(entity)),
+        };
+        Ok((entity.as_ref(), from_baseline))
     }
 
-    // SAFETY: if it's being deleted menas that it was created, riiight? but
-    // there's a risk (that only should exist if replay is corrupted).
+    // NOTE: if it's being deleted it must have been created; entity that does not exist means
+    // that the replay is damaged.
     #[inline]
-    pub(crate) unsafe fn handle_delete_unchecked(&mut self, index: i32) -> Entity {
-        let entity = self.entities.remove(&index);
-
-        debug_assert!(
-            entity.is_some(),
-            "tried to delete non-existent entity #{index}"
-        );
-
-        entity.unwrap_unchecked()
+    pub(crate) fn handle_delete(&mut self, index: i32) -> Result<(), EntityParseError> {
+        self.entities
+            .remove(&index)
+            .map(|_| ())
+            .ok_or(EntityParseError::UnknownEntity { index })
     }
 
-    // SAFETY: if entity was ever created, and not deleted, it can be updated!
-    // but there's a risk (that only should exist if replay is corrupted).
+    // NOTE: if entity was ever created, and not deleted, it can be updated; see handle_delete.
     #[inline]
-    pub(crate) unsafe fn handle_update_unchecked(
+    pub(crate) fn handle_update(
         &mut self,
         index: i32,
         field_decode_ctx: &mut FieldDecodeContext,
         br: &mut BitReader,
-    ) -> Result<&Entity, BitReaderOverflowError> {
-        let entity = self.entities.get_mut(&index);
-
-        debug_assert!(
-            entity.is_some(),
-            "tried to update non-existent entity #{index}"
-        );
-
-        let entity = entity.unwrap_unchecked();
-        entity.parse(field_decode_ctx, br, &mut self.field_paths)?;
+    ) -> Result<&Entity, EntityParseError> {
+        self.ensure_field_paths();
+        let entity = self
+            .entities
+            .get_mut(&index)
+            .ok_or(EntityParseError::UnknownEntity { index })?;
+
+        let entity = Arc::make_mut(entity);
+        self.updated_fields.clear();
+        entity.parse(
+            field_decode_ctx,
+            br,
+            &mut self.field_paths,
+            &mut self.updated_fields,
+            field_filter(&self.filter, entity.serializer.serializer_name.hash),
+        )?;
         Ok(entity)
     }
 
@@ -466,19 +852,62 @@ impl EntityContainer {
     // ----------
 
     pub fn iter(&self) -> impl Iterator<Item = (&i32, &Entity)> {
-        self.entities.iter()
+        self.entities
+            .iter()
+            .map(|(index, entity)| (index, entity.as_ref()))
     }
 
     pub fn get(&self, index: &i32) -> Option<&Entity> {
-        self.entities.get(index)
+        self.entities.get(index).map(Arc::as_ref)
+    }
+
+    /// fields that were decoded during the most recent entity create or update.
+    pub fn updated_fields(&self) -> &[UpdatedField] {
+        &self.updated_fields
     }
 
     pub fn iter_baselines(&self) -> impl Iterator<Item = (&i32, &Entity)> {
-        self.baseline_entities.iter()
+        self.baseline_entities
+            .iter()
+            .map(|(index, entity)| (index, entity.as_ref()))
     }
 
     pub fn get_baseline(&self, index: &i32) -> Option<&Entity> {
-        self.baseline_entities.get(index)
+        self.baseline_entities.get(index).map(Arc::as_ref)
+    }
+
+    /// sets the filter that determines which entities and fields are stored. baseline entities
+    /// are dropped because they were parsed with the previous filter.
+    ///
+    /// NOTE: filter is expected to be set before parsing starts; entities that exist already are
+    /// not affected.
+    pub(crate) fn set_filter(&mut self, filter: Option<EntityFilter>) {
+        self.filter = filter;
+        self.baseline_entities.clear();
+    }
+
+    pub fn filter(&self) -> Option<&EntityFilter> {
+        self.filter.as_ref()
+    }
+
+    /// returns false if the entity was filtered out by [`EntityFilter`].
+    #[inline]
+    pub fn is_wanted(&self, entity: &Entity) -> bool {
+        match self.filter.as_ref() {
+            Some(filter) => filter.is_entity_wanted(entity.serializer.serializer_name.hash),
+            None => true,
+        }
+    }
+
+    pub(crate) fn clone_entities(&self) -> NoHashMap<i32, Arc<Entity>> {
+        self.entities.clone()
+    }
+
+    /// replaces entities with ones that were copied with [`EntityContainer::clone_entities`].
+    /// baseline entities are dropped, they'll be re-created from instance baseline.
+    pub(crate) fn restore_entities(&mut self, entities: &NoHashMap<i32, Arc<Entity>>) {
+        self.entities.clone_from(entities);
+        self.baseline_entities.clear();
     }
 
     // clear clears underlying storage, but this has no effect on the allocated
@@ -492,3 +921,86 @@ impl EntityContainer {
         self.entities.is_empty()
     }
 }
+
+// NOTE: this is not derived because scratch buffers are not meant to be copied. updated fields
+// describe the most recent update as it is being dispatched; they are not copied either.
+impl Clone for EntityContainer {
+    fn clone(&self) -> Self {
+        Self {
+            entities: self.entities.clone(),
+            baseline_entities: self.baseline_entities.clone(),
+            field_paths: Vec::new(),
+            updated_fields: Vec::new(),
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+// NOTE: entities are serialized as a sequence ordered by index.
+#[cfg(feature = "serde")]
+impl serde::Serialize for EntityContainer {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entities = SerializeEntities {
+            entities: self,
+            serializers: None,
+        };
+        serde::Serialize::serialize(&entities, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) struct SerializeEntities<'a> {
+    pub(crate) entities: &'a EntityContainer,
+    pub(crate) serializers: Option<&'a FlattenedSerializerContainer>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SerializeEntities<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entities: Vec<&Entity> = self.entities.entities.values().map(Arc::as_ref).collect();
+        entities.sort_unstable_by_key(|entity| entity.index);
+        serializer.collect_seq(entities.into_iter().map(|entity| SerializeEntity {
+            entity,
+            serializers: self.serializers,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clone() -> anyhow::Result<()> {
+        let serializer = Arc::new(FlattenedSerializer::default());
+        let mut entities = EntityContainer::new();
+        entities.ensure_field_paths();
+        entities
+            .entities
+            .insert(1, Arc::new(Entity::new(1, serializer.clone())));
+        entities
+            .baseline_entities
+            .insert(0, Arc::new(Entity::new(0, serializer)));
+
+        let clone = entities.clone();
+        assert_eq!(entities.field_paths.len(), FIELD_PATHS_LEN);
+        assert_eq!(clone.field_paths.capacity(), 0);
+
+        let (Some(entity), Some(cloned_entity)) =
+            (entities.entities.get(&1), clone.entities.get(&1))
+        else {
+            anyhow::bail!("entity is missing");
+        };
+        assert!(Arc::ptr_eq(entity, cloned_entity));
+
+        let (Some(baseline), Some(cloned_baseline)) = (
+            entities.baseline_entities.get(&0),
+            clone.baseline_entities.get(&0),
+        ) else {
+            anyhow::bail!("baseline entity is missing");
+        };
+        assert!(Arc::ptr_eq(baseline, cloned_baseline));
+
+        Ok(())
+    }
+}
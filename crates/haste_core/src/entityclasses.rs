@@ -40,7 +40,7 @@ impl EntityClasses {
     }
 
     #[inline(always)]
-    pub unsafe fn by_id_unckecked(&self, class_id: i32) -> &ClassInfo {
-        self.class_infos.get_unchecked(class_id as usize)
+    pub fn by_id(&self, class_id: i32) -> Option<&ClassInfo> {
+        self.class_infos.get(class_id as usize)
     }
 }
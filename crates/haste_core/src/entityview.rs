@@ -0,0 +1,293 @@
+use crate::entities::{Entity, child_field_key, element_field_key};
+use crate::fieldvalue::FieldValue;
+use crate::flattenedserializers::{FlattenedSerializer, FlattenedSerializerField};
+
+// NOTE: entity stores its state as a flat map of field keys to values. keys are hashes that are
+// chained along the field path (see entities::child_field_key), which means that keys of nested
+// fields and of array elements can be computed from the serializer without any field paths. views
+// below do exactly that; they do not copy anything.
+
+/// node of entity's state tree.
+#[derive(Debug, Clone, Copy)]
+pub enum FieldNode<'a> {
+    Value(&'a FieldValue),
+    /// entity itself, nested serializer (component, pointer, fixed array) or an element of an
+    /// array of serializers.
+    Struct(StructView<'a>),
+    /// dynamic array (e.g. `CNetworkUtlVectorBase< ... >`, `CUtlVectorEmbeddedNetworkVar< ...
+    /// >`).
+    Array(ArrayView<'a>),
+}
+
+impl<'a> FieldNode<'a> {
+    fn new(entity: &'a Entity, field: &'a FlattenedSerializerField, key: u64) -> Option<Self> {
+        if field.is_dynamic_array() {
+            return Some(Self::Array(ArrayView { entity, field, key }));
+        }
+
+        if let Some(field_serializer) = field.field_serializer.as_ref() {
+            return Some(Self::Struct(StructView {
+                entity,
+                serializer: field_serializer,
+                key: Some(key),
+            }));
+        }
+
+        entity.get(&key).map(Self::Value)
+    }
+
+    #[inline]
+    pub fn as_value(&self) -> Option<&'a FieldValue> {
+        match self {
+            Self::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_struct(&self) -> Option<&StructView<'a>> {
+        match self {
+            Self::Struct(struct_view) => Some(struct_view),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_array(&self) -> Option<&ArrayView<'a>> {
+        match self {
+            Self::Array(array_view) => Some(array_view),
+            _ => None,
+        }
+    }
+}
+
+/// view of fields that belong to a serializer. see [`Entity::view`].
+///
+/// NOTE: elements of fixed arrays share the same key (that is how field keys are computed), thus
+/// only one of them is accessible.
+#[derive(Debug, Clone, Copy)]
+pub struct StructView<'a> {
+    entity: &'a Entity,
+    serializer: &'a FlattenedSerializer,
+    // NOTE: None for the entity itself.
+    key: Option<u64>,
+}
+
+impl<'a> StructView<'a> {
+    pub(crate) fn new(entity: &'a Entity) -> Self {
+        Self {
+            entity,
+            serializer: entity.serializer(),
+            key: None,
+        }
+    }
+
+    #[inline]
+    pub fn serializer(&self) -> &'a FlattenedSerializer {
+        self.serializer
+    }
+
+    /// value of the struct itself; pointers carry a bool that indicates whether they are set.
+    pub fn value(&self) -> Option<&'a FieldValue> {
+        self.key.and_then(|key| self.entity.get(&key))
+    }
+
+    /// returns field with the given name hash (`fxhash::hash_bytes(b"m_iHealth")`).
+    pub fn get_by_hash(&self, name_hash: u64) -> Option<FieldNode<'a>> {
+        let field = self
+            .serializer
+            .fields
+            .iter()
+            .find(|field| field.var_name.hash == name_hash)?;
+        let key = child_field_key(self.key, field.var_name.hash);
+        FieldNode::new(self.entity, field, key)
+    }
+
+    /// returns field with the given name (e.g. `m_iHealth`).
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<FieldNode<'a>> {
+        self.get_by_hash(fxhash::hash_bytes(name.as_bytes()))
+    }
+
+    /// iterates over fields that have values (or are structs / arrays).
+    pub fn iter(&self) -> impl Iterator<Item = (&'a FlattenedSerializerField, FieldNode<'a>)> {
+        let entity = self.entity;
+        let key = self.key;
+        self.serializer.fields.iter().filter_map(move |field| {
+            let field = field.as_ref();
+            FieldNode::new(entity, field, child_field_key(key, field.var_name.hash))
+                .map(|node| (field, node))
+        })
+    }
+}
+
+/// view of a dynamic array.
+#[derive(Debug, Clone, Copy)]
+pub struct ArrayView<'a> {
+    entity: &'a Entity,
+    field: &'a FlattenedSerializerField,
+    key: u64,
+}
+
+impl<'a> ArrayView<'a> {
+    #[inline]
+    pub fn field(&self) -> &'a FlattenedSerializerField {
+        self.field
+    }
+
+    /// current length of the array.
+    ///
+    /// NOTE: elements beyond the length may still be stored in the entity (arrays can shrink);
+    /// they are garbage and are not accessible through the view.
+    pub fn len(&self) -> usize {
+        self.entity.get_value::<u64>(&self.key).unwrap_or_default() as usize
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<FieldNode<'a>> {
+        if index >= self.len() {
+            return None;
+        }
+        let element = self.field.get_child(0)?;
+        FieldNode::new(self.entity, element, element_field_key(self.key, index))
+    }
+
+    /// iterates over elements that have values, along with their indices.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, FieldNode<'a>)> {
+        let array_view = *self;
+        (0..self.len()).filter_map(move |index| array_view.get(index).map(|node| (index, node)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::bitreader::BitReader;
+    use crate::entities::{EntityParseError, FieldFilter, UpdatedField};
+    use crate::fielddecoder::FieldDecodeContext;
+    use crate::fieldpath::{self, FieldPath};
+    use crate::flattenedserializers::FlattenedSerializerContainer;
+    use crate::testutil::{self, BitWriter};
+
+    // CFoo { m_iHealth: int32, m_inner: CInner { m_nX: int32 }, m_vec: vector of int32 }
+    fn make_serializer() -> anyhow::Result<Arc<FlattenedSerializer>> {
+        let serializers = FlattenedSerializerContainer::parse(testutil::make_send_tables(&[
+            ("CInner", &[("m_nX", "int32", None)]),
+            (
+                "CFoo",
+                &[
+                    ("m_iHealth", "int32", None),
+                    ("m_inner", "CInner", None),
+                    ("m_vec", "CNetworkUtlVectorBase< int32 >", None),
+                ],
+            ),
+        ]))?;
+        serializers
+            .by_name_hash(fxhash::hash_bytes(b"CFoo"))
+            .ok_or_else(|| anyhow::anyhow!("serializer is missing"))
+    }
+
+    fn make_entity_data() -> Vec<u8> {
+        let mut bw = BitWriter::default();
+        for op_index in [
+            fieldpath::FIELDOP_PLUS_ONE,                            // [0]
+            fieldpath::FIELDOP_PLUS_ONE,                            // [1]
+            fieldpath::FIELDOP_PUSH_ONE_LEFT_DELTA_ZERO_RIGHT_ZERO, // [1, 0]
+            fieldpath::FIELDOP_POP_ONE_PLUS_ONE,                    // [2]
+            fieldpath::FIELDOP_PUSH_ONE_LEFT_DELTA_ZERO_RIGHT_ZERO, // [2, 0]
+            fieldpath::FIELDOP_PLUS_ONE,                            // [2, 1]
+            fieldpath::FIELDOP_FIELD_PATH_ENCODE_FINISH,
+        ] {
+            bw.write_field_op(op_index);
+        }
+        // m_iHealth = 42, m_inner.m_nX = -3, m_vec = [7, 9].
+        bw.write_varint(42);
+        bw.write_varint(-3);
+        bw.write_uvarint(2);
+        bw.write_varint(7);
+        bw.write_varint(9);
+        bw.into_bytes()
+    }
+
+    fn key_of(updated_fields: &[UpdatedField], path: &[u8]) -> anyhow::Result<u64> {
+        updated_fields
+            .iter()
+            .find(|updated_field| updated_field.path.iter().eq(path.iter()))
+            .map(|updated_field| updated_field.key)
+            .ok_or_else(|| anyhow::anyhow!("field {:?} was not updated", path))
+    }
+
+    #[test]
+    fn test_view_matches_parsed_keys() -> anyhow::Result<()> {
+        let data = make_entity_data();
+        let mut entity = Entity::new(1, make_serializer()?);
+        let mut updated_fields = Vec::new();
+        let mut br = BitReader::new(&data);
+        entity.parse(
+            &mut FieldDecodeContext::default(),
+            &mut br,
+            &mut vec![FieldPath::default(); 8],
+            &mut updated_fields,
+            Some(&FieldFilter::All),
+        )?;
+        br.is_overflowed().map_err(EntityParseError::from)?;
+        assert_eq!(updated_fields.len(), 5);
+
+        let expect_value = |node: Option<FieldNode<'_>>, path: &[u8]| -> anyhow::Result<()> {
+            let value = node
+                .and_then(|node| node.as_value())
+                .ok_or_else(|| anyhow::anyhow!("field {:?} is not a value", path))?;
+            let parsed = entity.get(&key_of(&updated_fields, path)?);
+            assert!(parsed.is_some_and(|parsed| std::ptr::eq(parsed, value)));
+            Ok(())
+        };
+
+        let view = entity.view();
+        expect_value(view.get("m_iHealth"), &[0])?;
+        assert_eq!(
+            entity.get_value::<i64>(&key_of(&updated_fields, &[0])?),
+            Some(42)
+        );
+
+        let inner = view.get("m_inner");
+        let inner = inner
+            .as_ref()
+            .and_then(|node| node.as_struct())
+            .ok_or_else(|| anyhow::anyhow!("m_inner is not a struct"))?;
+        expect_value(inner.get("m_nX"), &[1, 0])?;
+        assert_eq!(
+            entity.get_value::<i64>(&key_of(&updated_fields, &[1, 0])?),
+            Some(-3)
+        );
+
+        let vec = view.get("m_vec");
+        let vec = vec
+            .as_ref()
+            .and_then(|node| node.as_array())
+            .ok_or_else(|| anyhow::anyhow!("m_vec is not an array"))?;
+        assert_eq!(vec.len(), 2);
+        assert_eq!(
+            entity.get_value::<u64>(&key_of(&updated_fields, &[2])?),
+            Some(2)
+        );
+        expect_value(vec.get(0), &[2, 0])?;
+        expect_value(vec.get(1), &[2, 1])?;
+        assert!(vec.get(2).is_none());
+        let elements: Vec<Option<i64>> = vec
+            .iter()
+            .map(|(_, node)| {
+                node.as_value()
+                    .and_then(|value| value.clone().try_into().ok())
+            })
+            .collect();
+        assert_eq!(elements, vec![Some(7), Some(9)]);
+
+        Ok(())
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt::Debug;
 use dyn_clone::DynClone;
 
 use crate::bitreader::BitReader;
-use crate::fieldvalue::FieldValue;
+use crate::fieldvalue::{FieldValue, FieldValueKind};
 use crate::flattenedserializers::FlattenedSerializerField;
 use crate::quantizedfloat::{QuantizedFloat, QuantizedFloatError};
 
@@ -14,6 +14,8 @@ use crate::quantizedfloat::{QuantizedFloat, QuantizedFloatError};
 pub enum FieldDecoderConstructionError {
     #[error(transparent)]
     QuantizedFloatError(#[from] QuantizedFloatError),
+    #[error("unknown var encoder")]
+    UnknownVarEncoder,
 }
 
 // ----
@@ -57,7 +59,11 @@ impl Default for FieldDecodeContext {
 
 // TODO(blukai): try to not box internal decoders (for example u64).
 
-pub(crate) trait FieldDecode: DynClone + Debug {
+// NOTE: decoders are stateless (all state lives in FieldDecodeContext); Send + Sync bounds allow
+// serializers to be shared across threads.
+pub(crate) trait FieldDecode: DynClone + Debug + Send + Sync {
+    /// kind of values produced by [`FieldDecode::decode`]; `None` for [`InvalidDecoder`].
+    fn value_kind(&self) -> Option<FieldValueKind>;
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue;
 }
 
@@ -68,6 +74,10 @@ dyn_clone::clone_trait_object!(FieldDecode);
 pub(crate) struct InvalidDecoder;
 
 impl FieldDecode for InvalidDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        None
+    }
+
     #[cold]
     fn decode(&self, _ctx: &mut FieldDecodeContext, _br: &mut BitReader) -> FieldValue {
         unreachable!()
@@ -76,7 +86,7 @@ impl FieldDecode for InvalidDecoder {
 
 // ----
 
-trait InternalFieldDecode<T>: DynClone + Debug {
+trait InternalFieldDecode<T>: DynClone + Debug + Send + Sync {
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> T;
 }
 
@@ -88,6 +98,10 @@ dyn_clone::clone_trait_object!(<T> InternalFieldDecode<T>);
 pub(crate) struct I64Decoder;
 
 impl FieldDecode for I64Decoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::I64)
+    }
+
     fn decode(&self, _ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         FieldValue::I64(br.read_varint64())
     }
@@ -99,6 +113,10 @@ impl FieldDecode for I64Decoder {
 struct InternalU64Decoder;
 
 impl FieldDecode for InternalU64Decoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::U64)
+    }
+
     fn decode(&self, _ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         FieldValue::U64(br.read_uvarint64())
     }
@@ -108,6 +126,10 @@ impl FieldDecode for InternalU64Decoder {
 struct InternalU64Fixed64Decoder;
 
 impl FieldDecode for InternalU64Fixed64Decoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::U64)
+    }
+
     fn decode(&self, _ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         let mut buf = [0u8; 8];
         br.read_bytes(&mut buf);
@@ -145,6 +167,10 @@ impl U64Decoder {
 }
 
 impl FieldDecode for U64Decoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::U64)
+    }
+
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         self.decoder.decode(ctx, br)
     }
@@ -156,6 +182,10 @@ impl FieldDecode for U64Decoder {
 pub(crate) struct BoolDecoder;
 
 impl FieldDecode for BoolDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::Bool)
+    }
+
     fn decode(&self, _ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         FieldValue::Bool(br.read_bool())
     }
@@ -167,6 +197,10 @@ impl FieldDecode for BoolDecoder {
 pub(crate) struct StringDecoder;
 
 impl FieldDecode for StringDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::String)
+    }
+
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         // NOTE: string_buf must be cleared after use.
         assert!(ctx.string_buf.is_empty());
@@ -273,7 +307,7 @@ impl InternalF32Decoder {
                         decoder: Box::<InternalF32NormalDecoder>::default(),
                     });
                 }
-                _ => unimplemented!("{:?}", var_encoder),
+                _ => return Err(FieldDecoderConstructionError::UnknownVarEncoder),
             }
         }
 
@@ -316,6 +350,10 @@ impl F32Decoder {
 }
 
 impl FieldDecode for F32Decoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::F32)
+    }
+
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         FieldValue::F32(self.decoder.decode(ctx, br))
     }
@@ -329,6 +367,10 @@ struct InternalVector3DefaultDecoder {
 }
 
 impl FieldDecode for InternalVector3DefaultDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::Vector3)
+    }
+
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         let vec3 = [
             self.decoder.decode(ctx, br),
@@ -343,6 +385,10 @@ impl FieldDecode for InternalVector3DefaultDecoder {
 struct InternalVector3NormalDecoder;
 
 impl FieldDecode for InternalVector3NormalDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::Vector3)
+    }
+
     fn decode(&self, _ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         FieldValue::Vector3(br.read_bitvec3normal())
     }
@@ -373,6 +419,10 @@ impl Vector3Decoder {
 }
 
 impl FieldDecode for Vector3Decoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::Vector3)
+    }
+
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         self.decoder.decode(ctx, br)
     }
@@ -397,6 +447,10 @@ impl Vector2Decoder {
 }
 
 impl FieldDecode for Vector2Decoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::Vector2)
+    }
+
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         let vec2 = [self.decoder.decode(ctx, br), self.decoder.decode(ctx, br)];
         FieldValue::Vector2(vec2)
@@ -422,6 +476,10 @@ impl Vector4Decoder {
 }
 
 impl FieldDecode for Vector4Decoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::Vector4)
+    }
+
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         let vec4 = [
             self.decoder.decode(ctx, br),
@@ -441,6 +499,10 @@ struct InternalQAnglePitchYawDecoder {
 }
 
 impl FieldDecode for InternalQAnglePitchYawDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::QAngle)
+    }
+
     fn decode(&self, _ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         let vec3 = [
             br.read_bitangle(self.bit_count),
@@ -455,6 +517,10 @@ impl FieldDecode for InternalQAnglePitchYawDecoder {
 struct InternalQAngleNoBitCountDecoder;
 
 impl FieldDecode for InternalQAngleNoBitCountDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::QAngle)
+    }
+
     fn decode(&self, _ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         FieldValue::QAngle(br.read_bitvec3coord())
     }
@@ -464,6 +530,10 @@ impl FieldDecode for InternalQAngleNoBitCountDecoder {
 struct InternalQAnglePreciseDecoder;
 
 impl FieldDecode for InternalQAnglePreciseDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::QAngle)
+    }
+
     fn decode(&self, _ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         let mut vec3 = [0f32; 3];
 
@@ -491,6 +561,10 @@ struct InternalQAngleBitCountDecoder {
 }
 
 impl FieldDecode for InternalQAngleBitCountDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::QAngle)
+    }
+
     fn decode(&self, _ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         let vec3 = [
             br.read_bitangle(self.bit_count),
@@ -507,20 +581,22 @@ pub(crate) struct QAngleDecoder {
 }
 
 impl QAngleDecoder {
-    pub(crate) fn new(field: &FlattenedSerializerField) -> Self {
+    pub(crate) fn new(
+        field: &FlattenedSerializerField,
+    ) -> Result<Self, FieldDecoderConstructionError> {
         let bit_count = field.bit_count.unwrap_or_default() as usize;
 
         if let Some(var_encoder) = field.var_encoder.as_ref() {
             match var_encoder.hash {
                 hash if hash == fxhash::hash_bytes(b"qangle_pitch_yaw") => {
-                    return Self {
+                    return Ok(Self {
                         decoder: Box::new(InternalQAnglePitchYawDecoder { bit_count }),
-                    };
+                    });
                 }
                 hash if hash == fxhash::hash_bytes(b"qangle_precise") => {
-                    return Self {
+                    return Ok(Self {
                         decoder: Box::<InternalQAnglePreciseDecoder>::default(),
-                    };
+                    });
                 }
 
                 hash if hash == fxhash::hash_bytes(b"qangle") => {}
@@ -528,23 +604,27 @@ impl QAngleDecoder {
                 // name in dota 2 replay from 2018.
                 hash if hash == fxhash::hash_bytes(b"QAngle") => {}
 
-                _ => unimplemented!("{:?}", var_encoder),
+                _ => return Err(FieldDecoderConstructionError::UnknownVarEncoder),
             }
         }
 
         if bit_count == 0 {
-            return Self {
+            return Ok(Self {
                 decoder: Box::<InternalQAngleNoBitCountDecoder>::default(),
-            };
+            });
         }
 
-        Self {
+        Ok(Self {
             decoder: Box::new(InternalQAngleBitCountDecoder { bit_count }),
-        }
+        })
     }
 }
 
 impl FieldDecode for QAngleDecoder {
+    fn value_kind(&self) -> Option<FieldValueKind> {
+        Some(FieldValueKind::QAngle)
+    }
+
     fn decode(&self, ctx: &mut FieldDecodeContext, br: &mut BitReader) -> FieldValue {
         self.decoder.decode(ctx, br)
     }
@@ -65,6 +65,11 @@ pub(crate) enum FieldSpecialDescriptor {
     // (and deserialized value of the pointer field (/bool) must not be
     // stored)).
     Pointer,
+
+    /// var type (or encoder) is not known, thus there's no way to tell how many bits field's
+    /// value occupies. see
+    /// [`crate::flattenedserializers::FlattenedSerializerContainer::parse_tolerant`].
+    Undecodable,
 }
 
 impl FieldSpecialDescriptor {
@@ -81,6 +86,9 @@ impl FieldSpecialDescriptor {
 pub(crate) struct FieldMetadata {
     pub(crate) special_descriptor: Option<FieldSpecialDescriptor>,
     pub(crate) decoder: Box<dyn FieldDecode>,
+    // NOTE: true if var type (or a part of it, e.g. template argument) is not known and values are
+    // assumed to be unsigned integers; this is correct for enums, but may not be for other types.
+    pub(crate) var_type_assumed: bool,
 }
 
 impl Default for FieldMetadata {
@@ -89,6 +97,17 @@ impl Default for FieldMetadata {
         Self {
             special_descriptor: None,
             decoder: Box::<InvalidDecoder>::default(),
+            var_type_assumed: false,
+        }
+    }
+}
+
+impl FieldMetadata {
+    #[inline]
+    pub(crate) fn undecodable() -> Self {
+        Self {
+            special_descriptor: Some(FieldSpecialDescriptor::Undecodable),
+            ..Default::default()
         }
     }
 }
@@ -101,14 +120,14 @@ fn visit_ident(
     macro_rules! non_special {
         ($decoder:ident) => {
             Ok(FieldMetadata {
-                special_descriptor: None,
                 decoder: Box::<$decoder>::default(),
+                ..Default::default()
             })
         };
         ($decoder:expr) => {
             Ok(FieldMetadata {
-                special_descriptor: None,
                 decoder: Box::new($decoder),
+                ..Default::default()
             })
         };
     }
@@ -118,6 +137,7 @@ fn visit_ident(
             Ok(FieldMetadata {
                 special_descriptor: Some(FieldSpecialDescriptor::Pointer),
                 decoder: Box::<BoolDecoder>::default(),
+                ..Default::default()
             })
         };
     }
@@ -146,12 +166,16 @@ fn visit_ident(
         "C_BodyComponentBaseAnimating" => pointer!(),
         "C_BodyComponentBaseAnimatingOverlay" => pointer!(),
         "CPhysicsComponent" => pointer!(),
+        // https://github.com/SteamDatabase/GameTracking-CS2/blob/master/game/core/tools/demoinfo2/demoinfo2.txt
+        "CBodyComponentBaseAnimGraph" => pointer!(),
 
         // other custom types
         "CUtlSymbolLarge" => non_special!(StringDecoder),
         "CUtlString" => non_special!(StringDecoder),
+        // cs2
+        "CGlobalSymbol" => non_special!(StringDecoder),
         // public/mathlib/vector.h
-        "QAngle" => non_special!(QAngleDecoder::new(field)),
+        "QAngle" => non_special!(QAngleDecoder::new(field)?),
         // NOTE: not all quantized floats are actually quantized (if bit_count is 0 or 32 it's
         // not!) F32Decoder will determine which kind of f32 decoder to use.
         "CNetworkedQuantizedFloat" => non_special!(F32Decoder::new(field)?),
@@ -167,17 +191,30 @@ fn visit_ident(
         "m_SpeechBubbles" => Ok(FieldMetadata {
             special_descriptor: Some(FieldSpecialDescriptor::DynamicSerializerArray),
             decoder: Box::<U64Decoder>::default(),
+            ..Default::default()
         }),
         // https://github.com/SteamDatabase/GameTracking-CS2/blob/6b3bf6ad44266e3ee4440a0b9b2fee1268812840/game/core/tools/demoinfo2/demoinfo2.txt#L155C83-L155C111
         "DOTA_CombatLogQueryProgress" => Ok(FieldMetadata {
             special_descriptor: Some(FieldSpecialDescriptor::DynamicSerializerArray),
             decoder: Box::<U64Decoder>::default(),
+            ..Default::default()
         }),
 
+        // unsigned integers, handles, tokens, etc.
+        "uint8" | "uint16" | "uint32" | "uint64" => non_special!(U64Decoder::new(field)),
+        "CHandle" | "CStrongHandle" | "CEntityHandle" => non_special!(U64Decoder::new(field)),
+        "CUtlStringToken" | "Color" | "GameTick_t" | "HSequence" => {
+            non_special!(U64Decoder::new(field))
+        }
+
         // default
+        //
+        // NOTE: most of unknown idents are enums; idents of fields that have serializers are names
+        // of those serializers.
         _ => Ok(FieldMetadata {
-            special_descriptor: None,
             decoder: Box::new(U64Decoder::new(field)),
+            var_type_assumed: field.field_serializer_name.is_none(),
+            ..Default::default()
         }),
     }
 }
@@ -200,6 +237,7 @@ fn visit_template(
             return Ok(FieldMetadata {
                 special_descriptor: Some(FieldSpecialDescriptor::DynamicSerializerArray),
                 decoder: Box::<U64Decoder>::default(),
+                ..Default::default()
             });
         }
 
@@ -208,6 +246,7 @@ fn visit_template(
                 decoder: field_metadata.decoder,
             }),
             decoder: Box::<U64Decoder>::default(),
+            var_type_assumed: field_metadata.var_type_assumed,
         });
     }
 
@@ -223,8 +262,8 @@ fn visit_array(
     if let Expr::Ident(ident) = expr {
         if ident == "char" {
             return Ok(FieldMetadata {
-                special_descriptor: None,
                 decoder: Box::<StringDecoder>::default(),
+                ..Default::default()
             });
         }
     }
@@ -247,6 +286,7 @@ fn visit_array(
     visit_any(expr, field).map(|field_metadata| FieldMetadata {
         special_descriptor: Some(FieldSpecialDescriptor::FixedArray { length }),
         decoder: field_metadata.decoder,
+        var_type_assumed: field_metadata.var_type_assumed,
     })
 }
 
@@ -255,6 +295,7 @@ fn visit_pointer() -> Result<FieldMetadata, FieldMetadataError> {
     Ok(FieldMetadata {
         special_descriptor: Some(FieldSpecialDescriptor::Pointer),
         decoder: Box::<BoolDecoder>::default(),
+        ..Default::default()
     })
 }
 
@@ -681,3 +681,38 @@ pub(crate) fn read_field_paths(br: &mut BitReader, fps: &mut [FieldPath]) -> usi
         };
     }
 }
+
+// NOTE: indices of ops in FIELDOP_DESCRIPTORS; see write_field_op.
+#[cfg(test)]
+pub(crate) const FIELDOP_PLUS_ONE: usize = 0;
+#[cfg(test)]
+pub(crate) const FIELDOP_PUSH_ONE_LEFT_DELTA_ZERO_RIGHT_ZERO: usize = 5;
+#[cfg(test)]
+pub(crate) const FIELDOP_POP_ONE_PLUS_ONE: usize = 27;
+#[cfg(test)]
+pub(crate) const FIELDOP_FIELD_PATH_ENCODE_FINISH: usize = 39;
+
+/// appends huffman code of the op at the given index of `FIELDOP_DESCRIPTORS` to bits; this is
+/// the counterpart of [`read_field_paths`] for building test data.
+#[cfg(test)]
+pub(crate) fn write_field_op(bits: &mut Vec<bool>, op_index: usize) {
+    fn walk(node: &Node<FieldOp>, op_index: usize, bits: &mut Vec<bool>) -> bool {
+        match node {
+            // NOTE: leaf nums are indices of descriptors; see build_fieldop_hierarchy.
+            Node::Leaf { num, .. } => *num == op_index,
+            Node::Branch { left, right, .. } => {
+                for (bit, next) in [(false, left), (true, right)] {
+                    bits.push(bit);
+                    if walk(next, op_index, bits) {
+                        return true;
+                    }
+                    bits.pop();
+                }
+                false
+            }
+        }
+    }
+
+    let found = walk(&FIELDOP_HIERARCHY, op_index, bits);
+    assert!(found, "field op {} does not exist", op_index);
+}
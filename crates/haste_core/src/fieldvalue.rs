@@ -36,6 +36,21 @@ pub enum FieldValue {
     String(Box<[u8]>),
 }
 
+/// variant of [`FieldValue`] without the value. see
+/// [`crate::flattenedserializers::FlattenedSerializerField::value_kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldValueKind {
+    I64,
+    U64,
+    F32,
+    Bool,
+    Vector3,
+    Vector2,
+    Vector4,
+    QAngle,
+    String,
+}
+
 // TODO(blukai): when you'll be unfucking errors - rename this one to FieldValueInvalidConversion
 // or something..
 #[derive(Debug, thiserror::Error)]
@@ -167,3 +182,73 @@ impl_display! {
     QAngle,
     String
 }
+
+// serde...
+
+/// bytes that are serialized as a string when they are valid utf8; otherwise as
+/// `{"base64": "..."}`, see [`FieldValue::String`].
+#[cfg(feature = "serde")]
+pub(crate) struct SerializeBytes<'a>(pub(crate) &'a [u8]);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SerializeBytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use base64::Engine;
+        use serde::ser::SerializeMap;
+
+        match std::str::from_utf8(self.0) {
+            Ok(str) => serializer.serialize_str(str),
+            Err(_) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(
+                    "base64",
+                    &base64::engine::general_purpose::STANDARD.encode(self.0),
+                )?;
+                map.end()
+            }
+        }
+    }
+}
+
+// NOTE: values are serialized without variant tags (numbers, bools, arrays of floats and
+// strings); tags would only add noise to the output.
+#[cfg(feature = "serde")]
+impl serde::Serialize for FieldValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::I64(value) => serializer.serialize_i64(*value),
+            Self::U64(value) => serializer.serialize_u64(*value),
+            Self::F32(value) => serializer.serialize_f32(*value),
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::Vector3(value) | Self::QAngle(value) => {
+                serde::Serialize::serialize(value, serializer)
+            }
+            Self::Vector2(value) => serde::Serialize::serialize(value, serializer),
+            Self::Vector4(value) => serde::Serialize::serialize(value, serializer),
+            Self::String(value) => serde::Serialize::serialize(&SerializeBytes(value), serializer),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serialize() -> anyhow::Result<()> {
+        assert_eq!(serde_json::to_string(&FieldValue::I64(-1))?, "-1");
+        assert_eq!(
+            serde_json::to_string(&FieldValue::Vector2([1.0, 2.5]))?,
+            "[1.0,2.5]"
+        );
+        assert_eq!(
+            serde_json::to_string(&FieldValue::String(b"npc_dota_hero_zuus".to_vec().into()))?,
+            r#""npc_dota_hero_zuus""#
+        );
+        assert_eq!(
+            serde_json::to_string(&FieldValue::String(vec![0xff, 0x00, 0x01].into()))?,
+            r#"{"base64":"/wAB"}"#
+        );
+        Ok(())
+    }
+}
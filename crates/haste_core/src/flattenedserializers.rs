@@ -1,6 +1,6 @@
 use std::collections::hash_map;
 use std::hash::BuildHasherDefault;
-use std::rc::Rc;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 
 use nohash::NoHashMap;
 use valveprotos::common::{
@@ -10,9 +10,13 @@ use valveprotos::common::{
 use valveprotos::prost::{self, Message};
 use varint;
 
+use crate::entities::child_field_key;
+use crate::fielddecoder::FieldDecoderConstructionError;
 use crate::fieldmetadata::{
     FieldMetadata, FieldMetadataError, FieldSpecialDescriptor, get_field_metadata,
 };
+use crate::fieldpath::FieldPath;
+use crate::fieldvalue::FieldValueKind;
 
 #[derive(thiserror::Error, Debug)]
 pub enum FlattenedSerializersError {
@@ -20,8 +24,55 @@ pub enum FlattenedSerializersError {
     DecodeError(#[from] prost::DecodeError),
     #[error(transparent)]
     ReadVarintError(#[from] varint::ReadVarintError),
-    #[error(transparent)]
-    FieldMetadataError(#[from] FieldMetadataError),
+    #[error("field {} of type {} can't be decoded: {:?}", .0.var_name, .0.var_type, .0.reason)]
+    UndecodableField(Box<UnrecognizedField>),
+}
+
+/// reason why a field is listed in [`FlattenedSerializerContainer::unrecognized_fields`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnrecognizedFieldReason {
+    /// var type (or a part of it) is not known; values are decoded as unsigned integers. that is
+    /// correct for enums (which are most of such types), but may not be for anything else.
+    UnknownVarType,
+    /// field is undecodable.
+    UnknownVarEncoder,
+    /// fixed array's length is a constant that is not known; field is undecodable.
+    UnknownArrayLength(String),
+    /// var type could not be parsed, or decoder could not be constructed; field is undecodable.
+    Invalid(String),
+}
+
+impl From<FieldMetadataError> for UnrecognizedFieldReason {
+    fn from(value: FieldMetadataError) -> Self {
+        match value {
+            FieldMetadataError::FieldDecoderConstructionError(
+                FieldDecoderConstructionError::UnknownVarEncoder,
+            ) => Self::UnknownVarEncoder,
+            FieldMetadataError::UnknownArrayLengthIdent(ident) => Self::UnknownArrayLength(ident),
+            err => Self::Invalid(err.to_string()),
+        }
+    }
+}
+
+/// field that is not fully understood; see [`FlattenedSerializerContainer::parse_tolerant`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnrecognizedField {
+    /// name of the first serializer that contains the field; fields are shared between
+    /// serializers.
+    pub serializer_name: String,
+    pub var_name: String,
+    pub var_type: String,
+    pub var_encoder: Option<String>,
+    pub reason: UnrecognizedFieldReason,
+}
+
+impl UnrecognizedField {
+    /// undecodable fields can't be parsed; entities that receive updates of such fields fail to
+    /// parse with [`crate::entities::EntityParseError::UndecodableField`].
+    #[inline]
+    pub fn is_undecodable(&self) -> bool {
+        !matches!(self.reason, UnrecognizedFieldReason::UnknownVarType)
+    }
 }
 
 // TODO: symbol table / string cache (but do not use servo's string cache
@@ -50,6 +101,17 @@ impl From<&String> for Symbol {
     }
 }
 
+// NOTE: symbol is serialized as a string when strings are preserved, otherwise as a hash.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[cfg(feature = "preserve-metadata")]
+        return serializer.serialize_str(&self.str);
+        #[cfg(not(feature = "preserve-metadata"))]
+        return serializer.serialize_u64(self.hash);
+    }
+}
+
 // some info about string tables
 // https://developer.valvesoftware.com/wiki/Networking_Events_%26_Messages
 // https://developer.valvesoftware.com/wiki/Networking_Entities
@@ -79,17 +141,19 @@ pub struct FlattenedSerializerField {
     pub field_serializer_name: Option<Symbol>,
     pub var_encoder: Option<Symbol>,
 
-    pub field_serializer: Option<Rc<FlattenedSerializer>>,
+    pub field_serializer: Option<Arc<FlattenedSerializer>>,
     pub(crate) metadata: FieldMetadata,
 }
 
 // TODO: try to split flattened serializer field initialization into 3 clearly separate stages
 // (protobuf mapping; metadata; field serializer construction).
 impl FlattenedSerializerField {
+    // NOTE: returns reason if field is not fully understood; undecodable fields get
+    // FieldSpecialDescriptor::Undecodable.
     fn new(
         msg: &CsvcMsgFlattenedSerializer,
         field: &ProtoFlattenedSerializerFieldT,
-    ) -> Result<Self, FieldMetadataError> {
+    ) -> (Self, Option<UnrecognizedFieldReason>) {
         // SAFETY: some symbols are cricual, if they don't exist - fail early
         // and loudly.
         //
@@ -126,21 +190,23 @@ impl FlattenedSerializerField {
             field_serializer: None,
             metadata: Default::default(),
         };
-        ret.metadata = get_field_metadata(&ret, var_type)?;
-        Ok(ret)
+        let reason = match get_field_metadata(&ret, var_type) {
+            Ok(metadata) => {
+                let reason = metadata
+                    .var_type_assumed
+                    .then_some(UnrecognizedFieldReason::UnknownVarType);
+                ret.metadata = metadata;
+                reason
+            }
+            Err(err) => {
+                ret.metadata = FieldMetadata::undecodable();
+                Some(UnrecognizedFieldReason::from(err))
+            }
+        };
+        (ret, reason)
     }
 
     #[inline(always)]
-    pub(crate) unsafe fn get_child_unchecked(&self, index: usize) -> &Self {
-        let fs = self.field_serializer.as_ref();
-
-        debug_assert!(fs.is_some(), "field serializer is missing");
-
-        fs.unwrap_unchecked().get_child_unchecked(index)
-    }
-
-    // NOTE: using this method can hurt performance when used in critical code
-    // paths. use the unsafe [`Self::get_child_unchecked`] instead.
     pub fn get_child(&self, index: usize) -> Option<&Self> {
         self.field_serializer
             .as_ref()
@@ -159,6 +225,30 @@ impl FlattenedSerializerField {
             .as_ref()
             .is_some_and(|sd| sd.is_dynamic_array())
     }
+
+    /// see [`FlattenedSerializerContainer::parse_tolerant`].
+    #[inline(always)]
+    pub fn is_undecodable(&self) -> bool {
+        matches!(
+            self.metadata.special_descriptor,
+            Some(FieldSpecialDescriptor::Undecodable)
+        )
+    }
+
+    #[inline]
+    pub fn is_fixed_array(&self) -> bool {
+        matches!(
+            self.metadata.special_descriptor,
+            Some(FieldSpecialDescriptor::FixedArray { .. })
+        )
+    }
+
+    /// kind of [`crate::fieldvalue::FieldValue`] that is decoded for this field. for dynamic
+    /// arrays this is the kind of array's length; kind of elements is reported by array's child.
+    #[inline]
+    pub fn value_kind(&self) -> Option<FieldValueKind> {
+        self.metadata.decoder.value_kind()
+    }
 }
 
 /// note about missing `serializer_version` field (from
@@ -175,7 +265,7 @@ impl FlattenedSerializerField {
 #[derive(Debug, Clone, Default)]
 pub struct FlattenedSerializer {
     pub serializer_name: Symbol,
-    pub fields: Vec<Rc<FlattenedSerializerField>>,
+    pub fields: Vec<Arc<FlattenedSerializerField>>,
 }
 
 impl FlattenedSerializer {
@@ -197,29 +287,46 @@ impl FlattenedSerializer {
     }
 
     #[inline(always)]
-    pub(crate) unsafe fn get_child_unchecked(&self, index: usize) -> &FlattenedSerializerField {
-        debug_assert!(
-            self.fields.get(index).is_some(),
-            "field at index {} is missing",
-            index,
-        );
-
-        self.fields.get_unchecked(index)
-    }
-
-    // NOTE: using this method can hurt performance when used in critical code
-    // paths. use the unsafe [`Self::get_child_unchecked`] instead.
     pub fn get_child(&self, index: usize) -> Option<&FlattenedSerializerField> {
         self.fields.get(index).map(|field| field.as_ref())
     }
 }
 
+/// field name and type resolved with [`FlattenedSerializerContainer::resolve_path`] or
+/// [`FlattenedSerializerContainer::resolve_key`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedField<'a> {
+    /// dotted name, e.g. `CBodyComponent.m_cellX`. elements of arrays are represented by their
+    /// index, e.g. `m_vecPlayerData.3.m_iszPlayerName`.
+    pub name: String,
+    /// type of array elements is derived from array's type (e.g. `uint32` from `uint32[10]`).
+    pub var_type: Option<&'a str>,
+}
+
 pub struct FlattenedSerializerContainer {
-    serializer_map: NoHashMap<u64, Rc<FlattenedSerializer>>,
+    serializer_map: NoHashMap<u64, Arc<FlattenedSerializer>>,
+    // NOTE: symbols are kept regardless of preserve-metadata feature, they are needed to resolve
+    // field names at runtime. lookup tables are built lazily, on first use.
+    symbols: Vec<String>,
+    symbol_map: OnceLock<NoHashMap<u64, usize>>,
+    // NOTE: serializer name hash -> field key -> field path.
+    key_map: Mutex<NoHashMap<u64, NoHashMap<u64, FieldPath>>>,
+    unrecognized_fields: Vec<UnrecognizedField>,
 }
 
 impl FlattenedSerializerContainer {
+    /// fails if any of the fields is undecodable (see [`UnrecognizedField::is_undecodable`]).
     pub fn parse(cmd: CDemoSendTables) -> Result<Self, FlattenedSerializersError> {
+        let container = Self::parse_tolerant(cmd)?;
+        container.ensure_decodable()?;
+        Ok(container)
+    }
+
+    /// unlike [`FlattenedSerializerContainer::parse`] does not fail when var type or var encoder
+    /// of a field is not known (for example after a game update). such fields are marked as
+    /// undecodable; replays that never update them parse just fine. see
+    /// [`FlattenedSerializerContainer::unrecognized_fields`] for the report.
+    pub fn parse_tolerant(cmd: CDemoSendTables) -> Result<Self, FlattenedSerializersError> {
         let msg = {
             // TODO: make prost work with ByteString and turn data into Bytes
             //
@@ -238,13 +345,14 @@ impl FlattenedSerializerContainer {
             CsvcMsgFlattenedSerializer::decode(data)?
         };
 
-        let mut field_map: NoHashMap<i32, Rc<FlattenedSerializerField>> =
+        let mut field_map: NoHashMap<i32, Arc<FlattenedSerializerField>> =
             NoHashMap::with_capacity_and_hasher(msg.fields.len(), BuildHasherDefault::default());
-        let mut serializer_map: NoHashMap<u64, Rc<FlattenedSerializer>> =
+        let mut serializer_map: NoHashMap<u64, Arc<FlattenedSerializer>> =
             NoHashMap::with_capacity_and_hasher(
                 msg.serializers.len(),
                 BuildHasherDefault::default(),
             );
+        let mut unrecognized_fields: Vec<UnrecognizedField> = Vec::new();
 
         for serializer in msg.serializers.iter() {
             let mut flattened_serializer = FlattenedSerializer::new(&msg, serializer);
@@ -255,8 +363,22 @@ impl FlattenedSerializerContainer {
                     continue;
                 }
 
-                let mut field =
-                    FlattenedSerializerField::new(&msg, &msg.fields[*field_index as usize])?;
+                let (mut field, reason) =
+                    FlattenedSerializerField::new(&msg, &msg.fields[*field_index as usize]);
+                if let Some(reason) = reason {
+                    let resolve_sym = |i: i32| msg.symbols.get(i as usize).cloned();
+                    let field = &msg.fields[*field_index as usize];
+                    unrecognized_fields.push(UnrecognizedField {
+                        serializer_name: serializer
+                            .serializer_name_sym
+                            .and_then(resolve_sym)
+                            .unwrap_or_default(),
+                        var_name: field.var_name_sym.and_then(resolve_sym).unwrap_or_default(),
+                        var_type: field.var_type_sym.and_then(resolve_sym).unwrap_or_default(),
+                        var_encoder: field.var_encoder_sym.and_then(resolve_sym),
+                        reason,
+                    });
+                }
 
                 field.field_serializer = match field.metadata.special_descriptor {
                     Some(FieldSpecialDescriptor::FixedArray { length }) => {
@@ -265,10 +387,10 @@ impl FlattenedSerializerContainer {
                             .field_serializer_name
                             .as_ref()
                             .and_then(|symbol| serializer_map.get(&symbol.hash).cloned());
-                        Some(Rc::new(FlattenedSerializer {
+                        Some(Arc::new(FlattenedSerializer {
                             fields: {
                                 let mut fields = Vec::with_capacity(length);
-                                fields.resize(length, Rc::new(field));
+                                fields.resize(length, Arc::new(field));
                                 fields
                             },
                             ..Default::default()
@@ -282,8 +404,8 @@ impl FlattenedSerializerContainer {
                             },
                             ..Default::default()
                         };
-                        Some(Rc::new(FlattenedSerializer {
-                            fields: vec![Rc::new(field)],
+                        Some(Arc::new(FlattenedSerializer {
+                            fields: vec![Arc::new(field)],
                             ..Default::default()
                         }))
                     }
@@ -295,53 +417,281 @@ impl FlattenedSerializerContainer {
                                 .and_then(|symbol| serializer_map.get(&symbol.hash).cloned()),
                             ..Default::default()
                         };
-                        Some(Rc::new(FlattenedSerializer {
-                            fields: vec![Rc::new(field)],
+                        Some(Arc::new(FlattenedSerializer {
+                            fields: vec![Arc::new(field)],
                             ..Default::default()
                         }))
                     }
+                    // NOTE: there's no way to tell what is inside of undecodable fields.
+                    Some(FieldSpecialDescriptor::Undecodable) => None,
                     _ => field
                         .field_serializer_name
                         .as_ref()
                         .and_then(|symbol| serializer_map.get(&symbol.hash).cloned()),
                 };
 
-                let field = Rc::new(field);
+                let field = Arc::new(field);
                 field_map.insert(*field_index, field.clone());
                 flattened_serializer.fields.push(field);
             }
 
             serializer_map.insert(
                 flattened_serializer.serializer_name.hash,
-                Rc::new(flattened_serializer),
+                Arc::new(flattened_serializer),
             );
         }
 
-        Ok(Self { serializer_map })
+        Ok(Self {
+            serializer_map,
+            symbols: msg.symbols,
+            symbol_map: OnceLock::new(),
+            key_map: Mutex::default(),
+            unrecognized_fields,
+        })
+    }
+
+    /// fields that are not fully understood, in order of appearance.
+    #[inline]
+    pub fn unrecognized_fields(&self) -> &[UnrecognizedField] {
+        &self.unrecognized_fields
+    }
+
+    /// fails with the first undecodable field, if any.
+    pub fn ensure_decodable(&self) -> Result<(), FlattenedSerializersError> {
+        match self
+            .unrecognized_fields
+            .iter()
+            .find(|unrecognized_field| unrecognized_field.is_undecodable())
+        {
+            Some(unrecognized_field) => Err(FlattenedSerializersError::UndecodableField(Box::new(
+                unrecognized_field.clone(),
+            ))),
+            None => Ok(()),
+        }
     }
 
     // TODO: think about exposing the whole serializer map
 
     #[inline(always)]
-    pub fn by_name_hash(&self, serializer_name_hash: u64) -> Option<Rc<FlattenedSerializer>> {
+    pub fn by_name_hash(&self, serializer_name_hash: u64) -> Option<Arc<FlattenedSerializer>> {
         self.serializer_map.get(&serializer_name_hash).cloned()
     }
 
-    #[inline(always)]
-    pub unsafe fn by_name_hash_unckecked(
+    #[inline]
+    pub fn values(&self) -> hash_map::Values<'_, u64, Arc<FlattenedSerializer>> {
+        self.serializer_map.values()
+    }
+
+    /// resolves hash of a [`Symbol`] back to its string.
+    pub fn resolve_symbol(&self, hash: u64) -> Option<&str> {
+        let symbol_map = self.symbol_map.get_or_init(|| {
+            self.symbols
+                .iter()
+                .enumerate()
+                .map(|(i, symbol)| (fxhash::hash_bytes(symbol.as_bytes()), i))
+                .collect()
+        });
+        symbol_map
+            .get(&hash)
+            .and_then(|i| self.symbols.get(*i))
+            .map(String::as_str)
+    }
+
+    /// resolves field path (e.g. [`crate::entities::UpdatedField::path`]) to field's name and
+    /// type.
+    pub fn resolve_path(
         &self,
-        serializer_name_hash: u64,
-    ) -> Rc<FlattenedSerializer> {
-        self.serializer_map
-            .get(&serializer_name_hash)
-            .unwrap_unchecked()
-            // NOTE: do not chain .cloned() after calling .get(), because .cloned() uses match
-            // under the hood which adds a branch; that is redunant.
-            .clone()
+        serializer: &FlattenedSerializer,
+        path: &FieldPath,
+    ) -> Option<ResolvedField<'_>> {
+        // NOTE: this walks the path the same way Entity::parse does.
+        let mut field = serializer.get_child(path.get(0)?)?;
+        let mut name = self.resolve_symbol(field.var_name.hash)?.to_string();
+        let mut var_type = self.resolve_symbol(field.var_type.hash);
+        // NOTE: elements of fixed arrays are clones of the array field; they must not be treated
+        // as arrays themselves.
+        let mut is_element = false;
+        for i in 1..=path.last() {
+            let index = path.get(i)?;
+            name.push('.');
+            let is_array = !is_element && (field.is_dynamic_array() || field.is_fixed_array());
+            if is_array {
+                field = field.get_child(if field.is_fixed_array() { index } else { 0 })?;
+                name.push_str(&index.to_string());
+                var_type = var_type.and_then(element_var_type);
+            } else {
+                field = field.get_child(index)?;
+                name.push_str(self.resolve_symbol(field.var_name.hash)?);
+                var_type = self.resolve_symbol(field.var_type.hash);
+            }
+            is_element = is_array;
+        }
+        Some(ResolvedField { name, var_type })
     }
 
-    #[inline]
-    pub fn values(&self) -> hash_map::Values<'_, u64, Rc<FlattenedSerializer>> {
-        self.serializer_map.values()
+    /// resolves field key (see [`crate::entities::fkey_from_path`]) to field's name and type.
+    ///
+    /// NOTE: keys of dynamic array elements can't be resolved (key of the array itself can);
+    /// use [`FlattenedSerializerContainer::resolve_path`] for them. elements of fixed arrays share
+    /// the key, it resolves to the first element.
+    pub fn resolve_key(
+        &self,
+        serializer: &FlattenedSerializer,
+        key: u64,
+    ) -> Option<ResolvedField<'_>> {
+        let path = self
+            .key_map
+            // NOTE: key map is a cache; it remains valid even if other thread panicked while
+            // holding the lock.
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(serializer.serializer_name.hash)
+            .or_insert_with(|| {
+                let mut key_map = NoHashMap::default();
+                collect_field_keys(serializer, &mut FieldPath::default(), 0, None, &mut key_map);
+                key_map
+            })
+            .get(&key)
+            .cloned()?;
+        self.resolve_path(serializer, &path)
+    }
+}
+
+// NOTE: array types are `T[N]` (fixed) and `Container< T >` (dynamic).
+fn element_var_type(var_type: &str) -> Option<&str> {
+    if let Some((element_type, _)) = var_type.rsplit_once('[') {
+        return Some(element_type.trim_end());
+    }
+    let (_, rest) = var_type.split_once('<')?;
+    let (element_type, _) = rest.rsplit_once('>')?;
+    Some(element_type.trim())
+}
+
+// NOTE: elements of dynamic arrays are not collected; their count is unknown.
+fn collect_field_keys(
+    serializer: &FlattenedSerializer,
+    path: &mut FieldPath,
+    depth: usize,
+    parent_key: Option<u64>,
+    key_map: &mut NoHashMap<u64, FieldPath>,
+) {
+    if depth >= path.data.len() {
+        return;
+    }
+
+    for (i, field) in serializer.fields.iter().enumerate() {
+        path.data[depth] = i as u8;
+        path.last = depth;
+
+        let key = child_field_key(parent_key, field.var_name.hash);
+        // NOTE: elements of fixed arrays share the key; the first one wins.
+        key_map.entry(key).or_insert_with(|| path.clone());
+
+        if !field.is_dynamic_array() {
+            if let Some(field_serializer) = field.field_serializer.as_ref() {
+                collect_field_keys(field_serializer, path, depth + 1, Some(key), key_map);
+            }
+        }
+    }
+
+    path.data[depth] = 0;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil;
+
+    fn make_send_tables() -> CDemoSendTables {
+        testutil::make_send_tables(&[(
+            "CFoo",
+            &[
+                ("m_flBar", "float32", Some("some_future_encoder")),
+                ("m_eMode", "EMode", None),
+                ("m_iOk", "int32", None),
+            ],
+        )])
+    }
+
+    #[test]
+    fn test_parse_tolerant() -> anyhow::Result<()> {
+        assert!(matches!(
+            FlattenedSerializerContainer::parse(make_send_tables()),
+            Err(FlattenedSerializersError::UndecodableField(_))
+        ));
+
+        let serializers = FlattenedSerializerContainer::parse_tolerant(make_send_tables())?;
+        let reasons: Vec<(&str, &UnrecognizedFieldReason)> = serializers
+            .unrecognized_fields()
+            .iter()
+            .map(|unrecognized_field| {
+                (
+                    unrecognized_field.var_name.as_str(),
+                    &unrecognized_field.reason,
+                )
+            })
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("m_flBar", &UnrecognizedFieldReason::UnknownVarEncoder),
+                ("m_eMode", &UnrecognizedFieldReason::UnknownVarType),
+            ]
+        );
+        assert!(serializers.ensure_decodable().is_err());
+
+        let serializer = serializers
+            .by_name_hash(fxhash::hash_bytes(b"CFoo"))
+            .ok_or_else(|| anyhow::anyhow!("serializer is missing"))?;
+        let undecodable: Vec<bool> = serializer
+            .fields
+            .iter()
+            .map(|field| field.is_undecodable())
+            .collect();
+        assert_eq!(undecodable, vec![true, false, false]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_path() -> anyhow::Result<()> {
+        let serializers = FlattenedSerializerContainer::parse(testutil::make_send_tables(&[
+            ("CInner", &[("m_nX", "int32", None)]),
+            (
+                "CFoo",
+                &[
+                    ("m_iHealth", "int32", None),
+                    ("m_arr", "uint32[10]", None),
+                    ("m_vec", "CNetworkUtlVectorBase< int32 >", None),
+                    ("m_inner", "CInner", None),
+                ],
+            ),
+        ]))?;
+        let serializer = serializers
+            .by_name_hash(fxhash::hash_bytes(b"CFoo"))
+            .ok_or_else(|| anyhow::anyhow!("serializer is missing"))?;
+
+        let cases: [(&[u8], Option<(&str, &str)>); 6] = [
+            (&[0], Some(("m_iHealth", "int32"))),
+            (&[1], Some(("m_arr", "uint32[10]"))),
+            (&[1, 3], Some(("m_arr.3", "uint32"))),
+            (&[2, 5], Some(("m_vec.5", "int32"))),
+            (&[3, 0], Some(("m_inner.m_nX", "int32"))),
+            (&[4], None),
+        ];
+        for (components, want) in cases {
+            let mut path = FieldPath::default();
+            path.data[..components.len()].copy_from_slice(components);
+            path.last = components.len() - 1;
+            let got = serializers.resolve_path(&serializer, &path);
+            assert_eq!(
+                got.as_ref()
+                    .map(|resolved_field| (resolved_field.name.as_str(), resolved_field.var_type)),
+                want.map(|(name, var_type)| (name, Some(var_type))),
+                "{components:?}"
+            );
+        }
+
+        Ok(())
     }
 }
@@ -0,0 +1,398 @@
+use std::hash::BuildHasherDefault;
+
+use nohash::NoHashMap;
+use valveprotos::common::{
+    c_msg_source1_legacy_game_event, CMsgSource1LegacyGameEvent, CMsgSource1LegacyGameEventList,
+};
+
+use crate::entities::{ehandle_to_index, is_ehandle_valid};
+
+// NOTE: game events are "legacy" source 1 thing that is still alive in source 2. descriptors are
+// sent once (GE_Source1LegacyGameEventList), events themselves (GE_Source1LegacyGameEvent) carry
+// only event id and a list of values; names of the values are positional and need to be resolved
+// using descriptors.
+//
+// key types are defined in public/igameevents.h (adjusted):
+// TYPE_LOCAL = 0, // not networked
+// TYPE_STRING,    // zero terminated ASCII string
+// TYPE_FLOAT,     // float 32 bit
+// TYPE_LONG,      // signed int 32 bit
+// TYPE_SHORT,     // signed int 16 bit
+// TYPE_BYTE,      // unsigned int 8 bit
+// TYPE_BOOL,      // unsigned int 1 bit
+// TYPE_UINT64,    // unsigned int 64 bit
+//
+// cs2 extends the list with:
+// TYPE_PLAYERCONTROLLER, // player slot, sent as short
+// TYPE_PLAYERPAWN,       // entity handle of player's pawn, sent as long
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEventKeyType {
+    String,
+    Float,
+    Long,
+    Short,
+    Byte,
+    Bool,
+    Uint64,
+    PlayerController,
+    PlayerPawn,
+    /// key type that is not known to the parser; values of such keys can't be interpreted.
+    Unknown(i32),
+}
+
+impl From<i32> for GameEventKeyType {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => Self::String,
+            2 => Self::Float,
+            3 => Self::Long,
+            4 => Self::Short,
+            5 => Self::Byte,
+            6 => Self::Bool,
+            7 => Self::Uint64,
+            8 => Self::PlayerController,
+            9 => Self::PlayerPawn,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameEventKeyDescriptor {
+    pub name: Box<str>,
+    pub key_type: GameEventKeyType,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameEventDescriptor {
+    pub id: i32,
+    pub name: Box<str>,
+    pub keys: Vec<GameEventKeyDescriptor>,
+}
+
+impl GameEventDescriptor {
+    pub fn key_index(&self, name: &str) -> Option<usize> {
+        self.keys.iter().position(|key| key.name.as_ref().eq(name))
+    }
+}
+
+/// collection of game event descriptors.
+#[derive(Debug, Clone, Default)]
+pub struct GameEventList {
+    descriptors: NoHashMap<i32, GameEventDescriptor>,
+}
+
+impl GameEventList {
+    pub fn parse(msg: CMsgSource1LegacyGameEventList) -> Self {
+        let mut descriptors = NoHashMap::with_capacity_and_hasher(
+            msg.descriptors.len(),
+            BuildHasherDefault::default(),
+        );
+
+        for descriptor in msg.descriptors {
+            let keys = descriptor
+                .keys
+                .iter()
+                .map(|key| GameEventKeyDescriptor {
+                    name: key.name().into(),
+                    key_type: GameEventKeyType::from(key.r#type()),
+                })
+                .collect();
+
+            descriptors.insert(
+                descriptor.eventid(),
+                GameEventDescriptor {
+                    id: descriptor.eventid(),
+                    name: descriptor.name().into(),
+                    keys,
+                },
+            );
+        }
+
+        Self { descriptors }
+    }
+
+    #[inline]
+    pub fn get(&self, id: i32) -> Option<&GameEventDescriptor> {
+        self.descriptors.get(&id)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&GameEventDescriptor> {
+        self.descriptors
+            .values()
+            .find(|descriptor| descriptor.name.as_ref().eq(name))
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &GameEventDescriptor> {
+        self.descriptors.values()
+    }
+}
+
+/// slot of a player; value of a [`GameEventKeyType::PlayerController`] key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerSlot(pub i16);
+
+impl PlayerSlot {
+    /// entity index of player's controller.
+    #[inline]
+    pub fn controller_index(self) -> i32 {
+        self.0 as i32 + 1
+    }
+}
+
+/// entity handle of player's pawn; value of a [`GameEventKeyType::PlayerPawn`] key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerPawnHandle(pub u32);
+
+impl PlayerPawnHandle {
+    #[inline]
+    pub fn is_valid(self) -> bool {
+        is_ehandle_valid(self.0)
+    }
+
+    /// entity index of player's pawn.
+    #[inline]
+    pub fn pawn_index(self) -> i32 {
+        ehandle_to_index(self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEventValue {
+    String(String),
+    Float(f32),
+    Long(i32),
+    Short(i16),
+    Byte(u8),
+    Bool(bool),
+    Uint64(u64),
+    PlayerController(PlayerSlot),
+    PlayerPawn(PlayerPawnHandle),
+    /// value of a key of [`GameEventKeyType::Unknown`] type.
+    Unknown,
+}
+
+impl GameEventValue {
+    fn from_key(key_type: GameEventKeyType, key: &c_msg_source1_legacy_game_event::KeyT) -> Self {
+        match key_type {
+            GameEventKeyType::String => Self::String(key.val_string.clone().unwrap_or_default()),
+            GameEventKeyType::Float => Self::Float(key.val_float()),
+            GameEventKeyType::Long => Self::Long(key.val_long()),
+            GameEventKeyType::Short => Self::Short(key.val_short() as i16),
+            GameEventKeyType::Byte => Self::Byte(key.val_byte() as u8),
+            GameEventKeyType::Bool => Self::Bool(key.val_bool()),
+            GameEventKeyType::Uint64 => Self::Uint64(key.val_uint64()),
+            GameEventKeyType::PlayerController => {
+                Self::PlayerController(PlayerSlot(key.val_short() as i16))
+            }
+            GameEventKeyType::PlayerPawn => {
+                Self::PlayerPawn(PlayerPawnHandle(key.val_long() as u32))
+            }
+            GameEventKeyType::Unknown(_) => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("incompatible game event value type")]
+pub struct GameEventValueConversionError;
+
+macro_rules! impl_try_into {
+    ($($variant:ident => $ty:ty),+) => {
+        $(
+            impl TryInto<$ty> for &GameEventValue {
+                type Error = GameEventValueConversionError;
+
+                fn try_into(self) -> Result<$ty, Self::Error> {
+                    match self {
+                        GameEventValue::$variant(value) => Ok(value.clone()),
+                        _ => Err(GameEventValueConversionError),
+                    }
+                }
+            }
+        )+
+    }
+}
+
+impl_try_into! {
+    String => String,
+    Float => f32,
+    Long => i32,
+    Short => i16,
+    Byte => u8,
+    Bool => bool,
+    Uint64 => u64,
+    PlayerController => PlayerSlot,
+    PlayerPawn => PlayerPawnHandle
+}
+
+/// game event that was matched with its [`GameEventDescriptor`].
+///
+/// NOTE: values are resolved against the descriptor when they are accessed, not upfront.
+#[derive(Debug, Clone)]
+pub struct GameEvent<'a> {
+    descriptor: &'a GameEventDescriptor,
+    keys: Vec<c_msg_source1_legacy_game_event::KeyT>,
+}
+
+impl<'a> GameEvent<'a> {
+    /// returns `None` if there's no descriptor for the event in the list.
+    pub fn resolve(
+        game_event_list: &'a GameEventList,
+        msg: CMsgSource1LegacyGameEvent,
+    ) -> Option<Self> {
+        let descriptor = game_event_list.get(msg.eventid())?;
+        Some(Self {
+            descriptor,
+            keys: msg.keys,
+        })
+    }
+
+    #[inline]
+    pub fn descriptor(&self) -> &GameEventDescriptor {
+        self.descriptor
+    }
+
+    #[inline]
+    pub fn id(&self) -> i32 {
+        self.descriptor.id
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        self.descriptor.name.as_ref()
+    }
+
+    pub fn get(&self, key_name: &str) -> Option<GameEventValue> {
+        let i = self.descriptor.key_index(key_name)?;
+        // NOTE: key type that is specified by the descriptor is the source of truth; type sent
+        // along with the value is ignored.
+        let key_type = self.descriptor.keys[i].key_type;
+        self.keys
+            .get(i)
+            .map(|key| GameEventValue::from_key(key_type, key))
+    }
+
+    /// get the value of the key with the provided name, and attempt to convert it.
+    ///
+    /// returns `None` if the key does not exist or if the conversion failed.
+    pub fn get_value<T>(&self, key_name: &str) -> Option<T>
+    where
+        for<'v> &'v GameEventValue: TryInto<T, Error = GameEventValueConversionError>,
+    {
+        self.get(key_name)
+            .and_then(|value| (&value).try_into().ok())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, GameEventValue)> {
+        self.descriptor
+            .keys
+            .iter()
+            .zip(self.keys.iter())
+            .map(|(key_descriptor, key)| {
+                (
+                    key_descriptor.name.as_ref(),
+                    GameEventValue::from_key(key_descriptor.key_type, key),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use valveprotos::common::c_msg_source1_legacy_game_event_list::{DescriptorT, KeyT};
+
+    use super::*;
+
+    // NOTE: key types of player_death as cs2 sends them.
+    fn make_game_event_list() -> CMsgSource1LegacyGameEventList {
+        let key = |name: &str, r#type: i32| KeyT {
+            r#type: Some(r#type),
+            name: Some(name.to_string()),
+        };
+        CMsgSource1LegacyGameEventList {
+            descriptors: vec![DescriptorT {
+                eventid: Some(42),
+                name: Some("player_death".to_string()),
+                keys: vec![
+                    key("userid", 8),
+                    key("userid_pawn", 9),
+                    key("attacker", 8),
+                    key("weapon", 1),
+                    key("headshot", 6),
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_cs2_key_types() -> anyhow::Result<()> {
+        let game_event_list = GameEventList::parse(make_game_event_list());
+        let descriptor = game_event_list
+            .find_by_name("player_death")
+            .ok_or_else(|| anyhow::anyhow!("descriptor was not parsed"))?;
+        let key_types: Vec<GameEventKeyType> =
+            descriptor.keys.iter().map(|key| key.key_type).collect();
+        assert_eq!(
+            key_types,
+            vec![
+                GameEventKeyType::PlayerController,
+                GameEventKeyType::PlayerPawn,
+                GameEventKeyType::PlayerController,
+                GameEventKeyType::String,
+                GameEventKeyType::Bool,
+            ]
+        );
+
+        let value = |r#type: i32| c_msg_source1_legacy_game_event::KeyT {
+            r#type: Some(r#type),
+            ..Default::default()
+        };
+        let msg = CMsgSource1LegacyGameEvent {
+            eventid: Some(42),
+            keys: vec![
+                c_msg_source1_legacy_game_event::KeyT {
+                    val_short: Some(3),
+                    ..value(8)
+                },
+                c_msg_source1_legacy_game_event::KeyT {
+                    val_long: Some((7 << 14) | 123),
+                    ..value(9)
+                },
+                c_msg_source1_legacy_game_event::KeyT {
+                    val_short: Some(0),
+                    ..value(8)
+                },
+                c_msg_source1_legacy_game_event::KeyT {
+                    val_string: Some("ak47".to_string()),
+                    ..value(1)
+                },
+                c_msg_source1_legacy_game_event::KeyT {
+                    val_bool: Some(true),
+                    ..value(6)
+                },
+            ],
+            ..Default::default()
+        };
+        let game_event = GameEvent::resolve(&game_event_list, msg)
+            .ok_or_else(|| anyhow::anyhow!("game event was not resolved"))?;
+
+        let userid: Option<PlayerSlot> = game_event.get_value("userid");
+        assert_eq!(userid.map(PlayerSlot::controller_index), Some(4));
+        let attacker: Option<PlayerSlot> = game_event.get_value("attacker");
+        assert_eq!(attacker.map(PlayerSlot::controller_index), Some(1));
+        let pawn: Option<PlayerPawnHandle> = game_event.get_value("userid_pawn");
+        assert_eq!(pawn.map(PlayerPawnHandle::pawn_index), Some(123));
+        assert_eq!(
+            game_event.get_value::<String>("weapon").as_deref(),
+            Some("ak47")
+        );
+        assert_eq!(game_event.get_value::<bool>("headshot"), Some(true));
+        // NOTE: player controller values are not shorts.
+        assert_eq!(game_event.get_value::<i16>("userid"), None);
+
+        Ok(())
+    }
+}
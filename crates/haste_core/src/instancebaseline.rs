@@ -1,14 +1,20 @@
-use std::cell::UnsafeCell;
-use std::num::ParseIntError;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::stringtables::StringTable;
 
 pub(crate) const INSTANCE_BASELINE_TABLE_NAME: &str = "instancebaseline";
 
-#[derive(Default)]
+/// string of an instancebaseline item is expected to be id of the class the baseline belongs to.
+#[derive(thiserror::Error, Debug)]
+#[error("invalid class id of instance baseline item: {string:?}")]
+pub struct InstanceBaselineError {
+    pub string: Option<String>,
+}
+
+#[derive(Default, Clone)]
 pub(crate) struct InstanceBaseline {
-    data: Vec<Option<Rc<UnsafeCell<Vec<u8>>>>>,
+    // NOTE: shared with items of instancebaseline string table.
+    data: Vec<Option<Arc<Vec<u8>>>>,
 }
 
 impl InstanceBaseline {
@@ -16,33 +22,37 @@ impl InstanceBaseline {
         &mut self,
         string_table: &StringTable,
         classes: usize,
-    ) -> Result<(), ParseIntError> {
+    ) -> Result<(), InstanceBaselineError> {
         if self.data.len() < classes {
             self.data.resize(classes, None);
         }
 
         for (_entity_index, item) in string_table.items() {
-            // SAFETY: in normal circumbstances this is safe; it is expected for
-            // instancebaseline's string to be convertable to number, if it
-            // cannot be converted to number - fail loudly!
-            let string =
-                unsafe { std::str::from_utf8_unchecked(item.string.as_ref().unwrap_unchecked()) };
-            let class_id = string.parse::<i32>()?;
-            self.data[class_id as usize] = item.user_data.clone();
+            let slot = item
+                .string
+                .as_deref()
+                .and_then(|string| std::str::from_utf8(string).ok())
+                .and_then(|string| string.parse::<usize>().ok())
+                .and_then(|class_id| self.data.get_mut(class_id));
+            let Some(slot) = slot else {
+                return Err(InstanceBaselineError {
+                    string: item
+                        .string
+                        .as_deref()
+                        .map(|string| String::from_utf8_lossy(string).into_owned()),
+                });
+            };
+            *slot = item.user_data.clone();
         }
         Ok(())
     }
 
     #[inline]
-    pub(crate) unsafe fn by_id_unchecked(&self, class_id: i32) -> &[u8] {
-        unsafe {
-            &*self
-                .data
-                .get_unchecked(class_id as usize)
-                .as_ref()
-                .unwrap_unchecked()
-                .get()
-        }
+    pub(crate) fn by_id(&self, class_id: i32) -> Option<&[u8]> {
+        self.data
+            .get(class_id as usize)
+            .and_then(|data| data.as_deref())
+            .map(Vec::as_slice)
     }
 
     /// clear clears underlying storage, but this has no effect on the allocated capacity.
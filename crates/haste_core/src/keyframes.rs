@@ -0,0 +1,263 @@
+use std::io::{self, Read, SeekFrom, Write};
+
+use valveprotos::common::EDemoCommands;
+
+use crate::demostream::{ReadCmdHeaderError, SeekableDemoStream};
+
+// NOTE: keyframe is a DemFullPacket cmd. full packets contain string tables and entity snapshots,
+// thus parsing can be started from any of them (after signon cmds are handled).
+//
+// sidecar file layout (all numbers are little endian):
+// - magic (8 bytes)
+// - stream len (u64); used to detect that the index does not belong to the stream
+// - keyframe count (u32)
+// - keyframes (tick i32, offset u64)
+
+const KEYFRAME_INDEX_MAGIC: [u8; 8] = *b"HSTKFI1\0";
+
+#[derive(thiserror::Error, Debug)]
+pub enum KeyframeIndexError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    ReadCmdHeaderError(#[from] ReadCmdHeaderError),
+    #[error("invalid keyframe index magic (got {got:?}; want {KEYFRAME_INDEX_MAGIC:?})")]
+    InvalidMagic { got: [u8; 8] },
+    #[error("cmd at offset {offset} is truncated")]
+    TruncatedCmd { offset: u64 },
+    #[error("keyframe index does not match the stream (stream len {got}; want {want})")]
+    StreamMismatch { got: u64, want: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keyframe {
+    pub tick: i32,
+    /// position of full packet's cmd header in the stream.
+    pub offset: u64,
+}
+
+/// index of full packets (keyframes) in a demo stream. see [`crate::parser::Parser::run_to_tick`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyframeIndex {
+    stream_len: u64,
+    // NOTE: sorted by tick.
+    keyframes: Vec<Keyframe>,
+}
+
+impl KeyframeIndex {
+    /// scans the whole stream for full packets. position of the stream is restored afterwards.
+    pub fn build<D: SeekableDemoStream>(demo_stream: &mut D) -> Result<Self, KeyframeIndexError> {
+        Self::build_inner(demo_stream, false)
+    }
+
+    /// same as [`KeyframeIndex::build`], but cmds that can't be read (for example because the
+    /// stream is truncated) end the index instead of failing; keyframes before them are valid.
+    pub fn build_tolerant<D: SeekableDemoStream>(
+        demo_stream: &mut D,
+    ) -> Result<Self, KeyframeIndexError> {
+        Self::build_inner(demo_stream, true)
+    }
+
+    fn build_inner<D: SeekableDemoStream>(
+        demo_stream: &mut D,
+        tolerant: bool,
+    ) -> Result<Self, KeyframeIndexError> {
+        let backup = demo_stream.stream_position()?;
+        let stream_len = demo_stream.stream_len()?;
+
+        let mut keyframes = Vec::new();
+        let mut offset = demo_stream.seek(SeekFrom::Start(demo_stream.start_position()))?;
+        loop {
+            match demo_stream.read_cmd_header() {
+                // NOTE: skipping does not fail if body is truncated; such cmd must not become a
+                // keyframe.
+                Ok(cmd_header)
+                    if offset + cmd_header.size as u64 + cmd_header.body_size as u64
+                        <= stream_len =>
+                {
+                    if cmd_header.cmd == EDemoCommands::DemFullPacket {
+                        keyframes.push(Keyframe {
+                            tick: cmd_header.tick,
+                            offset,
+                        });
+                    }
+                    demo_stream.skip_cmd(&cmd_header)?;
+                    offset += cmd_header.size as u64 + cmd_header.body_size as u64;
+                }
+                Ok(_) if tolerant => break,
+                Ok(_) => {
+                    demo_stream.seek(SeekFrom::Start(backup))?;
+                    return Err(KeyframeIndexError::TruncatedCmd { offset });
+                }
+                Err(ReadCmdHeaderError::IoError(_))
+                    if demo_stream.is_at_eof().unwrap_or_default() =>
+                {
+                    break;
+                }
+                Err(_) if tolerant => break,
+                Err(err) => {
+                    demo_stream.seek(SeekFrom::Start(backup))?;
+                    return Err(err.into());
+                }
+            }
+        }
+
+        demo_stream.seek(SeekFrom::Start(backup))?;
+        Ok(Self {
+            stream_len,
+            keyframes,
+        })
+    }
+
+    /// returns the last keyframe at or before the given tick.
+    pub fn find(&self, tick: i32) -> Option<&Keyframe> {
+        let n = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.tick <= tick);
+        n.checked_sub(1).and_then(|i| self.keyframes.get(i))
+    }
+
+    /// checks whether the index was built for a stream of the same length.
+    pub fn matches<D: SeekableDemoStream>(&self, demo_stream: &mut D) -> Result<bool, io::Error> {
+        Ok(self.stream_len == demo_stream.stream_len()?)
+    }
+
+    /// fails if the index was not built for the stream, see [`KeyframeIndex::matches`].
+    pub fn ensure_matches<D: SeekableDemoStream>(
+        &self,
+        demo_stream: &mut D,
+    ) -> Result<(), KeyframeIndexError> {
+        let stream_len = demo_stream.stream_len()?;
+        if self.stream_len != stream_len {
+            return Err(KeyframeIndexError::StreamMismatch {
+                got: stream_len,
+                want: self.stream_len,
+            });
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Keyframe> {
+        self.keyframes.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.keyframes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    pub fn write_to<W: Write>(&self, mut wtr: W) -> Result<(), io::Error> {
+        wtr.write_all(&KEYFRAME_INDEX_MAGIC)?;
+        wtr.write_all(&self.stream_len.to_le_bytes())?;
+        wtr.write_all(&(self.keyframes.len() as u32).to_le_bytes())?;
+        for keyframe in self.keyframes.iter() {
+            wtr.write_all(&keyframe.tick.to_le_bytes())?;
+            wtr.write_all(&keyframe.offset.to_le_bytes())?;
+        }
+        wtr.flush()
+    }
+
+    pub fn read_from<R: Read>(mut rdr: R) -> Result<Self, KeyframeIndexError> {
+        let mut magic = [0u8; 8];
+        rdr.read_exact(&mut magic)?;
+        if magic != KEYFRAME_INDEX_MAGIC {
+            return Err(KeyframeIndexError::InvalidMagic { got: magic });
+        }
+
+        let mut buf_u64 = [0u8; size_of::<u64>()];
+        let mut buf_u32 = [0u8; size_of::<u32>()];
+
+        rdr.read_exact(&mut buf_u64)?;
+        let stream_len = u64::from_le_bytes(buf_u64);
+
+        rdr.read_exact(&mut buf_u32)?;
+        let count = u32::from_le_bytes(buf_u32) as usize;
+
+        // NOTE: count can't be trusted (the file may be corrupt or truncated), thus memory is not
+        // preallocated; the vec grows only as long as keyframes can actually be read.
+        let mut keyframes = Vec::new();
+        for _ in 0..count {
+            rdr.read_exact(&mut buf_u32)?;
+            let tick = i32::from_le_bytes(buf_u32);
+            rdr.read_exact(&mut buf_u64)?;
+            let offset = u64::from_le_bytes(buf_u64);
+            keyframes.push(Keyframe { tick, offset });
+        }
+
+        Ok(Self {
+            stream_len,
+            keyframes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::demofile::DemoFile;
+    use crate::demostream::DemoStream;
+    use crate::testutil;
+
+    #[test]
+    fn test_build_find_persist() -> anyhow::Result<()> {
+        let data = testutil::make_packets_demo((0..=9000).step_by(30), Some(1800))?;
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data))?;
+
+        let keyframe_index = KeyframeIndex::build(&mut demo_file)?;
+        assert_eq!(keyframe_index.len(), 6);
+        assert_eq!(keyframe_index.find(-1), None);
+        assert_eq!(
+            keyframe_index.find(1799).map(|keyframe| keyframe.tick),
+            Some(0)
+        );
+        assert_eq!(
+            keyframe_index.find(1800).map(|keyframe| keyframe.tick),
+            Some(1800)
+        );
+
+        let keyframe = keyframe_index.find(4000).copied().unwrap_or(Keyframe {
+            tick: -1,
+            offset: 0,
+        });
+        demo_file.seek(SeekFrom::Start(keyframe.offset))?;
+        let cmd_header = demo_file.read_cmd_header()?;
+        assert_eq!(cmd_header.cmd, EDemoCommands::DemFullPacket);
+        assert_eq!(cmd_header.tick, 3600);
+
+        let mut sidecar = Vec::new();
+        keyframe_index.write_to(&mut sidecar)?;
+        let restored = KeyframeIndex::read_from(sidecar.as_slice())?;
+        assert_eq!(restored, keyframe_index);
+        assert!(restored.matches(&mut demo_file)?);
+
+        // NOTE: count of a truncated sidecar claims more keyframes than there are.
+        let mut truncated = sidecar[..sidecar.len() - 1].to_vec();
+        truncated[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(KeyframeIndex::read_from(truncated.as_slice()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_tolerant() -> anyhow::Result<()> {
+        let mut data = testutil::make_packets_demo(0..100, Some(30))?;
+        // NOTE: cut off the last cmd (file info).
+        data.truncate(data.len() - 1);
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        assert!(KeyframeIndex::build(&mut demo_file).is_err());
+
+        let keyframe_index = KeyframeIndex::build_tolerant(&mut demo_file)?;
+        assert_eq!(keyframe_index.len(), 4);
+
+        Ok(())
+    }
+}
@@ -3,20 +3,32 @@
 #![deny(clippy::panic)]
 
 // TODO: figure pub scopes for all the things
+pub mod batch;
 pub mod bitreader;
+#[cfg(feature = "dota2")]
+pub mod combatlog;
 pub mod demofile;
 pub mod demostream;
 pub mod entities;
 pub mod entityclasses;
+pub mod entityview;
 pub(crate) mod fielddecoder;
 pub(crate) mod fieldmetadata;
 pub mod fieldpath;
 pub mod fieldvalue;
 pub mod flattenedserializers;
+pub mod gameevents;
 pub(crate) mod instancebaseline;
+pub mod keyframes;
+pub mod messagehandler;
+pub mod parallel;
 pub mod parser;
 pub(crate) mod quantizedfloat;
+pub mod serializercache;
+pub mod snapshots;
 pub mod stringtables;
+#[cfg(test)]
+mod testutil;
 
 // own crate re-exports
 pub(crate) use haste_vartype as vartype;
@@ -35,9 +47,10 @@ pub use valveprotos;
 // second as metric (inspired by
 // https://github.com/markus-wa/demoinfocs-golang?tab=readme-ov-file#performance--benchmarks).
 
-// TODO: don't ignore length of vectors (dynamic length arrays) because they may
-// contain garbage; see
+// NOTE: length of vectors (dynamic length arrays) must not be ignored because
+// they may contain garbage; see
 // https://github.com/markus-wa/demoinfocs-golang/issues/450 for details.
+// entityview::ArrayView respects it.
 
 // TODO: generate list of entities (/flattened serializers) where it'll be
 // possible to get "the thing" by name hash and construct it.
@@ -53,8 +66,6 @@ pub use valveprotos;
 // hash and uses it for comparisons, it discards the string - this is nice. see
 // public/tier1/utlstringtoken.h
 
-// TODO: figure out combat log
-
 // NOTE: preserve-metadata feature is enabled in haste_dota2_atoms_codegen
 // crate, but it's disabled everywhere else. command `cargo build --release
 // --bin emptybench` produces a release build in which this feature is enabled,
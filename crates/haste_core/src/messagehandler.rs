@@ -0,0 +1,272 @@
+use std::hash::BuildHasherDefault;
+use std::marker::PhantomData;
+
+use anyhow::Result;
+use nohash::NoHashMap;
+use valveprotos::common;
+#[cfg(feature = "deadlock")]
+use valveprotos::deadlock;
+#[cfg(feature = "dota2")]
+use valveprotos::dota2;
+use valveprotos::prost::Message;
+
+use crate::demostream::CmdHeader;
+use crate::entities::{Entity, EntityEvent, UpdatedField};
+use crate::gameevents::GameEvent;
+use crate::parser::{Context, Visitor};
+use crate::stringtables::{StringTable, UpdatedStringTableItem};
+
+// NOTE: packet messages arrive as (type, bytes) pairs; type is a value of one of valve's message
+// enums (SvcMessages, NetMessages, EBaseUserMessages, EBaseGameEvents, EDotaUserMessages,
+// CitadelUserMessageIds, etc.). enum values do not overlap, thus plain u32 is enough to identify
+// the message.
+
+/// packet message with its id (value of the message enum it belongs to). ties message type to
+/// its id, see [`MessageDispatcher::with`].
+///
+/// messages that are not covered can be handled in [`Visitor::on_packet`].
+pub trait PacketMessage: Message + Default {
+    const ID: u32;
+}
+
+macro_rules! impl_packet_message {
+    ($($msg:ty => $id:expr),* $(,)?) => {
+        $(
+            impl PacketMessage for $msg {
+                const ID: u32 = $id as u32;
+            }
+        )*
+    };
+}
+
+impl_packet_message! {
+    common::CsvcMsgServerInfo => common::SvcMessages::SvcServerInfo,
+    common::CsvcMsgCreateStringTable => common::SvcMessages::SvcCreateStringTable,
+    common::CsvcMsgUpdateStringTable => common::SvcMessages::SvcUpdateStringTable,
+    common::CsvcMsgPacketEntities => common::SvcMessages::SvcPacketEntities,
+    common::CnetMsgTick => common::NetMessages::NetTick,
+    common::CMsgSource1LegacyGameEventList =>
+        common::EBaseGameEvents::GeSource1LegacyGameEventList,
+    common::CMsgSource1LegacyGameEvent => common::EBaseGameEvents::GeSource1LegacyGameEvent,
+}
+
+#[cfg(feature = "dota2")]
+impl_packet_message! {
+    dota2::CdotaUserMsgChatMessage => dota2::EDotaUserMessages::DotaUmChatMessage,
+    dota2::CMsgDotaCombatLogEntry => dota2::EDotaUserMessages::DotaUmCombatLogDataHltv,
+    dota2::CdotaUserMsgCombatLogBulkData => dota2::EDotaUserMessages::DotaUmCombatLogBulkData,
+}
+
+#[cfg(feature = "deadlock")]
+impl_packet_message! {
+    deadlock::CCitadelUserMsgHeroKilled => deadlock::CitadelUserMessageIds::KEUserMsgHeroKilled,
+}
+
+/// a typed handler of a decoded packet message.
+///
+/// it is implemented for all closures (and functions) with the matching signature.
+pub trait MessageHandler<V, M> {
+    fn handle(&self, visitor: &mut V, ctx: &Context, msg: &M) -> Result<()>;
+}
+
+impl<V, M, F> MessageHandler<V, M> for F
+where
+    F: Fn(&mut V, &Context, &M) -> Result<()>,
+{
+    #[inline]
+    fn handle(&self, visitor: &mut V, ctx: &Context, msg: &M) -> Result<()> {
+        self(visitor, ctx, msg)
+    }
+}
+
+// NOTE: type erasure of MessageHandler so that handlers of different message types can be stored
+// side by side.
+trait DecodeAndHandle<V> {
+    fn decode_and_handle(&self, visitor: &mut V, ctx: &Context, data: &[u8]) -> Result<()>;
+}
+
+struct TypedHandler<M, H> {
+    handler: H,
+    _phantom: PhantomData<fn(&M)>,
+}
+
+impl<V, M, H> DecodeAndHandle<V> for TypedHandler<M, H>
+where
+    M: Message + Default,
+    H: MessageHandler<V, M>,
+{
+    #[inline]
+    fn decode_and_handle(&self, visitor: &mut V, ctx: &Context, data: &[u8]) -> Result<()> {
+        let msg = M::decode(data)?;
+        self.handler.handle(visitor, ctx, &msg)
+    }
+}
+
+/// [`MessageDispatcher`] is a [`Visitor`] that decodes packet messages of registered types and
+/// dispatches them to typed handlers. messages of types that were not registered are not decoded.
+///
+/// all callbacks are forwarded to the wrapped visitor; handlers receive mutable access to it.
+pub struct MessageDispatcher<V> {
+    visitor: V,
+    // NOTE: each handler decodes message on its own. having multiple handlers for the same
+    // message type means that the message will be decoded multiple times.
+    handlers: NoHashMap<u32, Vec<Box<dyn DecodeAndHandle<V>>>>,
+}
+
+impl<V> MessageDispatcher<V> {
+    pub fn new(visitor: V) -> Self {
+        Self {
+            visitor,
+            handlers: NoHashMap::with_capacity_and_hasher(16, BuildHasherDefault::default()),
+        }
+    }
+
+    /// registers a handler for messages of type `M` (id of the message is [`PacketMessage::ID`]).
+    /// type of the message is inferred from the handler.
+    pub fn with<M, H>(mut self, handler: H) -> Self
+    where
+        M: PacketMessage + 'static,
+        H: MessageHandler<V, M> + 'static,
+    {
+        self.handlers
+            .entry(M::ID)
+            .or_default()
+            .push(Box::new(TypedHandler {
+                handler,
+                _phantom: PhantomData,
+            }));
+        self
+    }
+
+    #[inline]
+    pub fn has_handler<M: PacketMessage>(&self) -> bool {
+        self.handlers.contains_key(&M::ID)
+    }
+
+    #[inline]
+    pub fn visitor(&self) -> &V {
+        &self.visitor
+    }
+
+    #[inline]
+    pub fn visitor_mut(&mut self) -> &mut V {
+        &mut self.visitor
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> V {
+        self.visitor
+    }
+}
+
+impl<V: Visitor> Visitor for MessageDispatcher<V> {
+    #[inline]
+    fn on_entity(
+        &mut self,
+        ctx: &Context,
+        event: EntityEvent,
+        entity: &Entity,
+        updated_fields: &[UpdatedField],
+    ) -> Result<()> {
+        self.visitor.on_entity(ctx, event, entity, updated_fields)
+    }
+
+    #[inline]
+    fn on_cmd(&mut self, ctx: &Context, cmd_header: &CmdHeader, data: &[u8]) -> Result<()> {
+        self.visitor.on_cmd(ctx, cmd_header, data)
+    }
+
+    fn on_packet(&mut self, ctx: &Context, packet_type: u32, data: &[u8]) -> Result<()> {
+        self.visitor.on_packet(ctx, packet_type, data)?;
+
+        if let Some(handlers) = self.handlers.get(&packet_type) {
+            for handler in handlers.iter() {
+                handler.decode_and_handle(&mut self.visitor, ctx, data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn on_game_event(&mut self, ctx: &Context, game_event: &GameEvent) -> Result<()> {
+        self.visitor.on_game_event(ctx, game_event)
+    }
+
+    #[inline]
+    fn on_string_table(
+        &mut self,
+        ctx: &Context,
+        string_table: &StringTable,
+        updated_items: &[UpdatedStringTableItem],
+    ) -> Result<()> {
+        self.visitor
+            .on_string_table(ctx, string_table, updated_items)
+    }
+
+    #[inline]
+    fn on_tick_end(&mut self, ctx: &Context) -> Result<()> {
+        self.visitor.on_tick_end(ctx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use valveprotos::common::{CDemoFileInfo, CnetMsgTick, EDemoCommands};
+
+    use super::*;
+    use crate::demofile::{DemoFile, DemoWriter};
+    use crate::parser::Parser;
+    use crate::testutil;
+
+    #[derive(Default)]
+    struct State {
+        packet_types: Vec<u32>,
+        net_ticks: Vec<u32>,
+    }
+
+    impl Visitor for State {
+        fn on_packet(&mut self, _ctx: &Context, packet_type: u32, _data: &[u8]) -> Result<()> {
+            self.packet_types.push(packet_type);
+            Ok(())
+        }
+    }
+
+    fn net_tick(state: &mut State, _ctx: &Context, msg: &CnetMsgTick) -> Result<()> {
+        state.net_ticks.extend(msg.tick);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dispatch() -> anyhow::Result<()> {
+        // NOTE: message of an unregistered type is garbage; decoding it would fail.
+        const UNREGISTERED_ID: u32 = 9999;
+        let net_tick_msg = CnetMsgTick {
+            tick: Some(42),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let packet = testutil::make_packet(&[
+            (UNREGISTERED_ID, &[0xff; 4]),
+            (CnetMsgTick::ID, &net_tick_msg),
+        ]);
+
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        demo_writer.write_cmd_message(EDemoCommands::DemPacket, 0, &packet, false)?;
+        let data = demo_writer.finish(&CDemoFileInfo::default())?.into_inner();
+
+        let visitor = MessageDispatcher::new(State::default()).with(net_tick);
+        assert!(visitor.has_handler::<CnetMsgTick>());
+        let demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, visitor)?;
+        parser.run_to_end()?;
+
+        let state = parser.visitor().visitor();
+        assert_eq!(state.packet_types, vec![UNREGISTERED_ID, CnetMsgTick::ID]);
+        assert_eq!(state.net_ticks, vec![42]);
+
+        Ok(())
+    }
+}
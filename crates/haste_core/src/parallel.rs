@@ -0,0 +1,511 @@
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::Result;
+use valveprotos::common::EDemoCommands;
+
+use crate::demostream::{CmdHeader, DemoStream, SeekableDemoStream};
+use crate::entities::{Entity, EntityEvent, UpdatedField};
+use crate::entityclasses::EntityClasses;
+use crate::flattenedserializers::FlattenedSerializerContainer;
+use crate::gameevents::GameEvent;
+use crate::keyframes::{Keyframe, KeyframeIndex};
+use crate::parser::{Context, Parser, Visitor};
+use crate::stringtables::{StringTable, UpdatedStringTableItem};
+
+// NOTE: full packets contain everything that is needed to restore the state (see keyframes), thus
+// a demo can be split into segments that start at full packets and are parsed independently.
+// each segment is handled by its own parser (and visitor) on its own thread; flattened
+// serializers and entity classes are parsed only once and shared.
+//
+// segment's parser restores the state by seeking to segment's keyframe. visitor does not receive
+// any callbacks while that happens; it starts receiving them right after the keyframe tick. thus
+// visitors of all segments together observe every tick exactly once.
+//
+// NOTE: entities that exist at the start of a segment (they were created by the full packet) are
+// announced with synthetic EntityEvent::Created events when the visitor is activated, thus
+// visitors that track entities observe the same state as a sequential parser would.
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParallelParserError {
+    #[error("demo does not contain send tables or class info")]
+    MissingSchema,
+    #[error("segment #{index} worker panicked")]
+    WorkerPanicked { index: usize },
+    #[error("demo was not split into any segments")]
+    NoSegments,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub index: usize,
+    /// keyframe the segment's state is restored from; `None` for the first segment, it is parsed
+    /// from the very beginning. visitor receives callbacks for cmds with ticks that are greater
+    /// than keyframe's tick.
+    pub keyframe: Option<Keyframe>,
+    /// last tick of the segment (inclusive); `None` for the last segment.
+    pub end_tick: Option<i32>,
+}
+
+/// splits a demo into (at most) `segment_count` segments of approximately equal length.
+pub fn split_into_segments(keyframe_index: &KeyframeIndex, segment_count: usize) -> Vec<Segment> {
+    let keyframes: Vec<&Keyframe> = keyframe_index
+        .iter()
+        .filter(|keyframe| keyframe.tick >= 0)
+        .collect();
+    let segment_count = segment_count.clamp(1, keyframes.len() + 1);
+
+    let mut starts: Vec<Keyframe> = (1..segment_count)
+        .filter_map(|i| keyframes.get(i * keyframes.len() / segment_count))
+        .map(|keyframe| **keyframe)
+        .collect();
+    starts.dedup_by_key(|keyframe| keyframe.tick);
+
+    let mut segments = Vec::with_capacity(starts.len() + 1);
+    let mut keyframe = None;
+    for (index, next) in starts.iter().enumerate() {
+        segments.push(Segment {
+            index,
+            keyframe,
+            end_tick: Some(next.tick),
+        });
+        keyframe = Some(*next);
+    }
+    segments.push(Segment {
+        index: starts.len(),
+        keyframe,
+        end_tick: None,
+    });
+    segments
+}
+
+// NOTE: forwards callbacks only when the segment's state is restored.
+struct SegmentVisitor<V> {
+    inner: V,
+    active: bool,
+}
+
+impl<V: Visitor> SegmentVisitor<V> {
+    // NOTE: entities are announced in index order, as they would be by a full packet. there are no
+    // updated fields because nothing was decoded for the visitor.
+    fn activate(&mut self, ctx: &Context) -> Result<()> {
+        self.active = true;
+
+        let Some(entities) = ctx.entities() else {
+            return Ok(());
+        };
+        let mut restored: Vec<(&i32, &Entity)> = entities
+            .iter()
+            .filter(|(_, entity)| entities.is_wanted(entity))
+            .collect();
+        restored.sort_unstable_by_key(|(index, _)| **index);
+        for (_, entity) in restored {
+            self.inner.on_entity(
+                ctx,
+                EntityEvent::Created {
+                    from_baseline: false,
+                },
+                entity,
+                &[],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<V: Visitor> Visitor for SegmentVisitor<V> {
+    fn on_entity(
+        &mut self,
+        ctx: &Context,
+        event: EntityEvent,
+        entity: &Entity,
+        updated_fields: &[UpdatedField],
+    ) -> Result<()> {
+        if self.active {
+            self.inner.on_entity(ctx, event, entity, updated_fields)?;
+        }
+        Ok(())
+    }
+
+    fn on_cmd(&mut self, ctx: &Context, cmd_header: &CmdHeader, data: &[u8]) -> Result<()> {
+        if self.active {
+            self.inner.on_cmd(ctx, cmd_header, data)?;
+        }
+        Ok(())
+    }
+
+    fn on_packet(&mut self, ctx: &Context, packet_type: u32, data: &[u8]) -> Result<()> {
+        if self.active {
+            self.inner.on_packet(ctx, packet_type, data)?;
+        }
+        Ok(())
+    }
+
+    fn on_game_event(&mut self, ctx: &Context, game_event: &GameEvent) -> Result<()> {
+        if self.active {
+            self.inner.on_game_event(ctx, game_event)?;
+        }
+        Ok(())
+    }
+
+    fn on_string_table(
+        &mut self,
+        ctx: &Context,
+        string_table: &StringTable,
+        updated_items: &[UpdatedStringTableItem],
+    ) -> Result<()> {
+        if self.active {
+            self.inner
+                .on_string_table(ctx, string_table, updated_items)?;
+        }
+        Ok(())
+    }
+
+    fn on_tick_end(&mut self, ctx: &Context) -> Result<()> {
+        if self.active {
+            self.inner.on_tick_end(ctx)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_schema<D: DemoStream>(
+    demo_stream: &mut D,
+) -> Result<(Arc<FlattenedSerializerContainer>, Arc<EntityClasses>)> {
+    let mut serializers = None;
+    let mut entity_classes = None;
+    loop {
+        let cmd_header = demo_stream.read_cmd_header()?;
+        match cmd_header.cmd {
+            EDemoCommands::DemSendTables => {
+                let cmd_body = demo_stream.read_cmd(&cmd_header)?;
+                let cmd = D::decode_cmd_send_tables(cmd_body)?;
+                serializers = Some(Arc::new(FlattenedSerializerContainer::parse(cmd)?));
+            }
+            EDemoCommands::DemClassInfo => {
+                let cmd_body = demo_stream.read_cmd(&cmd_header)?;
+                let cmd = D::decode_cmd_class_info(cmd_body)?;
+                entity_classes = Some(Arc::new(EntityClasses::parse(cmd)));
+            }
+            // NOTE: DemSyncTick is the last signon cmd.
+            EDemoCommands::DemSyncTick => break,
+            _ => demo_stream.skip_cmd(&cmd_header)?,
+        }
+
+        if serializers.is_some() && entity_classes.is_some() {
+            break;
+        }
+    }
+
+    match (serializers, entity_classes) {
+        (Some(serializers), Some(entity_classes)) => Ok((serializers, entity_classes)),
+        _ => Err(ParallelParserError::MissingSchema.into()),
+    }
+}
+
+/// parses segments of a single demo (see [`split_into_segments`]) in parallel.
+///
+/// each segment needs its own demo stream, thus streams are produced by `make_demo_stream`
+/// (for example by opening the same file again).
+pub struct ParallelParser<F> {
+    make_demo_stream: F,
+    keyframe_index: Option<KeyframeIndex>,
+    segment_count: usize,
+}
+
+impl<D, F> ParallelParser<F>
+where
+    D: SeekableDemoStream,
+    F: Fn() -> Result<D> + Sync,
+{
+    /// segment count defaults to the number of available cores.
+    pub fn new(make_demo_stream: F) -> Self {
+        Self {
+            make_demo_stream,
+            keyframe_index: None,
+            segment_count: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    /// use a pre-built keyframe index instead of building one. [`ParallelParser::run`] fails if
+    /// the index was not built for the demo (see [`KeyframeIndex::matches`]).
+    pub fn with_keyframe_index(mut self, keyframe_index: KeyframeIndex) -> Self {
+        self.keyframe_index = Some(keyframe_index);
+        self
+    }
+
+    pub fn with_segment_count(mut self, segment_count: usize) -> Self {
+        self.segment_count = segment_count;
+        self
+    }
+
+    /// parses all segments; `make_visitor` is called once per segment. visitors are returned in
+    /// segment order, which is also tick order. see [`ParallelParser::run_and_merge`].
+    pub fn run<V, M>(&self, make_visitor: M) -> Result<Vec<V>>
+    where
+        V: Visitor + Send,
+        M: Fn(&Segment) -> V + Sync,
+    {
+        let mut demo_stream = (self.make_demo_stream)()?;
+        let (serializers, entity_classes) = read_schema(&mut demo_stream)?;
+        let keyframe_index = match self.keyframe_index.as_ref() {
+            Some(keyframe_index) => {
+                keyframe_index.ensure_matches(&mut demo_stream)?;
+                keyframe_index.clone()
+            }
+            None => KeyframeIndex::build(&mut demo_stream)?,
+        };
+        drop(demo_stream);
+
+        let segments = split_into_segments(&keyframe_index, self.segment_count);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = segments
+                .iter()
+                .map(|segment| {
+                    let keyframe_index = keyframe_index.clone();
+                    let serializers = serializers.clone();
+                    let entity_classes = entity_classes.clone();
+                    let make_visitor = &make_visitor;
+                    scope.spawn(move || -> Result<V> {
+                        let demo_stream = (self.make_demo_stream)()?;
+                        let visitor = SegmentVisitor {
+                            inner: make_visitor(segment),
+                            active: segment.keyframe.is_none(),
+                        };
+                        let mut parser = Parser::from_stream_with_visitor(demo_stream, visitor)?;
+                        parser.set_keyframe_index(Some(keyframe_index))?;
+                        parser.set_schema(serializers, entity_classes);
+
+                        if let Some(keyframe) = segment.keyframe {
+                            parser.run_to_tick(keyframe.tick)?;
+                            let (ctx, visitor) = parser.context_and_visitor_mut();
+                            visitor.activate(ctx)?;
+                        }
+                        match segment.end_tick {
+                            Some(end_tick) => parser.run_deltas_to_tick(end_tick)?,
+                            None => parser.run_to_end()?,
+                        }
+
+                        Ok(parser.into_visitor().inner)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .zip(segments.iter())
+                .map(|(handle, segment)| {
+                    handle
+                        .join()
+                        .map_err(|_| ParallelParserError::WorkerPanicked {
+                            index: segment.index,
+                        })?
+                })
+                .collect()
+        })
+    }
+
+    /// same as [`ParallelParser::run`], but folds visitors into one with `merge`. `merge` receives
+    /// the accumulated visitor (of earlier segments) and the visitor of the next segment.
+    pub fn run_and_merge<V, M, R>(&self, make_visitor: M, merge: R) -> Result<V>
+    where
+        V: Visitor + Send,
+        M: Fn(&Segment) -> V + Sync,
+        R: FnMut(V, V) -> V,
+    {
+        self.run(make_visitor)?
+            .into_iter()
+            .reduce(merge)
+            .ok_or_else(|| ParallelParserError::NoSegments.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    use valveprotos::common::{CDemoFileInfo, CDemoFullPacket};
+
+    use super::*;
+    use crate::demofile::{DemoFile, DemoWriter};
+    use crate::fieldvalue::FieldValue;
+    use crate::testutil::{self, EntityOp};
+
+    #[test]
+    fn test_split_into_segments() -> anyhow::Result<()> {
+        let data = testutil::make_packets_demo((0..=9000).step_by(30), Some(1800))?;
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let keyframe_index = KeyframeIndex::build(&mut demo_file)?;
+
+        let segments = split_into_segments(&keyframe_index, 3);
+        let bounds: Vec<(Option<i32>, Option<i32>)> = segments
+            .iter()
+            .map(|segment| {
+                (
+                    segment.keyframe.map(|keyframe| keyframe.tick),
+                    segment.end_tick,
+                )
+            })
+            .collect();
+        assert_eq!(
+            bounds,
+            vec![
+                (None, Some(3600)),
+                (Some(3600), Some(7200)),
+                (Some(7200), None)
+            ]
+        );
+
+        // NOTE: there can't be more segments than keyframes (+ the leading one).
+        assert_eq!(split_into_segments(&keyframe_index, 100).len(), 7);
+        assert_eq!(split_into_segments(&keyframe_index, 0).len(), 1);
+
+        Ok(())
+    }
+
+    // NOTE: entity #1 lives through the whole demo and changes every tick; #3 exists only in the
+    // first and the second segment; #2 appears in the second segment and changes in the third.
+    fn make_entities_demo() -> anyhow::Result<Vec<u8>> {
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        demo_writer.write_cmd_message(
+            EDemoCommands::DemSendTables,
+            -1,
+            &testutil::make_send_tables(&[("CFoo", &[("m_iHealth", "int32", None)])]),
+            false,
+        )?;
+        demo_writer.write_cmd_message(
+            EDemoCommands::DemClassInfo,
+            -1,
+            &testutil::make_class_info(),
+            false,
+        )?;
+        demo_writer.write_cmd(EDemoCommands::DemSyncTick, -1, &[], false)?;
+
+        let mut live: BTreeMap<i32, i64> = BTreeMap::new();
+        for tick in 0..180 {
+            let mut ops = Vec::new();
+            for (index, value) in [(1, tick as i64), (2, 5), (3, 7)] {
+                let op = match (index, tick) {
+                    (1, 0) | (3, 30) | (2, 100) => EntityOp::Create(value),
+                    (1, _) => EntityOp::Update(value),
+                    (3, 90) => EntityOp::Delete,
+                    (2, 150) => EntityOp::Update(6),
+                    _ => continue,
+                };
+                match op {
+                    EntityOp::Create(value) | EntityOp::Update(value) => {
+                        live.insert(index, value);
+                    }
+                    EntityOp::Delete => {
+                        live.remove(&index);
+                    }
+                }
+                ops.push((index, op));
+            }
+            demo_writer.write_cmd_message(
+                EDemoCommands::DemPacket,
+                tick,
+                &testutil::make_packet_entities(&ops),
+                false,
+            )?;
+
+            if tick == 60 || tick == 120 {
+                let creates: Vec<(i32, EntityOp)> = live
+                    .iter()
+                    .map(|(index, value)| (*index, EntityOp::Create(*value)))
+                    .collect();
+                demo_writer.write_cmd_message(
+                    EDemoCommands::DemFullPacket,
+                    tick,
+                    &CDemoFullPacket {
+                        string_table: None,
+                        packet: Some(testutil::make_packet_entities(&creates)),
+                    },
+                    false,
+                )?;
+            }
+        }
+        let cursor = demo_writer.finish(&CDemoFileInfo::default())?;
+        Ok(cursor.into_inner())
+    }
+
+    // NOTE: collects state of all entities at the end of each tick, as seen through callbacks.
+    #[derive(Default)]
+    struct StateCollector {
+        live: BTreeMap<i32, Option<i64>>,
+        states: Vec<(i32, Vec<(i32, Option<i64>)>)>,
+    }
+
+    fn health(entity: &Entity) -> Option<i64> {
+        match entity.get(&fxhash::hash_bytes(b"m_iHealth")) {
+            Some(FieldValue::I64(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    impl Visitor for StateCollector {
+        fn on_entity(
+            &mut self,
+            _ctx: &Context,
+            event: EntityEvent,
+            entity: &Entity,
+            _updated_fields: &[UpdatedField],
+        ) -> Result<()> {
+            match event {
+                EntityEvent::Created { .. } => {
+                    self.live.insert(entity.index(), health(entity));
+                }
+                // NOTE: updates of entities that were never announced are not tracked.
+                EntityEvent::Updated => {
+                    if let Some(value) = self.live.get_mut(&entity.index()) {
+                        *value = health(entity);
+                    }
+                }
+                EntityEvent::Deleted => {
+                    self.live.remove(&entity.index());
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+
+        fn on_tick_end(&mut self, ctx: &Context) -> Result<()> {
+            let state = self
+                .live
+                .iter()
+                .map(|(index, value)| (*index, *value))
+                .collect();
+            self.states.push((ctx.tick(), state));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() -> anyhow::Result<()> {
+        let data = make_entities_demo()?;
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data.clone()))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, StateCollector::default())?;
+        parser.run_to_end()?;
+        let sequential = parser.into_visitor().states;
+        assert_eq!(sequential.len(), 180);
+        assert_eq!(sequential[60], (60, vec![(1, Some(60)), (3, Some(7))]));
+
+        let parallel_parser =
+            ParallelParser::new(|| Ok(DemoFile::start_reading(Cursor::new(data.clone()))?))
+                .with_segment_count(3);
+        let parallel = parallel_parser.run_and_merge(
+            |_segment| StateCollector::default(),
+            |mut acc, next| {
+                acc.states.extend(next.states);
+                acc
+            },
+        )?;
+        assert_eq!(parallel.states, sequential);
+
+        Ok(())
+    }
+}
@@ -1,33 +1,232 @@
+use std::fmt;
 use std::io::{self, SeekFrom};
+use std::sync::Arc;
 
-use anyhow::Result;
 use valveprotos::common::{
-    CDemoFullPacket, CDemoPacket, CDemoStringTables, CsvcMsgCreateStringTable,
-    CsvcMsgPacketEntities, CsvcMsgServerInfo, CsvcMsgUpdateStringTable, EDemoCommands, SvcMessages,
+    CDemoFullPacket, CDemoPacket, CDemoStringTables, CMsgSource1LegacyGameEvent,
+    CMsgSource1LegacyGameEventList, CsvcMsgCreateStringTable, CsvcMsgPacketEntities,
+    CsvcMsgServerInfo, CsvcMsgUpdateStringTable, EBaseGameEvents, EDemoCommands, SvcMessages,
 };
-use valveprotos::prost::Message;
+use valveprotos::prost::{self, Message};
 
-use crate::bitreader::BitReader;
+use crate::bitreader::{BitReader, BitReaderOverflowError};
 use crate::demofile::{DemoHeaderError, DEMO_RECORD_BUFFER_SIZE};
-use crate::demostream::{CmdHeader, DemoStream};
-use crate::entities::{DeltaHeader, Entity, EntityContainer};
+use crate::demostream::{
+    CmdHeader, DecodeCmdError, DemoStream, ReadCmdError, ReadCmdHeaderError, SeekableDemoStream,
+};
+use crate::entities::{
+    DeltaHeader, Entity, EntityContainer, EntityEvent, EntityFilter, EntityParseError, UpdatedField,
+};
 use crate::entityclasses::EntityClasses;
 use crate::fielddecoder::FieldDecodeContext;
-use crate::flattenedserializers::FlattenedSerializerContainer;
-use crate::instancebaseline::{InstanceBaseline, INSTANCE_BASELINE_TABLE_NAME};
-use crate::stringtables::StringTableContainer;
+use crate::flattenedserializers::{FlattenedSerializerContainer, FlattenedSerializersError};
+use crate::gameevents::{GameEvent, GameEventList};
+use crate::instancebaseline::{
+    InstanceBaseline, InstanceBaselineError, INSTANCE_BASELINE_TABLE_NAME,
+};
+use crate::keyframes::{KeyframeIndex, KeyframeIndexError};
+use crate::serializercache::{SerializerCache, SerializerCacheError};
+use crate::snapshots::{ContextSnapshot, SnapshotConfig, SnapshotStore};
+use crate::stringtables::{StringTable, StringTableContainer, UpdatedStringTableItem};
 
 // as can be observed when dumping commands. also as specified in clarity
 // (src/main/java/skadistats/clarity/model/engine/AbstractDotaEngineType.java)
 // and documented in manta (string_table.go).
 //
 // NOTE: full packet interval is 1800 only if tick interval is 1 / 30 - this is true for dota2, but
-// deaclock's tick interval is x 2.
+// deaclock's tick interval is x 2 and cs2's is x 2.1(3).
 const DEFAULT_FULL_PACKET_INTERVAL: i32 = 1800;
 // NOTE: tick interval is needed to be able to correctly decide simulation time values.
-// dota2's tick interval is 1 / 30; deadlock's 1 / 60 - they are constant.
+// dota2's tick interval is 1 / 30; deadlock's 1 / 60; cs2's 1 / 64 - they are constant.
 const DEFAULT_TICK_INTERVAL: f32 = 1.0 / 30.0;
 
+#[derive(thiserror::Error, Debug)]
+pub enum ParserError {
+    // demo stream
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    ReadCmdHeaderError(#[from] ReadCmdHeaderError),
+    #[error(transparent)]
+    ReadCmdError(#[from] ReadCmdError),
+    #[error(transparent)]
+    DecodeCmdError(#[from] DecodeCmdError),
+    // packets and messages
+    #[error(transparent)]
+    DecodeProtobufError(#[from] prost::DecodeError),
+    #[error(transparent)]
+    DecompressError(#[from] snap::Error),
+    #[error(transparent)]
+    BitReaderOverflowError(#[from] BitReaderOverflowError),
+    #[error(transparent)]
+    EntityParseError(#[from] EntityParseError),
+    #[error(transparent)]
+    FlattenedSerializersError(#[from] FlattenedSerializersError),
+    #[error(transparent)]
+    SerializerCacheError(#[from] SerializerCacheError),
+    #[error("failed to update instance baseline: {0}")]
+    InstanceBaselineError(#[from] InstanceBaselineError),
+    #[error(transparent)]
+    KeyframeIndexError(#[from] KeyframeIndexError),
+    #[error("packet message size ({size}) exceeds buffer size")]
+    PacketMessageTooLarge { size: usize },
+    #[error("packet entities arrived before send tables or class info")]
+    MissingSchema,
+    #[error("string table #{table_id} does not exist")]
+    UnknownStringTable { table_id: usize },
+    /// error that was returned by one of [`Visitor`]'s methods.
+    #[error(transparent)]
+    VisitorError(anyhow::Error),
+    /// any of the above, along with where it happened.
+    ///
+    /// NOTE: the error is not exposed as the source because it is a part of the message already;
+    /// see [`ParserError::root`].
+    #[error("{error} (at {context})")]
+    WithContext {
+        context: ErrorContext,
+        error: Box<ParserError>,
+    },
+}
+
+impl ParserError {
+    /// returns where the error happened, if known.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// returns the error without context.
+    pub fn root(&self) -> &ParserError {
+        match self {
+            Self::WithContext { error, .. } => error,
+            _ => self,
+        }
+    }
+
+    /// returns visitor's error if that is what stopped the parser.
+    pub fn visitor_error(&self) -> Option<&anyhow::Error> {
+        match self.root() {
+            Self::VisitorError(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    // NOTE: returns None if the error was not caused by damaged data (for example visitor's error or
+    // failure of the underlying reader).
+    fn damage_kind(&self) -> Option<DamageKind> {
+        match self.root() {
+            Self::ReadCmdHeaderError(ReadCmdHeaderError::IoError(err))
+            | Self::ReadCmdError(ReadCmdError::IoError(err)) => {
+                (err.kind() == io::ErrorKind::UnexpectedEof).then_some(DamageKind::Stream)
+            }
+            Self::ReadCmdHeaderError(_) | Self::ReadCmdError(ReadCmdError::BodyTooLarge { .. }) => {
+                Some(DamageKind::Stream)
+            }
+            Self::ReadCmdError(ReadCmdError::DecompressError(_))
+            | Self::DecodeCmdError(_)
+            | Self::DecodeProtobufError(_)
+            | Self::DecompressError(_)
+            | Self::BitReaderOverflowError(_)
+            | Self::EntityParseError(
+                EntityParseError::BitReaderOverflowError(_)
+                | EntityParseError::UnknownField { .. }
+                | EntityParseError::UnknownEntity { .. }
+                | EntityParseError::UnknownClass { .. }
+                | EntityParseError::MissingSerializer { .. },
+            )
+            | Self::InstanceBaselineError(_)
+            | Self::PacketMessageTooLarge { .. }
+            | Self::MissingSchema
+            | Self::UnknownStringTable { .. } => Some(DamageKind::Cmd),
+            _ => None,
+        }
+    }
+
+    // NOTE: context is merged instead of being nested; fields that were set closer to the origin
+    // of the error take precedence.
+    fn with_context(self, outer: ErrorContext) -> Self {
+        match self {
+            Self::WithContext { context, error } => Self::WithContext {
+                context: ErrorContext {
+                    tick: context.tick.or(outer.tick),
+                    cmd: context.cmd.or(outer.cmd),
+                    offset: context.offset.or(outer.offset),
+                    entity_index: context.entity_index.or(outer.entity_index),
+                },
+                error,
+            },
+            error => Self::WithContext {
+                context: outer,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    fn with_entity_index(self, entity_index: i32) -> Self {
+        self.with_context(ErrorContext {
+            entity_index: Some(entity_index),
+            ..Default::default()
+        })
+    }
+}
+
+enum DamageKind {
+    /// cmd was read, but its content is damaged; following cmds are readable.
+    Cmd,
+    /// cmd can't be read (data is truncated or cmd header is damaged); nothing after it is
+    /// readable.
+    Stream,
+}
+
+/// damaged part of a demo that was skipped in recovery mode; see [`Parser::set_recovery`].
+#[derive(Debug)]
+pub struct Damage {
+    /// error that was caused by the damaged cmd; see [`ParserError::context`] for its position.
+    pub error: ParserError,
+    /// count of cmds that were not handled, including the damaged one.
+    pub skipped_cmds: usize,
+    /// tick of the full packet the state was restored from; `None` if the data ended before that.
+    pub resumed_tick: Option<i32>,
+}
+
+/// where a [`ParserError`] happened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub tick: Option<i32>,
+    pub cmd: Option<EDemoCommands>,
+    /// position of cmd's header in the stream.
+    ///
+    /// NOTE: parser tracks the position on its own (forward-only streams can't tell it); it is
+    /// unknown after the stream was accessed through [`Parser::demo_stream_mut`], until the next
+    /// seek (see [`Parser::run_to_tick`]).
+    pub offset: Option<u64>,
+    pub entity_index: Option<i32>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::with_capacity(4);
+        if let Some(tick) = self.tick {
+            parts.push(format!("tick {tick}"));
+        }
+        if let Some(cmd) = self.cmd {
+            parts.push(format!("cmd {cmd:?}"));
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("offset {offset}"));
+        }
+        if let Some(entity_index) = self.entity_index {
+            parts.push(format!("entity #{entity_index}"));
+        }
+        if parts.is_empty() {
+            return f.write_str("unknown position");
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
 // NOTE: primary purpose of Context is to to be able to expose state to the
 // public; attempts to put parser into arguments of Visitor's method did not
 // result in anything satisfyable.
@@ -36,12 +235,20 @@ const DEFAULT_TICK_INTERVAL: f32 = 1.0 / 30.0;
 // there also must be an intermediary variant (or maybe stuff can be piled into Uninitialized
 // variant) for incremental initialization. this may improve public api because string_tables,
 // serializer, and other methods will not have to return Option when context is initialized.
+//
+// NOTE: context is Send + Sync, thus a clone can be handed to another thread for analysis while
+// parsing continues. serializers, entity classes and game event list are shared; entities,
+// baseline entities and user data of string table items are shared until they change
+// (copy-on-write); scratch buffers are not copied. what is copied are maps of entities and string
+// table items (along with strings of the items), cost of a clone is proportional to their count.
+#[derive(Clone)]
 pub struct Context {
     string_tables: StringTableContainer,
     instance_baseline: InstanceBaseline,
-    serializers: Option<FlattenedSerializerContainer>,
-    entity_classes: Option<EntityClasses>,
+    serializers: Option<Arc<FlattenedSerializerContainer>>,
+    entity_classes: Option<Arc<EntityClasses>>,
     entities: EntityContainer,
+    game_event_list: Option<Arc<GameEventList>>,
     tick_interval: f32,
     full_packet_interval: i32,
     tick: i32,
@@ -62,12 +269,12 @@ impl Context {
 
     #[inline]
     pub fn serializers(&self) -> Option<&FlattenedSerializerContainer> {
-        self.serializers.as_ref()
+        self.serializers.as_deref()
     }
 
     #[inline]
     pub fn entity_classes(&self) -> Option<&EntityClasses> {
-        self.entity_classes.as_ref()
+        self.entity_classes.as_deref()
     }
 
     #[inline]
@@ -79,41 +286,153 @@ impl Context {
         }
     }
 
+    #[inline]
+    pub fn game_event_list(&self) -> Option<&GameEventList> {
+        self.game_event_list.as_deref()
+    }
+
     #[inline]
     pub fn tick_interval(&self) -> f32 {
         self.tick_interval
     }
 
+    #[inline]
+    pub fn full_packet_interval(&self) -> i32 {
+        self.full_packet_interval
+    }
+
     #[inline]
     pub fn tick(&self) -> i32 {
         self.tick
     }
+
+    #[inline]
+    fn is_initialized(&self) -> bool {
+        self.entity_classes.is_some() && !self.string_tables.is_empty()
+    }
+
+    fn take_snapshot(&self, offset: u64) -> ContextSnapshot {
+        let entities = self.entities.clone_entities();
+        let string_table_items = self.string_tables.clone_items();
+        // NOTE: entities and user data are shared with the live state (and with other snapshots)
+        // until they change, thus actual size is smaller.
+        let size = entities
+            .values()
+            .map(|entity| entity.approx_size())
+            .sum::<usize>()
+            + self
+                .string_tables
+                .tables()
+                .map(|table| table.approx_items_size())
+                .sum::<usize>();
+        ContextSnapshot {
+            tick: self.tick,
+            offset,
+            entities,
+            string_table_items,
+            size,
+        }
+    }
+
+    fn restore_snapshot(&mut self, snapshot: &ContextSnapshot) -> Result<(), ParserError> {
+        self.entities.restore_entities(&snapshot.entities);
+        self.string_tables
+            .restore_items(&snapshot.string_table_items);
+
+        // NOTE: instance baseline shares user data with string table items that were just
+        // replaced.
+        self.instance_baseline.clear();
+        if let (Some(string_table), Some(entity_classes)) = (
+            self.string_tables.find_table(INSTANCE_BASELINE_TABLE_NAME),
+            self.entity_classes.as_ref(),
+        ) {
+            self.instance_baseline
+                .update(string_table, entity_classes.classes)?;
+        }
+
+        self.tick = snapshot.tick;
+        self.prev_tick = snapshot.tick;
+
+        Ok(())
+    }
 }
 
+// NOTE: serializers, entity classes and game event list are not included; they describe the
+// schema, not the state.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Context {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        use crate::entities::SerializeEntities;
+
+        let mut state = serializer.serialize_struct("Context", 4)?;
+        state.serialize_field("tick", &self.tick)?;
+        state.serialize_field("tick_interval", &self.tick_interval)?;
+        // NOTE: serializers are used to resolve field names.
+        state.serialize_field(
+            "entities",
+            &SerializeEntities {
+                entities: &self.entities,
+                serializers: self.serializers.as_deref(),
+            },
+        )?;
+        state.serialize_field("string_tables", &self.string_tables)?;
+        state.end()
+    }
+}
+
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Context>();
+};
+
 pub trait Visitor {
-    // TODO: include updated fields (list of field paths?)
+    /// `updated_fields` contains fields that were decoded in the current update, along with their
+    /// previous values. it is empty for [`EntityEvent::LeftPvs`] and [`EntityEvent::Deleted`].
     #[allow(unused_variables)]
     fn on_entity(
         &mut self,
         ctx: &Context,
-        delta_header: DeltaHeader,
+        event: EntityEvent,
         entity: &Entity,
-    ) -> Result<()> {
+        updated_fields: &[UpdatedField],
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn on_cmd(&mut self, ctx: &Context, cmd_header: &CmdHeader, data: &[u8]) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn on_packet(&mut self, ctx: &Context, packet_type: u32, data: &[u8]) -> anyhow::Result<()> {
         Ok(())
     }
 
+    /// called for each game event; event's values are resolved using descriptors from
+    /// [`Context::game_event_list`]. events that are not described there are skipped.
     #[allow(unused_variables)]
-    fn on_cmd(&mut self, ctx: &Context, cmd_header: &CmdHeader, data: &[u8]) -> Result<()> {
+    fn on_game_event(&mut self, ctx: &Context, game_event: &GameEvent) -> anyhow::Result<()> {
         Ok(())
     }
 
+    /// called when items of a string table were created or modified by `SvcCreateStringTable`,
+    /// `SvcUpdateStringTable` or by a full packet (`CDemoStringTables`). current state of updated
+    /// items is accessible through [`StringTable::get_item`].
     #[allow(unused_variables)]
-    fn on_packet(&mut self, ctx: &Context, packet_type: u32, data: &[u8]) -> Result<()> {
+    fn on_string_table(
+        &mut self,
+        ctx: &Context,
+        string_table: &StringTable,
+        updated_items: &[UpdatedStringTableItem],
+    ) -> anyhow::Result<()> {
         Ok(())
     }
 
     #[allow(unused_variables)]
-    fn on_tick_end(&mut self, ctx: &Context) -> Result<()> {
+    fn on_tick_end(&mut self, ctx: &Context) -> anyhow::Result<()> {
         Ok(())
     }
 }
@@ -141,10 +460,26 @@ pub struct Parser<D: DemoStream, V: Visitor> {
     ctx: Context,
     // NOTE(blukai): is this the place for this? can it be moved "closer" to entities somewhere?
     field_decode_ctx: FieldDecodeContext,
+    keyframe_index: Option<KeyframeIndex>,
+    snapshot_store: Option<SnapshotStore>,
+    serializer_cache: Option<Arc<SerializerCache>>,
+    tolerant: bool,
+    recovery: bool,
+    damages: Vec<Damage>,
+    // NOTE: true after damage was skipped and until the state is restored; cmds are not handled
+    // meanwhile.
+    resyncing: bool,
+    // NOTE: position of the next cmd header in the stream; None if unknown. see
+    // ErrorContext::offset.
+    offset: Option<u64>,
+    // NOTE: false if the most recent run did not finish at cmd boundary (for example visitor
+    // returned an error); in that case state can't be used as a starting point for a seek.
+    can_resume: bool,
 }
 
 impl<D: DemoStream, V: Visitor> Parser<D, V> {
     pub fn from_stream_with_visitor(demo_stream: D, visitor: V) -> Result<Self, DemoHeaderError> {
+        let offset = Some(demo_stream.start_position());
         Ok(Self {
             demo_stream,
             buf: vec![0; DEMO_RECORD_BUFFER_SIZE],
@@ -155,12 +490,22 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                 instance_baseline: InstanceBaseline::default(),
                 serializers: None,
                 entity_classes: None,
+                game_event_list: None,
                 tick_interval: 0.0,
                 full_packet_interval: 0,
                 tick: -1,
                 prev_tick: -1,
             },
             field_decode_ctx: FieldDecodeContext::default(),
+            keyframe_index: None,
+            snapshot_store: None,
+            serializer_cache: None,
+            tolerant: false,
+            recovery: false,
+            damages: Vec::new(),
+            resyncing: false,
+            offset,
+            can_resume: false,
         })
     }
 
@@ -173,123 +518,197 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
     // recorded).
     //
     // must be publicly exposed for this to be actually useful.
-    fn run<F>(&mut self, mut handler: F) -> Result<()>
+    fn run<F>(&mut self, mut handler: F) -> Result<(), ParserError>
     where
-        F: FnMut(&mut Self, &CmdHeader) -> Result<ControlFlow>,
+        F: FnMut(&mut Self, &CmdHeader) -> Result<ControlFlow, ParserError>,
     {
+        self.can_resume = false;
         loop {
-            match self.demo_stream.read_cmd_header() {
-                Ok(cmd_header) => {
-                    self.ctx.prev_tick = self.ctx.tick;
-                    self.ctx.tick = cmd_header.tick;
-                    match handler(self, &cmd_header)? {
-                        ControlFlow::HandleCmd => {
-                            self.handle_cmd(&cmd_header)?;
-                            if self.ctx.prev_tick != self.ctx.tick {
-                                self.visitor.on_tick_end(&self.ctx)?;
-                            }
-                        }
-                        ControlFlow::SkipCmd => self.demo_stream.skip_cmd(&cmd_header)?,
-                        ControlFlow::IgnoreCmd => {}
-                        ControlFlow::Break => {
-                            self.demo_stream.unread_cmd_header(&cmd_header)?;
-                            self.ctx.tick = self.ctx.prev_tick;
-                            return Ok(());
-                        }
-                    }
+            let offset = self.offset;
+            let cmd_header = match self.demo_stream.read_cmd_header() {
+                Ok(cmd_header) => cmd_header,
+                // NOTE: only a stream that ends before the first byte of a cmd header ends cleanly.
+                // a header that is cut short, or a trailing byte that does not decode to a known
+                // cmd, is damage.
+                Err(ReadCmdHeaderError::IoError(_))
+                    if self.demo_stream.is_at_eof().unwrap_or_default() =>
+                {
+                    self.can_resume = true;
+                    return Ok(());
                 }
                 Err(err) => {
-                    if self.demo_stream.is_at_eof().unwrap_or_default() {
-                        return Ok(());
-                    }
-                    return Err(err.into());
+                    let err = ParserError::from(err).with_context(ErrorContext {
+                        tick: Some(self.ctx.tick),
+                        offset,
+                        ..Default::default()
+                    });
+                    return self.recover_or_fail(err);
                 }
+            };
+            // NOTE: the cmd is assumed to be consumed; see run_cmd for exceptions.
+            self.offset =
+                offset.map(|offset| offset + cmd_header.size as u64 + cmd_header.body_size as u64);
+
+            let result = self
+                .run_cmd(&cmd_header, offset, &mut handler)
+                .map_err(|err| {
+                    err.with_context(ErrorContext {
+                        tick: Some(cmd_header.tick),
+                        cmd: Some(cmd_header.cmd),
+                        offset,
+                        entity_index: None,
+                    })
+                });
+            match result {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(err) => match err.damage_kind() {
+                    Some(DamageKind::Cmd) if self.can_recover() => self.skip_damage(err),
+                    _ => return self.recover_or_fail(err),
+                },
             }
         }
     }
 
-    pub fn run_to_end(&mut self) -> Result<()> {
-        self.run(|_notnotself, _cmd_header| Ok(ControlFlow::HandleCmd))
+    // NOTE: recovery is not possible if the damage is in signon cmds; schema can't be restored
+    // from full packets.
+    fn can_recover(&self) -> bool {
+        self.recovery && self.ctx.serializers.is_some() && self.ctx.entity_classes.is_some()
     }
 
-    fn reset(&mut self) -> Result<(), io::Error> {
-        self.demo_stream
-            .seek(SeekFrom::Start(self.demo_stream.start_position()))?;
-
-        self.ctx.entities.clear();
-        self.ctx.string_tables.clear();
-        self.ctx.instance_baseline.clear();
-        self.ctx.tick = -1;
-        self.ctx.prev_tick = -1;
+    // NOTE: consecutive damage (for example the full packet that was supposed to restore the
+    // state is damaged too) extends the current one.
+    fn skip_damage(&mut self, err: ParserError) {
+        match self.damages.last_mut().filter(|_| self.resyncing) {
+            Some(damage) => damage.skipped_cmds += 1,
+            None => self.damages.push(Damage {
+                error: err,
+                skipped_cmds: 1,
+                resumed_tick: None,
+            }),
+        }
+        self.resyncing = true;
+    }
 
+    // NOTE: in recovery mode damage that makes the rest of the stream unreadable ends the run as
+    // if the end of the stream was reached.
+    fn recover_or_fail(&mut self, err: ParserError) -> Result<(), ParserError> {
+        if !matches!(err.damage_kind(), Some(DamageKind::Stream)) || !self.can_recover() {
+            return Err(err);
+        }
+        self.skip_damage(err);
         Ok(())
     }
 
-    pub fn run_to_tick(&mut self, target_tick: i32) -> Result<()> {
-        // TODO: do not allow tick to be less then -1
+    // NOTE: cmds are skipped until a full packet is reached; the state is restored from it.
+    fn resync_cmd(&mut self, cmd_header: &CmdHeader, handled: bool) -> Result<(), ParserError> {
+        // NOTE: if handler took care of the cmd and it was a full packet - the state is restored.
+        if !handled {
+            if cmd_header.cmd != EDemoCommands::DemFullPacket {
+                self.demo_stream.skip_cmd(cmd_header)?;
+                if let Some(damage) = self.damages.last_mut() {
+                    damage.skipped_cmds += 1;
+                }
+                return Ok(());
+            }
 
-        // TODO: do not allow tick to be greater then total ticks
+            let cmd_body = self.demo_stream.read_cmd(cmd_header)?;
+            self.visitor
+                .on_cmd(&self.ctx, cmd_header, cmd_body)
+                .map_err(ParserError::VisitorError)?;
+
+            let cmd = D::decode_cmd_full_packet(cmd_body)?;
+            // NOTE: entities that exist in the full packet are created again.
+            self.ctx.entities.clear();
+            self.handle_cmd_full_packet(cmd)?;
+            self.visitor
+                .on_tick_end(&self.ctx)
+                .map_err(ParserError::VisitorError)?;
+        }
 
-        // TODO: do not clear if seeking forward and there's no full packet on
-        // the way to the wanted tick / if target tick is closer then full
-        // packet interval
-        self.reset()?;
+        if cmd_header.cmd == EDemoCommands::DemFullPacket {
+            self.resyncing = false;
+            if let Some(damage) = self.damages.last_mut() {
+                damage.resumed_tick = Some(cmd_header.tick);
+            }
+        }
+        Ok(())
+    }
 
-        // NOTE: EDemoCommands::DemSyncTick is the last command with 4294967295
-        // tick (normlized to -1). last "initialization" command.
-        let mut did_handle_first_sync_tick = false;
+    // NOTE: returns true if the run loop must stop; `offset` is the position of cmd's header.
+    fn run_cmd<F>(
+        &mut self,
+        cmd_header: &CmdHeader,
+        offset: Option<u64>,
+        handler: &mut F,
+    ) -> Result<bool, ParserError>
+    where
+        F: FnMut(&mut Self, &CmdHeader) -> Result<ControlFlow, ParserError>,
+    {
+        if cmd_header.tick > self.ctx.tick {
+            self.maybe_take_snapshot(offset);
+        }
 
-        // NOTE: EDemoCommands::DemFullPacket contains snapshot of everything...
-        // everything? it does not seem like it: string tables must be handled.
-        let mut did_handle_last_full_packet = false;
+        self.ctx.prev_tick = self.ctx.tick;
+        self.ctx.tick = cmd_header.tick;
+        let control_flow = handler(self, cmd_header)?;
+        if self.resyncing && !matches!(control_flow, ControlFlow::Break) {
+            self.resync_cmd(cmd_header, matches!(control_flow, ControlFlow::IgnoreCmd))?;
+            return Ok(false);
+        }
 
-        self.run(|notnotself, cmd_header| {
-            if cmd_header.tick > target_tick {
-                return Ok(ControlFlow::Break);
+        match control_flow {
+            ControlFlow::HandleCmd => {
+                self.handle_cmd(cmd_header)?;
+                if self.ctx.prev_tick != self.ctx.tick {
+                    self.visitor
+                        .on_tick_end(&self.ctx)
+                        .map_err(ParserError::VisitorError)?;
+                }
             }
-
-            // init string tables, flattened serializers and entity classes
-            if !did_handle_first_sync_tick {
-                did_handle_first_sync_tick = cmd_header.cmd == EDemoCommands::DemSyncTick;
-                return Ok(ControlFlow::HandleCmd);
+            ControlFlow::SkipCmd => self.demo_stream.skip_cmd(cmd_header)?,
+            ControlFlow::IgnoreCmd => {}
+            ControlFlow::Break => {
+                self.demo_stream.unread_cmd_header(cmd_header)?;
+                self.offset = offset;
+                self.ctx.tick = self.ctx.prev_tick;
+                self.can_resume = true;
+                return Ok(true);
             }
+        }
 
-            let is_full_packet = cmd_header.cmd == EDemoCommands::DemFullPacket;
-            let distance_to_target_tick = target_tick - notnotself.ctx.tick;
-            // TODO: what if there's no full packet ahead? maybe dem file is
-            // corrupted or something... scan for full packets before enterint
-            // the "run"?
-            let has_full_packet_ahead =
-                distance_to_target_tick > notnotself.ctx.full_packet_interval + 100;
-            if is_full_packet {
-                let cmd_body = notnotself.demo_stream.read_cmd(cmd_header)?;
-                notnotself
-                    .visitor
-                    .on_cmd(&notnotself.ctx, cmd_header, cmd_body)?;
-
-                let mut cmd = D::decode_cmd_full_packet(cmd_body)?;
-                if has_full_packet_ahead {
-                    // NOTE: clarity seem to ignore "intermediary" full packet's
-                    // packet
-                    //
-                    // TODO: verify that is okay to ignore "intermediary" full
-                    // packet's packet
-                    cmd.packet = None;
-                }
-                notnotself.handle_cmd_full_packet(cmd)?;
-                // NOTE: there's absolutely no reason to check if tick changed because it changed.
-                notnotself.visitor.on_tick_end(&notnotself.ctx)?;
+        Ok(false)
+    }
 
-                did_handle_last_full_packet = !has_full_packet_ahead;
+    pub fn run_to_end(&mut self) -> Result<(), ParserError> {
+        self.run(|_notnotself, _cmd_header| Ok(ControlFlow::HandleCmd))
+    }
 
-                return Ok(ControlFlow::IgnoreCmd);
-            }
+    // NOTE: called at tick boundary, before the first cmd of the next tick is handled; `offset`
+    // is the position of the cmd header that was read already. snapshots are only taken if the
+    // position is known (it is tracked by the parser, see ErrorContext::offset).
+    fn maybe_take_snapshot(&mut self, offset: Option<u64>) {
+        let (Some(snapshot_store), Some(offset)) = (self.snapshot_store.as_mut(), offset) else {
+            return;
+        };
+        if !self.ctx.is_initialized()
+            || self.resyncing
+            || self.ctx.entities.is_empty()
+            || !snapshot_store.is_due(self.ctx.tick)
+        {
+            return;
+        }
+
+        snapshot_store.insert(self.ctx.take_snapshot(offset));
+    }
 
-            if did_handle_last_full_packet {
-                Ok(ControlFlow::HandleCmd)
-            } else {
-                Ok(ControlFlow::SkipCmd)
+    // NOTE: handles all cmds up to the target tick. state must be restored already.
+    pub(crate) fn run_deltas_to_tick(&mut self, target_tick: i32) -> Result<(), ParserError> {
+        self.run(|_notnotself, cmd_header| {
+            if cmd_header.tick > target_tick {
+                return Ok(ControlFlow::Break);
             }
+            Ok(ControlFlow::HandleCmd)
         })
     }
 
@@ -297,12 +716,14 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
     // 1. DemSignonPacket (SvcCreateStringTable)
     // 2. DemSendTables (flattened serializers; never update)
     // 3. DemClassInfo (never update)
-    fn handle_cmd(&mut self, cmd_header: &CmdHeader) -> Result<()> {
+    fn handle_cmd(&mut self, cmd_header: &CmdHeader) -> Result<(), ParserError> {
         // TODO: consider introducing CmdInstance thing that would allow to decode body once and
         // not read it, but skip, if unconsumed. note that to work temporary ownership of
         // demo_stream will need to be taken.
         let cmd_body = self.demo_stream.read_cmd(cmd_header)?;
-        self.visitor.on_cmd(&self.ctx, cmd_header, cmd_body)?;
+        self.visitor
+            .on_cmd(&self.ctx, cmd_header, cmd_body)
+            .map_err(ParserError::VisitorError)?;
 
         match cmd_header.cmd {
             EDemoCommands::DemPacket | EDemoCommands::DemSignonPacket => {
@@ -318,7 +739,14 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                 }
 
                 let cmd = D::decode_cmd_send_tables(cmd_body)?;
-                self.ctx.serializers = Some(FlattenedSerializerContainer::parse(cmd)?);
+                let serializers = match self.serializer_cache.as_ref() {
+                    Some(serializer_cache) => serializer_cache.get_or_parse(cmd)?,
+                    None => Arc::new(FlattenedSerializerContainer::parse_tolerant(cmd)?),
+                };
+                if !self.tolerant {
+                    serializers.ensure_decodable()?;
+                }
+                self.ctx.serializers = Some(serializers);
             }
 
             EDemoCommands::DemClassInfo => {
@@ -329,7 +757,10 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                 }
 
                 let cmd = D::decode_cmd_class_info(cmd_body)?;
-                self.ctx.entity_classes = Some(EntityClasses::parse(cmd));
+                let entity_classes = self
+                    .ctx
+                    .entity_classes
+                    .insert(Arc::new(EntityClasses::parse(cmd)));
 
                 // NOTE: DemClassInfo message becomes available after
                 // SvcCreateStringTable(which has instancebaselines). to know
@@ -341,9 +772,6 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                     .string_tables
                     .find_table(INSTANCE_BASELINE_TABLE_NAME)
                 {
-                    // SAFETY: entity_classes value was assigned above ^.
-                    let entity_classes =
-                        unsafe { self.ctx.entity_classes.as_ref().unwrap_unchecked() };
                     self.ctx
                         .instance_baseline
                         .update(string_table, entity_classes.classes)?;
@@ -358,7 +786,7 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
         Ok(())
     }
 
-    fn handle_cmd_packet(&mut self, cmd: CDemoPacket) -> Result<()> {
+    fn handle_cmd_packet(&mut self, cmd: CDemoPacket) -> Result<(), ParserError> {
         let data = cmd.data.unwrap_or_default();
         let mut br = BitReader::new(&data);
 
@@ -366,11 +794,15 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
             let command = br.read_ubitvar();
             let size = br.read_uvarint32() as usize;
 
-            let buf = &mut self.buf[..size];
+            let Some(buf) = self.buf.get_mut(..size) else {
+                return Err(ParserError::PacketMessageTooLarge { size });
+            };
             br.read_bytes(buf);
             let buf: &_ = buf;
 
-            self.visitor.on_packet(&self.ctx, command, buf)?;
+            self.visitor
+                .on_packet(&self.ctx, command, buf)
+                .map_err(ParserError::VisitorError)?;
 
             match command {
                 c if c == SvcMessages::SvcCreateStringTable as u32 => {
@@ -390,11 +822,17 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
 
                 c if c == SvcMessages::SvcServerInfo as u32 => {
                     let msg = CsvcMsgServerInfo::decode(buf)?;
+                    // NOTE: game dir selects game specific user data decoders of string tables.
+                    if let Some(game_dir) = msg.game_dir.as_deref() {
+                        self.ctx.string_tables.set_game_dir(game_dir);
+                    }
                     if let Some(tick_interval) = msg.tick_interval {
                         self.ctx.tick_interval = tick_interval;
 
                         let ratio = DEFAULT_TICK_INTERVAL / tick_interval;
-                        self.ctx.full_packet_interval = DEFAULT_FULL_PACKET_INTERVAL * ratio as i32;
+                        // NOTE: ratio is not an integer in cs2.
+                        self.ctx.full_packet_interval =
+                            (DEFAULT_FULL_PACKET_INTERVAL as f32 * ratio).round() as i32;
 
                         // NOTE(blukai): field decoder context needs tick interval to be able to
                         // decode simulation time floats.
@@ -402,6 +840,27 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                     }
                 }
 
+                c if c == EBaseGameEvents::GeSource1LegacyGameEventList as u32 => {
+                    // NOTE: this check exists because seeking exists, there's no need to re-parse
+                    // game event descriptors
+                    if self.ctx.game_event_list.is_none() {
+                        let msg = CMsgSource1LegacyGameEventList::decode(buf)?;
+                        self.ctx.game_event_list = Some(Arc::new(GameEventList::parse(msg)));
+                    }
+                }
+
+                c if c == EBaseGameEvents::GeSource1LegacyGameEvent as u32 => {
+                    if let Some(game_event_list) = self.ctx.game_event_list.as_ref() {
+                        let msg = CMsgSource1LegacyGameEvent::decode(buf)?;
+                        // NOTE: events that are not described by the list are skipped.
+                        if let Some(game_event) = GameEvent::resolve(game_event_list, msg) {
+                            self.visitor
+                                .on_game_event(&self.ctx, &game_event)
+                                .map_err(ParserError::VisitorError)?;
+                        }
+                    }
+                }
+
                 _ => {
                     // ignore
                 }
@@ -412,7 +871,10 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
         Ok(())
     }
 
-    fn handle_svc_create_string_table(&mut self, msg: CsvcMsgCreateStringTable) -> Result<()> {
+    fn handle_svc_create_string_table(
+        &mut self,
+        msg: CsvcMsgCreateStringTable,
+    ) -> Result<(), ParserError> {
         let string_table = self.ctx.string_tables.create_string_table_mut(
             msg.name(),
             msg.user_data_fixed_size(),
@@ -443,23 +905,32 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
             }
         }
 
+        if let Some(string_table) = self
+            .ctx
+            .string_tables
+            .find_table(msg.name())
+            .filter(|string_table| !string_table.updated_items().is_empty())
+        {
+            self.visitor
+                .on_string_table(&self.ctx, string_table, string_table.updated_items())
+                .map_err(ParserError::VisitorError)?;
+        }
+
         Ok(())
     }
 
-    fn handle_svc_update_string_table(&mut self, msg: CsvcMsgUpdateStringTable) -> Result<()> {
+    fn handle_svc_update_string_table(
+        &mut self,
+        msg: CsvcMsgUpdateStringTable,
+    ) -> Result<(), ParserError> {
         debug_assert!(msg.table_id.is_some(), "invalid table id");
         let table_id = msg.table_id() as usize;
 
-        debug_assert!(
-            self.ctx.string_tables.has_table(table_id),
-            "tryting to update non-existent table"
-        );
-        let string_table = unsafe {
-            self.ctx
-                .string_tables
-                .get_table_mut(table_id)
-                .unwrap_unchecked()
-        };
+        let string_table = self
+            .ctx
+            .string_tables
+            .get_table_mut(table_id)
+            .ok_or(ParserError::UnknownStringTable { table_id })?;
 
         let mut br = BitReader::new(msg.string_data());
         string_table.parse_update(&mut br, msg.num_changed_entries())?;
@@ -473,17 +944,34 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
             }
         }
 
+        if let Some(string_table) = self
+            .ctx
+            .string_tables
+            .get_table(table_id)
+            .filter(|string_table| !string_table.updated_items().is_empty())
+        {
+            self.visitor
+                .on_string_table(&self.ctx, string_table, string_table.updated_items())
+                .map_err(ParserError::VisitorError)?;
+        }
+
         Ok(())
     }
 
     // NOTE: handle_msg_packet_entities is partially based on
     // ReadPacketEntities in engine/client.cpp
-    fn handle_svc_packet_entities(&mut self, msg: CsvcMsgPacketEntities) -> Result<()> {
-        // SAFETY: safety here can only be guaranteed by the fact that entity
-        // classes and flattened serializers become available before packet
-        // entities.
-        let entity_classes = unsafe { self.ctx.entity_classes.as_ref().unwrap_unchecked() };
-        let serializers = unsafe { self.ctx.serializers.as_ref().unwrap_unchecked() };
+    fn handle_svc_packet_entities(
+        &mut self,
+        msg: CsvcMsgPacketEntities,
+    ) -> Result<(), ParserError> {
+        // NOTE: entity classes and flattened serializers become available before packet entities,
+        // unless the demo is damaged.
+        let (Some(entity_classes), Some(serializers)) = (
+            self.ctx.entity_classes.as_ref(),
+            self.ctx.serializers.as_ref(),
+        ) else {
+            return Err(ParserError::MissingSchema);
+        };
         let instance_baseline = &self.ctx.instance_baseline;
 
         let entity_data = msg.entity_data();
@@ -499,15 +987,21 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
             let delta_header = DeltaHeader::from_bit_reader(&mut br);
             match delta_header {
                 DeltaHeader::CREATE => {
-                    let entity = unsafe {
-                        let entity = self.ctx.entities.handle_create(
-                            entity_index,
-                            &mut self.field_decode_ctx,
-                            &mut br,
-                            entity_classes,
-                            instance_baseline,
-                            serializers,
-                        )?;
+                    let (entity, from_baseline) = unsafe {
+                        let (entity, from_baseline) = self
+                            .ctx
+                            .entities
+                            .handle_create(
+                                entity_index,
+                                &mut self.field_decode_ctx,
+                                &mut br,
+                                entity_classes,
+                                instance_baseline,
+                                serializers,
+                            )
+                            .map_err(|err| {
+                                ParserError::from(err).with_entity_index(entity_index)
+                            })?;
                         // SAFETY: borrow checker is not happy because handle_create requires
                         // mutable access to entities; rust's borrowing rules specify that you
                         // cannot have both mutable and immutable refs to the same data at the same
@@ -517,25 +1011,81 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
                         // not make any sense, that is redundant because .get is called inside of
                         // .handle_create. i can't think of any issues that may arrise because of
                         // my raw pointer approach.
-                        &*(entity as *const Entity)
+                        (&*(entity as *const Entity), from_baseline)
                     };
-                    self.visitor.on_entity(&self.ctx, delta_header, entity)?;
+                    if !self.ctx.entities.is_wanted(entity) {
+                        continue;
+                    }
+                    self.visitor
+                        .on_entity(
+                            &self.ctx,
+                            EntityEvent::Created { from_baseline },
+                            entity,
+                            self.ctx.entities.updated_fields(),
+                        )
+                        .map_err(|err| {
+                            ParserError::VisitorError(err).with_entity_index(entity_index)
+                        })?;
                 }
                 DeltaHeader::DELETE => {
-                    let entity = unsafe { self.ctx.entities.handle_delete_unchecked(entity_index) };
-                    self.visitor.on_entity(&self.ctx, delta_header, &entity)?;
+                    // NOTE: visitor is notified before the entity is removed so that its last
+                    // known state remains accessible through the context.
+                    if let Some(entity) = self
+                        .ctx
+                        .entities
+                        .get(&entity_index)
+                        .filter(|entity| self.ctx.entities.is_wanted(entity))
+                    {
+                        self.visitor
+                            .on_entity(&self.ctx, EntityEvent::Deleted, entity, &[])
+                            .map_err(|err| {
+                                ParserError::VisitorError(err).with_entity_index(entity_index)
+                            })?;
+                    }
+                    self.ctx
+                        .entities
+                        .handle_delete(entity_index)
+                        .map_err(|err| ParserError::from(err).with_entity_index(entity_index))?;
+                }
+                DeltaHeader::LEAVE => {
+                    if let Some(entity) = self
+                        .ctx
+                        .entities
+                        .get(&entity_index)
+                        .filter(|entity| self.ctx.entities.is_wanted(entity))
+                    {
+                        self.visitor
+                            .on_entity(&self.ctx, EntityEvent::LeftPvs, entity, &[])
+                            .map_err(|err| {
+                                ParserError::VisitorError(err).with_entity_index(entity_index)
+                            })?;
+                    }
                 }
                 DeltaHeader::UPDATE => {
                     let entity = unsafe {
-                        let entity = self.ctx.entities.handle_update_unchecked(
-                            entity_index,
-                            &mut self.field_decode_ctx,
-                            &mut br,
-                        )?;
+                        let entity = self
+                            .ctx
+                            .entities
+                            .handle_update(entity_index, &mut self.field_decode_ctx, &mut br)
+                            .map_err(|err| {
+                                ParserError::from(err).with_entity_index(entity_index)
+                            })?;
                         // SAFETY: see comment above (below .handle_create call); same stuff.
                         &*(entity as *const Entity)
                     };
-                    self.visitor.on_entity(&self.ctx, delta_header, entity)?;
+                    if !self.ctx.entities.is_wanted(entity) {
+                        continue;
+                    }
+                    self.visitor
+                        .on_entity(
+                            &self.ctx,
+                            EntityEvent::Updated,
+                            entity,
+                            self.ctx.entities.updated_fields(),
+                        )
+                        .map_err(|err| {
+                            ParserError::VisitorError(err).with_entity_index(entity_index)
+                        })?;
                 }
                 _ => {}
             }
@@ -545,25 +1095,40 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
         Ok(())
     }
 
-    fn handle_cmd_string_tables(&mut self, cmd: CDemoStringTables) -> Result<()> {
-        self.ctx.string_tables.do_full_update(cmd);
+    fn handle_cmd_string_tables(&mut self, cmd: CDemoStringTables) -> Result<(), ParserError> {
+        self.ctx.string_tables.do_full_update(&cmd);
 
-        // SAFETY: entity_classes value is expected to be already assigned
-        let entity_classes = unsafe { self.ctx.entity_classes.as_ref().unwrap_unchecked() };
-        if let Some(string_table) = self
-            .ctx
-            .string_tables
-            .find_table(INSTANCE_BASELINE_TABLE_NAME)
-        {
+        // NOTE: entity classes are expected to be assigned already; if they are not - instance
+        // baseline is updated when they arrive (see DemClassInfo handling).
+        if let (Some(entity_classes), Some(string_table)) = (
+            self.ctx.entity_classes.as_ref(),
+            self.ctx
+                .string_tables
+                .find_table(INSTANCE_BASELINE_TABLE_NAME),
+        ) {
             self.ctx
                 .instance_baseline
                 .update(string_table, entity_classes.classes)?;
         }
 
+        // NOTE: visitor is notified after all tables (and instance baseline) are updated.
+        for incoming in cmd.tables.iter() {
+            if let Some(string_table) = self
+                .ctx
+                .string_tables
+                .find_table(incoming.table_name())
+                .filter(|string_table| !string_table.updated_items().is_empty())
+            {
+                self.visitor
+                    .on_string_table(&self.ctx, string_table, string_table.updated_items())
+                    .map_err(ParserError::VisitorError)?;
+            }
+        }
+
         Ok(())
     }
 
-    fn handle_cmd_full_packet(&mut self, cmd: CDemoFullPacket) -> Result<()> {
+    fn handle_cmd_full_packet(&mut self, cmd: CDemoFullPacket) -> Result<(), ParserError> {
         if let Some(string_table) = cmd.string_table {
             self.handle_cmd_string_tables(string_table)?;
         }
@@ -583,8 +1148,11 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
         &self.demo_stream
     }
 
+    /// NOTE: stream can be repositioned through the returned reference, thus afterwards
+    /// [`ErrorContext::offset`] is unknown until the next seek.
     #[inline]
     pub fn demo_stream_mut(&mut self) -> &mut D {
+        self.offset = None;
         &mut self.demo_stream
     }
 
@@ -592,6 +1160,275 @@ impl<D: DemoStream, V: Visitor> Parser<D, V> {
     pub fn context(&self) -> &Context {
         &self.ctx
     }
+
+    #[inline]
+    pub fn visitor(&self) -> &V {
+        &self.visitor
+    }
+
+    #[inline]
+    pub fn visitor_mut(&mut self) -> &mut V {
+        &mut self.visitor
+    }
+
+    /// NOTE: visitor callbacks take the context by reference; this allows to invoke them from
+    /// outside of the parser.
+    #[inline]
+    pub(crate) fn context_and_visitor_mut(&mut self) -> (&Context, &mut V) {
+        (&self.ctx, &mut self.visitor)
+    }
+
+    #[inline]
+    pub fn into_visitor(self) -> V {
+        self.visitor
+    }
+
+    /// index of full packets that is used by [`Parser::run_to_tick`]; it is built during the
+    /// first seek, unless it was provided with [`Parser::set_keyframe_index`].
+    #[inline]
+    pub fn keyframe_index(&self) -> Option<&KeyframeIndex> {
+        self.keyframe_index.as_ref()
+    }
+
+    /// sets flattened serializers and entity classes that were parsed elsewhere (for example by
+    /// another parser of the same demo); corresponding signon cmds will not be parsed again.
+    pub(crate) fn set_schema(
+        &mut self,
+        serializers: Arc<FlattenedSerializerContainer>,
+        entity_classes: Arc<EntityClasses>,
+    ) {
+        self.ctx.serializers = Some(serializers);
+        self.ctx.entity_classes = Some(entity_classes);
+    }
+
+    /// in tolerant mode fields of unknown var types or encoders do not fail parsing of send tables;
+    /// parsing fails only if such a field is actually updated (with
+    /// [`crate::entities::EntityParseError::UndecodableField`]). see
+    /// [`FlattenedSerializerContainer::parse_tolerant`] and
+    /// [`FlattenedSerializerContainer::unrecognized_fields`] for the report.
+    ///
+    /// NOTE: mode should be set before parsing starts.
+    pub fn set_tolerant(&mut self, tolerant: bool) {
+        self.tolerant = tolerant;
+    }
+
+    /// in recovery mode damaged data does not stop the parser (unless the damage is in signon
+    /// cmds). a cmd that can't be read (for example because the demo is truncated) is treated as
+    /// the end of the stream. after a cmd that can't be handled (corrupt packet) cmds are skipped
+    /// until the next full packet and the state is restored from it; visitor does not receive
+    /// callbacks for skipped cmds. see [`Parser::damages`] for the report.
+    ///
+    /// NOTE: visitor's errors are never recovered from.
+    pub fn set_recovery(&mut self, recovery: bool) {
+        self.recovery = recovery;
+    }
+
+    /// parts of the demo that were skipped in recovery mode.
+    #[inline]
+    pub fn damages(&self) -> &[Damage] {
+        &self.damages
+    }
+
+    /// serializers will be taken from (and stored in) the cache instead of being parsed from send
+    /// tables of each demo. see [`SerializerCache`].
+    ///
+    /// NOTE: cache should be set before parsing starts.
+    pub fn set_serializer_cache(&mut self, serializer_cache: Option<Arc<SerializerCache>>) {
+        self.serializer_cache = serializer_cache;
+    }
+
+    /// returns count of snapshots and approximate amount of memory (in bytes) they occupy.
+    pub fn snapshot_stats(&self) -> (usize, usize) {
+        self.snapshot_store
+            .as_ref()
+            .map_or((0, 0), |snapshot_store| {
+                (snapshot_store.len(), snapshot_store.size())
+            })
+    }
+
+    /// maps user data of the given string table to `T`; see
+    /// [`crate::stringtables::UserDataDecoderRegistry`] and [`StringTable::get_decoded`].
+    pub fn register_user_data_decoder<T: prost::Message + Default + 'static>(
+        &mut self,
+        table_name: &str,
+    ) {
+        self.ctx
+            .string_tables
+            .register_user_data_decoder::<T>(table_name);
+    }
+
+    /// restricts entities and fields that are stored and dispatched to
+    /// [`Visitor::on_entity`]; `None` means everything. see [`EntityFilter`].
+    ///
+    /// NOTE: filter should be set before parsing starts.
+    pub fn set_entity_filter(&mut self, filter: Option<EntityFilter>) {
+        self.ctx.entities.set_filter(filter);
+        // NOTE: snapshots contain entities that were stored with the previous filter.
+        if let Some(snapshot_store) = self.snapshot_store.as_mut() {
+            snapshot_store.clear();
+        }
+    }
+}
+
+impl<D: SeekableDemoStream, V: Visitor> Parser<D, V> {
+    fn reset(&mut self) -> Result<(), io::Error> {
+        let offset = self
+            .demo_stream
+            .seek(SeekFrom::Start(self.demo_stream.start_position()))?;
+        self.offset = Some(offset);
+        self.resyncing = false;
+
+        self.ctx.entities.clear();
+        self.ctx.string_tables.clear();
+        self.ctx.instance_baseline.clear();
+        self.ctx.tick = -1;
+        self.ctx.prev_tick = -1;
+
+        Ok(())
+    }
+
+    pub fn run_to_tick(&mut self, target_tick: i32) -> Result<(), ParserError> {
+        // TODO: do not allow tick to be less then -1
+
+        // TODO: do not allow tick to be greater then total ticks
+
+        // NOTE: position could have been lost (see Parser::demo_stream_mut); it is cheap to
+        // restore it once per seek.
+        if self.offset.is_none() {
+            self.offset = Some(self.demo_stream.stream_position()?);
+        }
+
+        if self.keyframe_index.is_none() {
+            self.build_keyframe_index()?;
+        }
+        let mut keyframe = self
+            .keyframe_index
+            .as_ref()
+            .and_then(|keyframe_index| keyframe_index.find(target_tick))
+            .copied();
+
+        // NOTE: state can be restored from a snapshot or current state can be advanced, but only
+        // if that is closer to the target tick then the keyframe. neither requires signon cmds to
+        // be handled again.
+        if self.ctx.is_initialized() {
+            let keyframe_tick = keyframe.map_or(-1, |keyframe| keyframe.tick);
+
+            let current_tick = self.ctx.tick;
+            if self.can_resume && current_tick <= target_tick && current_tick >= keyframe_tick {
+                return self.run_deltas_to_tick(target_tick);
+            }
+
+            if let Some(snapshot) = self
+                .snapshot_store
+                .as_ref()
+                .and_then(|snapshot_store| snapshot_store.find(target_tick))
+                .filter(|snapshot| snapshot.tick >= keyframe_tick)
+            {
+                self.can_resume = false;
+                self.demo_stream.seek(SeekFrom::Start(snapshot.offset))?;
+                self.offset = Some(snapshot.offset);
+                self.resyncing = false;
+                self.ctx.restore_snapshot(snapshot)?;
+                return self.run_deltas_to_tick(target_tick);
+            }
+        }
+
+        self.reset()?;
+
+        // NOTE: EDemoCommands::DemSyncTick is the last command with 4294967295
+        // tick (normlized to -1). last "initialization" command.
+        let mut did_handle_first_sync_tick = false;
+
+        // NOTE: EDemoCommands::DemFullPacket contains snapshot of everything...
+        // everything? it does not seem like it: string tables must be handled.
+        let mut did_handle_full_packet = false;
+
+        self.run(|notnotself, cmd_header| {
+            if cmd_header.tick > target_tick {
+                return Ok(ControlFlow::Break);
+            }
+
+            // init string tables, flattened serializers and entity classes
+            if !did_handle_first_sync_tick {
+                did_handle_first_sync_tick = cmd_header.cmd == EDemoCommands::DemSyncTick;
+                return Ok(ControlFlow::HandleCmd);
+            }
+
+            // NOTE: jump straight to the last full packet before the target tick. if there's no
+            // such full packet - all cmds up to the target tick need to be handled.
+            if let Some(keyframe) = keyframe.take() {
+                notnotself
+                    .demo_stream
+                    .seek(SeekFrom::Start(keyframe.offset))?;
+                notnotself.offset = Some(keyframe.offset);
+                return Ok(ControlFlow::IgnoreCmd);
+            }
+
+            if cmd_header.cmd == EDemoCommands::DemFullPacket {
+                // NOTE: full packets are only needed to restore the state; after that they are
+                // redundant.
+                if did_handle_full_packet {
+                    return Ok(ControlFlow::SkipCmd);
+                }
+
+                let cmd_body = notnotself.demo_stream.read_cmd(cmd_header)?;
+                notnotself
+                    .visitor
+                    .on_cmd(&notnotself.ctx, cmd_header, cmd_body)
+                    .map_err(ParserError::VisitorError)?;
+
+                let cmd = D::decode_cmd_full_packet(cmd_body)?;
+                notnotself.handle_cmd_full_packet(cmd)?;
+                // NOTE: there's absolutely no reason to check if tick changed because it changed.
+                notnotself
+                    .visitor
+                    .on_tick_end(&notnotself.ctx)
+                    .map_err(ParserError::VisitorError)?;
+
+                did_handle_full_packet = true;
+
+                return Ok(ControlFlow::IgnoreCmd);
+            }
+
+            Ok(ControlFlow::HandleCmd)
+        })
+    }
+
+    // public api
+    // ----
+
+    /// sets a pre-built (for example loaded from a sidecar file with
+    /// [`KeyframeIndex::read_from`]) keyframe index. fails if the index was not built for this
+    /// stream (see [`KeyframeIndex::matches`]); current index is kept then.
+    pub fn set_keyframe_index(
+        &mut self,
+        keyframe_index: Option<KeyframeIndex>,
+    ) -> Result<(), KeyframeIndexError> {
+        if let Some(keyframe_index) = keyframe_index.as_ref() {
+            keyframe_index.ensure_matches(&mut self.demo_stream)?;
+        }
+        self.keyframe_index = keyframe_index;
+        Ok(())
+    }
+
+    /// builds keyframe index up-front.
+    pub fn build_keyframe_index(&mut self) -> Result<&KeyframeIndex, KeyframeIndexError> {
+        // NOTE: in recovery mode damage at the end of the demo must not prevent seeking to ticks
+        // before it.
+        let keyframe_index = if self.recovery {
+            KeyframeIndex::build_tolerant(&mut self.demo_stream)?
+        } else {
+            KeyframeIndex::build(&mut self.demo_stream)?
+        };
+        Ok(self.keyframe_index.insert(keyframe_index))
+    }
+
+    /// enables periodic snapshots of the context that are used by [`Parser::run_to_tick`] to
+    /// seek (backward or forward) without rebuilding the state from a full packet. `None`
+    /// disables snapshots and drops existing ones. see [`SnapshotConfig`].
+    pub fn set_snapshot_config(&mut self, config: Option<SnapshotConfig>) {
+        self.snapshot_store = config.map(SnapshotStore::new);
+    }
 }
 
 pub struct NopVisitor;
@@ -603,3 +1440,251 @@ impl<D: DemoStream> Parser<D, NopVisitor> {
         Self::from_stream_with_visitor(demo_stream, NopVisitor)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use valveprotos::common::{
+        CDemoClassInfo, CDemoFileInfo, CDemoSendTables, CsvcMsgFlattenedSerializer,
+    };
+
+    use super::*;
+    use crate::demofile::{DemoFile, DemoWriter};
+    use crate::testutil;
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("tick {0} is not welcome")]
+    struct UnwelcomeTick(i32);
+
+    struct FailingVisitor;
+
+    impl Visitor for FailingVisitor {
+        fn on_tick_end(&mut self, ctx: &Context) -> anyhow::Result<()> {
+            if ctx.tick() == 5 {
+                return Err(UnwelcomeTick(ctx.tick()).into());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_error_context() -> anyhow::Result<()> {
+        let data = testutil::make_packets_demo(0..10, None)?;
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data.clone()))?;
+        let mut offset = demo_file.stream_position()?;
+        for _ in 0..5 {
+            let cmd_header = demo_file.read_cmd_header()?;
+            demo_file.skip_cmd(&cmd_header)?;
+            offset = demo_file.stream_position()?;
+        }
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, FailingVisitor)?;
+        let Err(err) = parser.run_to_end() else {
+            anyhow::bail!("visitor's error was not propagated");
+        };
+
+        assert_eq!(
+            err.context(),
+            Some(&ErrorContext {
+                tick: Some(5),
+                cmd: Some(EDemoCommands::DemPacket),
+                offset: Some(offset),
+                entity_index: None,
+            })
+        );
+        let visitor_error = err
+            .visitor_error()
+            .ok_or_else(|| anyhow::anyhow!("not a visitor error: {err:?}"))?;
+        assert!(visitor_error.downcast_ref::<UnwelcomeTick>().is_some());
+        assert_eq!(
+            err.to_string(),
+            format!("tick 5 is not welcome (at tick 5, cmd DemPacket, offset {offset})")
+        );
+
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct TickCollector {
+        ticks: Vec<i32>,
+    }
+
+    impl Visitor for TickCollector {
+        fn on_tick_end(&mut self, ctx: &Context) -> anyhow::Result<()> {
+            self.ticks.push(ctx.tick());
+            Ok(())
+        }
+    }
+
+    // NOTE: packet at tick 10 is corrupt, there's a full packet at tick 20 and the demo is cut off
+    // in the middle of the packet at tick 30.
+    fn make_damaged_demo() -> anyhow::Result<Vec<u8>> {
+        let mut demo_writer = start_demo_with_signon()?;
+        demo_writer.write_cmd(EDemoCommands::DemPacket, 10, &[0xff; 4], false)?;
+        testutil::write_packets(&mut demo_writer, 11..30, Some(20))?;
+        let packet = CDemoPacket {
+            data: Some(vec![0; 64]),
+        };
+        demo_writer.write_cmd_message(EDemoCommands::DemPacket, 30, &packet, false)?;
+        let mut data = demo_writer.finish(&CDemoFileInfo::default())?.into_inner();
+
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data.as_slice()))?;
+        loop {
+            let cmd_header = demo_file.read_cmd_header()?;
+            if cmd_header.tick == 30 {
+                let end = demo_file.stream_position()? + cmd_header.body_size as u64 / 2;
+                data.truncate(end as usize);
+                return Ok(data);
+            }
+            demo_file.skip_cmd(&cmd_header)?;
+        }
+    }
+
+    // NOTE: signon cmds with empty schema followed by packets at ticks 0..10.
+    fn start_demo_with_signon() -> anyhow::Result<DemoWriter<Cursor<Vec<u8>>>> {
+        let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+        let send_tables = CDemoSendTables {
+            data: Some(CsvcMsgFlattenedSerializer::default().encode_length_delimited_to_vec()),
+        };
+        demo_writer.write_cmd_message(EDemoCommands::DemSendTables, -1, &send_tables, false)?;
+        demo_writer.write_cmd_message(
+            EDemoCommands::DemClassInfo,
+            -1,
+            &CDemoClassInfo::default(),
+            false,
+        )?;
+        demo_writer.write_cmd(EDemoCommands::DemSyncTick, -1, &[], false)?;
+        testutil::write_packets(&mut demo_writer, 0..10, None)?;
+        Ok(demo_writer)
+    }
+
+    #[test]
+    fn test_recovery() -> anyhow::Result<()> {
+        let data = make_damaged_demo()?;
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data.clone()))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, TickCollector::default())?;
+        let err = parser.run_to_end().err();
+        assert_eq!(err.as_ref().and_then(|err| err.context()?.tick), Some(10));
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, TickCollector::default())?;
+        parser.set_recovery(true);
+        parser.run_to_end()?;
+
+        let want_ticks: Vec<i32> = (0..10).chain(20..30).collect();
+        assert_eq!(parser.visitor().ticks, want_ticks);
+
+        let damages: Vec<(Option<i32>, usize, Option<i32>)> = parser
+            .damages()
+            .iter()
+            .map(|damage| {
+                (
+                    damage.error.context().and_then(|context| context.tick),
+                    damage.skipped_cmds,
+                    damage.resumed_tick,
+                )
+            })
+            .collect();
+        // NOTE: corrupt packet and 9 packets after it were skipped.
+        assert_eq!(damages, vec![(Some(10), 10, Some(20)), (Some(30), 1, None)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovery_run_to_tick() -> anyhow::Result<()> {
+        let data = make_damaged_demo()?;
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data.clone()))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, TickCollector::default())?;
+        assert!(parser.run_to_tick(25).is_err());
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, TickCollector::default())?;
+        parser.set_recovery(true);
+        parser.run_to_tick(25)?;
+        assert_eq!(parser.context().tick(), 25);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_keyframe_index() -> anyhow::Result<()> {
+        let data = make_damaged_demo()?;
+        let demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, TickCollector::default())?;
+        assert!(parser.build_keyframe_index().is_err());
+        parser.set_recovery(true);
+        let keyframe_index = parser.build_keyframe_index()?.clone();
+        assert_eq!(keyframe_index.len(), 1);
+
+        // NOTE: index of another stream is rejected; current index is kept.
+        let other_data = testutil::make_packets_demo(0..10, Some(5))?;
+        let mut other_demo_file = DemoFile::start_reading(Cursor::new(other_data))?;
+        let other_keyframe_index = KeyframeIndex::build(&mut other_demo_file)?;
+        assert!(matches!(
+            parser.set_keyframe_index(Some(other_keyframe_index)),
+            Err(KeyframeIndexError::StreamMismatch { .. })
+        ));
+        assert_eq!(parser.keyframe_index(), Some(&keyframe_index));
+
+        parser.set_keyframe_index(Some(keyframe_index))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_cmd_header() -> anyhow::Result<()> {
+        let mut demo_writer = start_demo_with_signon()?;
+        testutil::write_packets(&mut demo_writer, 10..11, None)?;
+        let mut data = demo_writer.finish(&CDemoFileInfo::default())?.into_inner();
+
+        // NOTE: only the first byte of cmd header at tick 10 is left.
+        let mut demo_file = DemoFile::start_reading(Cursor::new(data.as_slice()))?;
+        loop {
+            let offset = demo_file.stream_position()?;
+            let cmd_header = demo_file.read_cmd_header()?;
+            if cmd_header.tick == 10 {
+                data.truncate(offset as usize + 1);
+                break;
+            }
+            demo_file.skip_cmd(&cmd_header)?;
+        }
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data.clone()))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, TickCollector::default())?;
+        assert!(parser.run_to_end().is_err());
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, TickCollector::default())?;
+        parser.set_recovery(true);
+        parser.run_to_end()?;
+        assert_eq!(parser.visitor().ticks, (0..10).collect::<Vec<i32>>());
+        assert_eq!(parser.damages().len(), 1);
+
+        // NOTE: the last byte decodes to an unknown cmd; the header is complete, but it's still
+        // damage and not the end of the stream.
+        let mut demo_writer = start_demo_with_signon()?;
+        testutil::write_packets(&mut demo_writer, 10..11, None)?;
+        let mut data = demo_writer.finish(&CDemoFileInfo::default())?.into_inner();
+        data.push(0x3f);
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data.clone()))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, TickCollector::default())?;
+        assert!(parser.run_to_end().is_err());
+
+        let demo_file = DemoFile::start_reading(Cursor::new(data))?;
+        let mut parser = Parser::from_stream_with_visitor(demo_file, TickCollector::default())?;
+        parser.set_recovery(true);
+        parser.run_to_end()?;
+        let ticks = &parser.visitor().ticks;
+        assert!(ticks.starts_with(&(0..10).collect::<Vec<i32>>()));
+        assert_eq!(parser.damages().len(), 1);
+
+        Ok(())
+    }
+}
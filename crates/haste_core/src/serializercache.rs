@@ -0,0 +1,253 @@
+use std::collections::hash_map::Entry;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use nohash::NoHashMap;
+use valveprotos::common::CDemoSendTables;
+
+use crate::flattenedserializers::{FlattenedSerializerContainer, FlattenedSerializersError};
+
+// NOTE: replays of the same game build carry identical send tables, and parsing them is one of the
+// most expensive parts of the setup. the cache is content-addressed: parsed serializers are keyed
+// by hash of send tables' data. the hash is not collision resistant, thus send tables are kept
+// alongside and compared on a hit.
+//
+// on disk the cache keeps send tables themselves (one file per hash), not parsed serializers;
+// serializers contain decoders that can't be (de)serialized. disk cache allows to parse known
+// builds up-front with [`SerializerCache::preload`] (for example when a long-running process that
+// handles broadcasts restarts), thus the setup cost is not paid when a replay starts.
+
+const FILE_EXTENSION: &str = "sendtables";
+
+#[derive(thiserror::Error, Debug)]
+pub enum SerializerCacheError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    FlattenedSerializersError(#[from] FlattenedSerializersError),
+}
+
+struct CacheEntry {
+    data: Vec<u8>,
+    serializers: Arc<FlattenedSerializerContainer>,
+}
+
+/// cache of parsed flattened serializers that can be shared between parsers (and threads); see
+/// [`crate::parser::Parser::set_serializer_cache`].
+#[derive(Default)]
+pub struct SerializerCache {
+    entries: Mutex<NoHashMap<u64, CacheEntry>>,
+    dir: Option<PathBuf>,
+}
+
+impl SerializerCache {
+    /// in-memory cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// cache that also stores send tables in the given directory. the directory is created when
+    /// needed.
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            entries: Mutex::default(),
+            dir: Some(dir.into()),
+        }
+    }
+
+    // NOTE: the map remains valid even if other thread panicked while holding the lock.
+    fn lock(&self) -> MutexGuard<'_, NoHashMap<u64, CacheEntry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// returns serializers parsed from identical send tables, or parses (and caches) them.
+    ///
+    /// NOTE: serializers are parsed with [`FlattenedSerializerContainer::parse_tolerant`]; see
+    /// [`FlattenedSerializerContainer::ensure_decodable`].
+    pub fn get_or_parse(
+        &self,
+        cmd: CDemoSendTables,
+    ) -> Result<Arc<FlattenedSerializerContainer>, SerializerCacheError> {
+        let hash = fxhash::hash_bytes(cmd.data());
+        if let Some(entry) = self.lock().get(&hash) {
+            if entry.data.as_slice() == cmd.data() {
+                return Ok(entry.serializers.clone());
+            }
+        }
+
+        // NOTE: the lock is not held while parsing; different send tables can be parsed
+        // concurrently. rarely the same send tables may be parsed twice.
+        let data = cmd.data().to_vec();
+        let serializers = Arc::new(FlattenedSerializerContainer::parse_tolerant(cmd)?);
+
+        // NOTE: only send tables that can be parsed are stored. cache is best-effort; failure to
+        // store send tables must not fail parsing.
+        if let Some(dir) = self.dir.as_ref() {
+            let _ = write_send_tables(dir, hash, &data);
+        }
+
+        match self.lock().entry(hash) {
+            Entry::Occupied(oe) if oe.get().data == data => Ok(oe.get().serializers.clone()),
+            // NOTE: hash collision; the entry that is cached already stays.
+            Entry::Occupied(_) => Ok(serializers),
+            Entry::Vacant(ve) => {
+                let entry = ve.insert(CacheEntry { data, serializers });
+                Ok(entry.serializers.clone())
+            }
+        }
+    }
+
+    /// parses send tables that were stored in cache's directory; returns count of serializer
+    /// containers that are in the cache afterwards. files that don't match their hash or can't
+    /// be parsed are ignored.
+    pub fn preload(&self) -> Result<usize, SerializerCacheError> {
+        let Some(dir) = self.dir.as_ref() else {
+            return Ok(self.len());
+        };
+        if !dir.exists() {
+            return Ok(self.len());
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != FILE_EXTENSION) {
+                continue;
+            }
+            let Some(hash) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| u64::from_str_radix(stem, 16).ok())
+            else {
+                continue;
+            };
+            if self.lock().contains_key(&hash) {
+                continue;
+            }
+
+            let data = fs::read(&path)?;
+            if fxhash::hash_bytes(&data) != hash {
+                continue;
+            }
+            // NOTE: a file that can't be parsed (for example it was written by a different
+            // version of the parser) must not prevent other files from being loaded.
+            let Ok(serializers) = FlattenedSerializerContainer::parse_tolerant(CDemoSendTables {
+                data: Some(data.clone()),
+            }) else {
+                continue;
+            };
+            self.lock().insert(
+                hash,
+                CacheEntry {
+                    data,
+                    serializers: Arc::new(serializers),
+                },
+            );
+        }
+
+        Ok(self.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// clears in-memory cache; files are left untouched.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+fn write_send_tables(dir: &Path, hash: u64, data: &[u8]) -> Result<(), io::Error> {
+    let path = dir.join(format!("{hash:016x}.{FILE_EXTENSION}"));
+    if path.exists() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(dir)?;
+    // NOTE: write to a temporary file first so that other processes never observe partially
+    // written file. the name is unique per write; threads of the same process may write the same
+    // send tables concurrently.
+    static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+    let tmp_path = path.with_extension(format!(
+        "{FILE_EXTENSION}.{}.{}",
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, &path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::{TempDir, make_send_tables};
+
+    #[test]
+    fn test_get_or_parse_preload() -> anyhow::Result<()> {
+        let dir = TempDir::new("serializercache")?;
+
+        let cache = SerializerCache::with_dir(dir.path());
+        let a = cache.get_or_parse(make_send_tables(&[("a", &[])]))?;
+        let b = cache.get_or_parse(make_send_tables(&[("a", &[])]))?;
+        assert!(Arc::ptr_eq(&a, &b));
+        cache.get_or_parse(make_send_tables(&[("bb", &[])]))?;
+        assert_eq!(cache.len(), 2);
+
+        // NOTE: file that matches its hash, but can't be parsed is skipped.
+        let garbage = [0xff; 4];
+        let name = format!("{:016x}.{FILE_EXTENSION}", fxhash::hash_bytes(&garbage));
+        fs::write(dir.path().join(name), garbage)?;
+
+        let cache = SerializerCache::with_dir(dir.path());
+        assert!(cache.is_empty());
+        assert_eq!(cache.preload()?, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_collision() -> anyhow::Result<()> {
+        let cache = SerializerCache::new();
+        let a = make_send_tables(&[("a", &[])]);
+        let hash = fxhash::hash_bytes(a.data());
+
+        // NOTE: pretend that different send tables have the same hash.
+        let bb = make_send_tables(&[("bb", &[])]);
+        let colliding = Arc::new(FlattenedSerializerContainer::parse(bb.clone())?);
+        cache.lock().insert(
+            hash,
+            CacheEntry {
+                data: bb.data().to_vec(),
+                serializers: colliding.clone(),
+            },
+        );
+
+        let serializers = cache.get_or_parse(a)?;
+        assert!(!Arc::ptr_eq(&serializers, &colliding));
+        assert!(Arc::ptr_eq(&cache.get_or_parse(bb)?, &colliding));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_send_tables_are_not_stored() -> anyhow::Result<()> {
+        let dir = TempDir::new("serializercache-invalid")?;
+
+        let cache = SerializerCache::with_dir(dir.path());
+        let cmd = CDemoSendTables {
+            data: Some(vec![0xff; 4]),
+        };
+        assert!(cache.get_or_parse(cmd).is_err());
+        assert!(cache.is_empty());
+        assert_eq!(fs::read_dir(dir.path())?.count(), 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use nohash::NoHashMap;
+
+use crate::entities::Entity;
+use crate::stringtables::StringTableItem;
+
+// NOTE: snapshot is a copy of the mutable part of parser's Context (entities and string tables;
+// instance baseline is derived from string tables) that is taken at tick boundary. restoring from
+// a snapshot and replaying a few ticks is much cheaper then rebuilding the state from a full
+// packet, which makes short backward (and forward) seeks cheap.
+//
+// serializers, entity classes and game event list are not stored; they do not change after
+// signon.
+
+/// configures how often [`crate::parser::Parser`] takes snapshots and how much memory they are
+/// allowed to occupy.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotConfig {
+    /// minimal distance between two snapshots, in ticks.
+    pub interval: i32,
+    /// approximate upper bound of memory (in bytes) that snapshots can occupy. when exceeded every
+    /// other snapshot is dropped and the interval is doubled.
+    pub memory_budget: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            // NOTE: 10 seconds in dota2 (tick interval is 1 / 30), 5 in deadlock.
+            interval: 300,
+            memory_budget: 512 * 1024 * 1024,
+        }
+    }
+}
+
+pub(crate) struct ContextSnapshot {
+    /// all cmds up to (and including) this tick were handled.
+    pub(crate) tick: i32,
+    /// position of the next cmd's header in the stream.
+    pub(crate) offset: u64,
+    pub(crate) entities: NoHashMap<i32, Arc<Entity>>,
+    pub(crate) string_table_items: Vec<NoHashMap<i32, StringTableItem>>,
+    pub(crate) size: usize,
+}
+
+pub(crate) struct SnapshotStore {
+    config: SnapshotConfig,
+    interval: i32,
+    // NOTE: keyed by tick.
+    snapshots: BTreeMap<i32, ContextSnapshot>,
+    size: usize,
+}
+
+impl SnapshotStore {
+    pub(crate) fn new(config: SnapshotConfig) -> Self {
+        Self {
+            config,
+            interval: config.interval.max(1),
+            snapshots: BTreeMap::default(),
+            size: 0,
+        }
+    }
+
+    /// returns true if there are no snapshots closer then interval to the given tick.
+    pub(crate) fn is_due(&self, tick: i32) -> bool {
+        let start = tick.saturating_sub(self.interval - 1);
+        let end = tick.saturating_add(self.interval - 1);
+        self.snapshots.range(start..=end).next().is_none()
+    }
+
+    pub(crate) fn insert(&mut self, snapshot: ContextSnapshot) {
+        self.size += snapshot.size;
+        if let Some(prev) = self.snapshots.insert(snapshot.tick, snapshot) {
+            self.size -= prev.size;
+        }
+
+        while self.size > self.config.memory_budget {
+            if self.snapshots.len() <= 1 {
+                self.clear();
+                break;
+            }
+            self.thin_out();
+        }
+    }
+
+    // NOTE: dropping every other snapshot (instead of oldest ones) keeps them evenly distributed
+    // across the whole demo.
+    fn thin_out(&mut self) {
+        let ticks: Vec<i32> = self.snapshots.keys().copied().skip(1).step_by(2).collect();
+        for tick in ticks {
+            if let Some(snapshot) = self.snapshots.remove(&tick) {
+                self.size -= snapshot.size;
+            }
+        }
+        self.interval = self.interval.saturating_mul(2);
+    }
+
+    /// returns the last snapshot at or before the given tick.
+    pub(crate) fn find(&self, tick: i32) -> Option<&ContextSnapshot> {
+        self.snapshots
+            .range(..=tick)
+            .next_back()
+            .map(|(_, snapshot)| snapshot)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.snapshots.clear();
+        self.size = 0;
+        self.interval = self.config.interval.max(1);
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    #[inline]
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn make_snapshot(tick: i32, size: usize) -> ContextSnapshot {
+        ContextSnapshot {
+            tick,
+            offset: 0,
+            entities: NoHashMap::default(),
+            string_table_items: Vec::default(),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_insert_find_thin_out() {
+        let mut store = SnapshotStore::new(SnapshotConfig {
+            interval: 100,
+            memory_budget: 1000,
+        });
+
+        for tick in (0..1000).step_by(100) {
+            assert!(store.is_due(tick));
+            store.insert(make_snapshot(tick, 100));
+            assert!(!store.is_due(tick + 99));
+        }
+        assert_eq!(store.len(), 10);
+        assert_eq!(store.find(-1).map(|snapshot| snapshot.tick), None);
+        assert_eq!(store.find(550).map(|snapshot| snapshot.tick), Some(500));
+
+        // NOTE: budget is exceeded; every other snapshot must be dropped.
+        store.insert(make_snapshot(1000, 100));
+        assert_eq!(store.len(), 6);
+        assert_eq!(store.size(), 600);
+        assert_eq!(store.find(350).map(|snapshot| snapshot.tick), Some(200));
+        assert!(!store.is_due(1199));
+        assert!(store.is_due(1200));
+    }
+}
@@ -1,10 +1,13 @@
-use std::cell::UnsafeCell;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::hash::BuildHasherDefault;
 use std::mem::MaybeUninit;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use nohash::NoHashMap;
 use valveprotos::common::{CDemoStringTables, c_demo_string_tables};
+use valveprotos::prost;
 
 use crate::bitreader::BitReader;
 
@@ -36,10 +39,163 @@ impl StringHistoryEntry {
 const MAX_USERDATA_BITS: usize = 17;
 const MAX_USERDATA_SIZE: usize = 1 << MAX_USERDATA_BITS;
 
-#[derive(Debug)]
+// NOTE: user data is immutable once created; updates replace it. that allows it to be shared
+// (with instance baseline, snapshots and clones of the context) without copying.
+#[derive(Debug, Clone)]
 pub struct StringTableItem {
     pub string: Option<Vec<u8>>,
-    pub user_data: Option<Rc<UnsafeCell<Vec<u8>>>>,
+    pub user_data: Option<Arc<Vec<u8>>>,
+}
+
+impl StringTableItem {
+    fn approx_size(&self) -> usize {
+        let string_len = self.string.as_ref().map_or(0, |string| string.len());
+        let user_data_len = self
+            .user_data
+            .as_ref()
+            .map_or(0, |user_data| user_data.len());
+        size_of::<(i32, Self)>() + string_len + user_data_len
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum UserDataDecodeError {
+    #[error(transparent)]
+    DecodeError(#[from] prost::DecodeError),
+    #[error("no user data decoder is registered for table '{table_name}'")]
+    NoDecoder { table_name: String },
+    #[error("user data of table '{table_name}' is {registered}, not {requested}")]
+    TypeMismatch {
+        table_name: String,
+        registered: &'static str,
+        requested: &'static str,
+    },
+}
+
+type DecodeUserDataFn = fn(&[u8]) -> Result<Box<dyn Any>, prost::DecodeError>;
+
+fn decode_boxed<T: prost::Message + Default + 'static>(
+    buf: &[u8],
+) -> Result<Box<dyn Any>, prost::DecodeError> {
+    T::decode(buf).map(|msg| Box::new(msg) as Box<dyn Any>)
+}
+
+/// decodes user data of string table items into a protobuf message of a particular type.
+#[derive(Debug, Clone, Copy)]
+pub struct UserDataDecoder {
+    type_id: TypeId,
+    type_name: &'static str,
+    decode: DecodeUserDataFn,
+}
+
+impl UserDataDecoder {
+    pub fn new<T: prost::Message + Default + 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+            decode: decode_boxed::<T>,
+        }
+    }
+
+    #[inline]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    #[inline]
+    pub fn decode(&self, buf: &[u8]) -> Result<Box<dyn Any>, prost::DecodeError> {
+        (self.decode)(buf)
+    }
+}
+
+/// maps string table names to [`UserDataDecoder`]s. default registry knows about tables of
+/// enabled games; mappings for other tables can be added with
+/// [`UserDataDecoderRegistry::register`] and [`UserDataDecoderRegistry::register_for_game`].
+#[derive(Debug, Clone)]
+pub struct UserDataDecoderRegistry {
+    decoders: HashMap<Box<str>, UserDataDecoder>,
+    // NOTE: tables with the same name may carry different payloads in different games (e.g.
+    // ActiveModifiers of dota2 and deadlock); keyed by game dir, then by table name.
+    game_decoders: HashMap<Box<str>, HashMap<Box<str>, UserDataDecoder>>,
+}
+
+impl Default for UserDataDecoderRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register::<valveprotos::common::CMsgPlayerInfo>("userinfo");
+
+        #[cfg(feature = "dota2")]
+        registry.register_for_game::<valveprotos::dota2::CdotaModifierBuffTableEntry>(
+            "dota",
+            "ActiveModifiers",
+        );
+
+        registry
+    }
+}
+
+impl UserDataDecoderRegistry {
+    /// registry without any mappings.
+    pub fn empty() -> Self {
+        Self {
+            decoders: HashMap::default(),
+            game_decoders: HashMap::default(),
+        }
+    }
+
+    /// maps the given table to `T` in demos of any game; existing mapping is replaced.
+    pub fn register<T: prost::Message + Default + 'static>(&mut self, table_name: &str) {
+        self.decoders
+            .insert(table_name.into(), UserDataDecoder::new::<T>());
+    }
+
+    /// maps the given table to `T` in demos of the given game (`game_dir` of
+    /// `CSVCMsg_ServerInfo`, e.g. `dota`, `citadel` or `csgo`); takes precedence over mappings
+    /// made with [`UserDataDecoderRegistry::register`]. existing mapping is replaced.
+    pub fn register_for_game<T: prost::Message + Default + 'static>(
+        &mut self,
+        game_dir: &str,
+        table_name: &str,
+    ) {
+        self.game_decoders
+            .entry(game_dir.into())
+            .or_default()
+            .insert(table_name.into(), UserDataDecoder::new::<T>());
+    }
+
+    /// returns decoder that is registered for the given table in demos of any game.
+    #[inline]
+    pub fn get(&self, table_name: &str) -> Option<&UserDataDecoder> {
+        self.decoders.get(table_name)
+    }
+
+    /// returns decoder that is registered for the given table in demos of the given game, falls
+    /// back to [`UserDataDecoderRegistry::get`].
+    pub fn get_for_game(
+        &self,
+        game_dir: Option<&str>,
+        table_name: &str,
+    ) -> Option<&UserDataDecoder> {
+        game_dir
+            .and_then(|game_dir| self.game_decoders.get(game_dir))
+            .and_then(|decoders| decoders.get(table_name))
+            .or_else(|| self.get(table_name))
+    }
+}
+
+/// an item that was created or modified during a string table update. see
+/// [`crate::parser::Visitor::on_string_table`].
+///
+/// NOTE: string of an existing item never changes, updates only replace its user data; thus only
+/// a handle to the previous user data is recorded.
+#[derive(Debug, Clone)]
+pub struct UpdatedStringTableItem {
+    pub index: i32,
+    /// `true` if the item did not exist before the update.
+    pub created: bool,
+    /// user data that the item had before the update; always `None` for created items.
+    pub prev_user_data: Option<Arc<Vec<u8>>>,
 }
 
 #[derive(Debug)]
@@ -52,13 +208,40 @@ pub struct StringTable {
     using_varint_bitcounts: bool,
 
     items: NoHashMap<i32, StringTableItem>,
+    updated_items: Vec<UpdatedStringTableItem>,
+    user_data_decoder: Option<UserDataDecoder>,
 
+    // NOTE: scratch buffers are allocated on first update; clones of the table do not carry them.
     history: Vec<StringHistoryEntry>,
     string_buf: Vec<u8>,
     user_data_buf: Vec<u8>,
     user_data_uncompressed_buf: Vec<u8>,
 }
 
+// NOTE: this is not derived because scratch buffers are not meant to be copied (they are
+// uninitialized). updated items describe the most recent update as it is being dispatched; they
+// are not copied either.
+impl Clone for StringTable {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            user_data_fixed_size: self.user_data_fixed_size,
+            user_data_size: self.user_data_size,
+            user_data_size_bits: self.user_data_size_bits,
+            flags: self.flags,
+            using_varint_bitcounts: self.using_varint_bitcounts,
+            items: self.items.clone(),
+            updated_items: Vec::new(),
+            user_data_decoder: self.user_data_decoder,
+
+            history: Vec::new(),
+            string_buf: Vec::new(),
+            user_data_buf: Vec::new(),
+            user_data_uncompressed_buf: Vec::new(),
+        }
+    }
+}
+
 impl StringTable {
     pub fn new(
         name: &str,
@@ -68,13 +251,6 @@ impl StringTable {
         flags: i32,
         using_varint_bitcounts: bool,
     ) -> Self {
-        #[inline(always)]
-        unsafe fn make_vec<T>(size: usize) -> Vec<T> {
-            let mut vec = Vec::with_capacity(size);
-            vec.set_len(size);
-            vec
-        }
-
         Self {
             name: name.into(),
             user_data_fixed_size,
@@ -83,11 +259,30 @@ impl StringTable {
             flags,
             using_varint_bitcounts,
             items: NoHashMap::with_capacity_and_hasher(1024, BuildHasherDefault::default()),
+            updated_items: Vec::with_capacity(1024),
+            user_data_decoder: None,
 
-            history: unsafe { make_vec(HISTORY_SIZE) },
-            string_buf: unsafe { make_vec(1024) },
-            user_data_buf: unsafe { make_vec(MAX_USERDATA_SIZE) },
-            user_data_uncompressed_buf: unsafe { make_vec(MAX_USERDATA_SIZE) },
+            history: Vec::new(),
+            string_buf: Vec::new(),
+            user_data_buf: Vec::new(),
+            user_data_uncompressed_buf: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn ensure_scratch_buffers(&mut self) {
+        #[inline(always)]
+        unsafe fn make_vec<T>(size: usize) -> Vec<T> {
+            let mut vec = Vec::with_capacity(size);
+            vec.set_len(size);
+            vec
+        }
+
+        if self.history.is_empty() {
+            self.history = unsafe { make_vec(HISTORY_SIZE) };
+            self.string_buf = unsafe { make_vec(1024) };
+            self.user_data_buf = unsafe { make_vec(MAX_USERDATA_SIZE) };
+            self.user_data_uncompressed_buf = unsafe { make_vec(MAX_USERDATA_SIZE) };
         }
     }
 
@@ -102,6 +297,9 @@ impl StringTable {
     ) -> Result<(), snap::Error> {
         let mut entry_index: i32 = -1;
 
+        self.ensure_scratch_buffers();
+        self.updated_items.clear();
+
         // TODO: feature flag or something for a static allocation of history,
         // string_buf and user_data_buf in single threaded environment (similar
         // to what butterfly does).
@@ -219,27 +417,38 @@ impl StringTable {
                 None
             };
 
-            self.items
-                .entry(entry_index)
-                .and_modify(|entry| {
-                    if let Some(dst_container) = entry.user_data.as_ref() {
-                        if let Some(src) = user_data {
-                            let dst = unsafe { dst_container.get().as_mut().unwrap_unchecked() };
-                            dst.resize(src.len(), 0);
-                            dst.clone_from_slice(src);
-                        }
-                    } else {
-                        entry.user_data = user_data.map(|v| Rc::new(UnsafeCell::new(v.to_vec())));
-                    }
-                })
-                .or_insert_with(|| StringTableItem {
-                    string: string.map(|src| {
-                        let mut dst = Vec::with_capacity(src.len());
-                        dst.extend_from_slice(src);
-                        dst
-                    }),
-                    user_data: user_data.map(|v| Rc::new(UnsafeCell::new(v.to_vec()))),
-                });
+            match self.items.entry(entry_index) {
+                Entry::Occupied(mut entry) => {
+                    // NOTE: existing items can only get new user data.
+                    let Some(src) = user_data else {
+                        continue;
+                    };
+
+                    let prev_user_data = entry.get_mut().user_data.replace(Arc::new(src.to_vec()));
+
+                    self.updated_items.push(UpdatedStringTableItem {
+                        index: entry_index,
+                        created: false,
+                        prev_user_data,
+                    });
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(StringTableItem {
+                        string: string.map(|src| {
+                            let mut dst = Vec::with_capacity(src.len());
+                            dst.extend_from_slice(src);
+                            dst
+                        }),
+                        user_data: user_data.map(|v| Arc::new(v.to_vec())),
+                    });
+
+                    self.updated_items.push(UpdatedStringTableItem {
+                        index: entry_index,
+                        created: true,
+                        prev_user_data: None,
+                    });
+                }
+            }
         }
 
         Ok(())
@@ -257,25 +466,78 @@ impl StringTable {
             "removing entries is not supported"
         );
 
+        self.updated_items.clear();
+
         for (i, incoming) in table.items.iter().enumerate() {
-            self.items
-                .entry(i as i32)
-                .and_modify(|existing| {
-                    existing.user_data = incoming
-                        .data
-                        .as_ref()
-                        .map(|data| Rc::new(UnsafeCell::new(data.clone())))
-                })
-                .or_insert_with(|| StringTableItem {
-                    string: incoming.str.as_ref().map(|v| v.as_bytes().to_vec()),
-                    user_data: incoming
-                        .data
-                        .as_ref()
-                        .map(|data| Rc::new(UnsafeCell::new(data.clone()))),
-                });
+            let user_data = incoming.data.as_ref().map(|data| Arc::new(data.clone()));
+
+            match self.items.entry(i as i32) {
+                Entry::Occupied(mut entry) => {
+                    let existing = entry.get_mut();
+                    // NOTE: full update carries all items; only ones that actually changed are
+                    // reported.
+                    if existing.user_data.as_deref() != incoming.data.as_ref() {
+                        let prev_user_data = std::mem::replace(&mut existing.user_data, user_data);
+                        self.updated_items.push(UpdatedStringTableItem {
+                            index: i as i32,
+                            created: false,
+                            prev_user_data,
+                        });
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(StringTableItem {
+                        string: incoming.str.as_ref().map(|v| v.as_bytes().to_vec()),
+                        user_data,
+                    });
+
+                    self.updated_items.push(UpdatedStringTableItem {
+                        index: i as i32,
+                        created: true,
+                        prev_user_data: None,
+                    });
+                }
+            }
         }
     }
 
+    /// inverse of [`StringTable::do_full_update`]; produces a snapshot that can be stored in
+    /// `CDemoFullPacket`.
+    pub fn to_full_update(&self) -> c_demo_string_tables::TableT {
+        // NOTE: full updates are positional, gaps need to be filled.
+        let len = self.items.keys().max().map_or(0, |max| *max as usize + 1);
+        let mut items = vec![c_demo_string_tables::ItemsT::default(); len];
+        for (index, item) in self.items.iter() {
+            items[*index as usize] = c_demo_string_tables::ItemsT {
+                str: item
+                    .string
+                    .as_ref()
+                    .map(|string| String::from_utf8_lossy(string).into_owned()),
+                data: item.user_data.as_deref().cloned(),
+            };
+        }
+
+        c_demo_string_tables::TableT {
+            table_name: Some(self.name.to_string()),
+            items,
+            items_clientside: Vec::default(),
+            table_flags: Some(self.flags),
+        }
+    }
+
+    /// copy of table's items, can be put back with [`StringTable::restore_items`].
+    pub(crate) fn clone_items(&self) -> NoHashMap<i32, StringTableItem> {
+        self.items.clone()
+    }
+
+    pub(crate) fn restore_items(&mut self, items: &NoHashMap<i32, StringTableItem>) {
+        self.items.clone_from(items);
+    }
+
+    pub(crate) fn approx_items_size(&self) -> usize {
+        self.items.values().map(StringTableItem::approx_size).sum()
+    }
+
     // NOTE: might need those for fast seeks
     // // HLTV change history & rollback
     // void EnableRollback();
@@ -295,12 +557,143 @@ impl StringTable {
     pub fn get_item(&self, entry_index: &i32) -> Option<&StringTableItem> {
         self.items.get(entry_index)
     }
+
+    /// items that were created or modified by the most recent update.
+    #[inline]
+    pub fn updated_items(&self) -> &[UpdatedStringTableItem] {
+        &self.updated_items
+    }
+
+    /// decoder that was registered for this table; see [`UserDataDecoderRegistry`].
+    #[inline]
+    pub fn user_data_decoder(&self) -> Option<&UserDataDecoder> {
+        self.user_data_decoder.as_ref()
+    }
+
+    #[inline]
+    pub fn set_user_data_decoder(&mut self, user_data_decoder: Option<UserDataDecoder>) {
+        self.user_data_decoder = user_data_decoder;
+    }
+
+    fn decode_user_data_with(
+        &self,
+        decoder: &UserDataDecoder,
+        entry_index: &i32,
+    ) -> Result<Option<Box<dyn Any>>, UserDataDecodeError> {
+        let Some(user_data) = self
+            .items
+            .get(entry_index)
+            .and_then(|item| item.user_data.as_deref())
+        else {
+            return Ok(None);
+        };
+        decoder.decode(user_data).map(Some).map_err(Into::into)
+    }
+
+    /// decodes user data of the item with registered decoder. returns `Ok(None)` if there's no
+    /// such item or it has no user data.
+    pub fn decode_user_data(
+        &self,
+        entry_index: &i32,
+    ) -> Result<Option<Box<dyn Any>>, UserDataDecodeError> {
+        let decoder =
+            self.user_data_decoder
+                .as_ref()
+                .ok_or_else(|| UserDataDecodeError::NoDecoder {
+                    table_name: self.name.to_string(),
+                })?;
+        self.decode_user_data_with(decoder, entry_index)
+    }
+
+    /// typed variant of [`StringTable::decode_user_data`]; `T` must match the registered
+    /// decoder, e.g. `table.get_decoded::<CMsgPlayerInfo>(&index)` for `userinfo` table.
+    pub fn get_decoded<T: prost::Message + Default + 'static>(
+        &self,
+        entry_index: &i32,
+    ) -> Result<Option<T>, UserDataDecodeError> {
+        let decoder =
+            self.user_data_decoder
+                .as_ref()
+                .ok_or_else(|| UserDataDecodeError::NoDecoder {
+                    table_name: self.name.to_string(),
+                })?;
+        if decoder.type_id != TypeId::of::<T>() {
+            return Err(UserDataDecodeError::TypeMismatch {
+                table_name: self.name.to_string(),
+                registered: decoder.type_name,
+                requested: std::any::type_name::<T>(),
+            });
+        }
+
+        Ok(self
+            .decode_user_data_with(decoder, entry_index)?
+            // NOTE: type ids are equal, downcast can't fail.
+            .and_then(|msg| msg.downcast::<T>().ok())
+            .map(|msg| *msg))
+    }
+}
+
+// serde...
+
+// NOTE: user data is binary, it is always serialized as base64.
+#[cfg(feature = "serde")]
+impl serde::Serialize for StringTableItem {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use base64::Engine;
+        use serde::ser::SerializeStruct;
+
+        use crate::fieldvalue::SerializeBytes;
+
+        let mut state = serializer.serialize_struct("StringTableItem", 2)?;
+        state.serialize_field("string", &self.string.as_deref().map(SerializeBytes))?;
+        let user_data = self.user_data.as_ref().map(|user_data| {
+            base64::engine::general_purpose::STANDARD.encode(user_data.as_slice())
+        });
+        state.serialize_field("user_data", &user_data)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for StringTable {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("StringTable", 2)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("items", &SerializeStringTableItems(&self.items))?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+struct SerializeStringTableItems<'a>(&'a NoHashMap<i32, StringTableItem>);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SerializeStringTableItems<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // NOTE: sort to get stable output.
+        let mut items: Vec<(&i32, &StringTableItem)> = self.0.iter().collect();
+        items.sort_unstable_by_key(|(index, _)| **index);
+        serializer.collect_map(items)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for StringTableContainer {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.tables)
+    }
 }
 
 // NOTE: this is modelled after CNetworkStringTableContainer
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct StringTableContainer {
     tables: Vec<StringTable>,
+    // NOTE: survives clear; decoders are assigned to tables when they are created.
+    user_data_decoders: UserDataDecoderRegistry,
+    // NOTE: survives clear too; see StringTableContainer::set_game_dir.
+    game_dir: Option<Box<str>>,
 }
 
 impl StringTableContainer {
@@ -319,7 +712,7 @@ impl StringTableContainer {
             "tried to create string table '{name}' twice",
         );
 
-        let table = StringTable::new(
+        let mut table = StringTable::new(
             name,
             user_data_fixed_size,
             user_data_size,
@@ -327,20 +720,36 @@ impl StringTableContainer {
             flags,
             using_varint_bitcounts,
         );
+        table.set_user_data_decoder(
+            self.user_data_decoders
+                .get_for_game(self.game_dir.as_deref(), name)
+                .copied(),
+        );
 
         let len = self.tables.len();
         self.tables.push(table);
         &mut self.tables[len]
     }
 
-    pub fn do_full_update(&mut self, cmd: CDemoStringTables) {
-        for incoming in &cmd.tables {
+    pub fn do_full_update(&mut self, cmd: &CDemoStringTables) {
+        for incoming in cmd.tables.iter() {
             if let Some(existing) = self.find_table_mut(incoming.table_name()) {
                 existing.do_full_update(incoming);
             }
         }
     }
 
+    /// inverse of [`StringTableContainer::do_full_update`].
+    pub fn to_full_update(&self) -> CDemoStringTables {
+        CDemoStringTables {
+            tables: self
+                .tables
+                .iter()
+                .map(StringTable::to_full_update)
+                .collect(),
+        }
+    }
+
     // INetworkStringTable *FindTable( const char *tableName ) const ;
     pub fn find_table(&self, name: &str) -> Option<&StringTable> {
         self.tables
@@ -398,9 +807,199 @@ impl StringTableContainer {
     // void EnableRollback( bool bState );
     // void RestoreTick( int tick );
 
+    /// copy of items of all tables (indexed by table id); see
+    /// [`StringTableContainer::restore_items`].
+    pub(crate) fn clone_items(&self) -> Vec<NoHashMap<i32, StringTableItem>> {
+        self.tables.iter().map(StringTable::clone_items).collect()
+    }
+
+    /// puts back items that were copied with [`StringTableContainer::clone_items`]. tables that
+    /// were created after items were copied are left untouched.
+    pub(crate) fn restore_items(&mut self, items: &[NoHashMap<i32, StringTableItem>]) {
+        for (table, items) in self.tables.iter_mut().zip(items.iter()) {
+            table.restore_items(items);
+        }
+    }
+
+    #[inline]
+    pub fn user_data_decoders(&self) -> &UserDataDecoderRegistry {
+        &self.user_data_decoders
+    }
+
+    /// maps user data of the given table to `T` (see [`UserDataDecoderRegistry::register`]).
+    /// applies to the table if it already exists.
+    pub fn register_user_data_decoder<T: prost::Message + Default + 'static>(
+        &mut self,
+        table_name: &str,
+    ) {
+        self.user_data_decoders.register::<T>(table_name);
+        self.update_user_data_decoders();
+    }
+
+    /// game of the demo (`game_dir` of `CSVCMsg_ServerInfo`); it selects game specific user data
+    /// decoders (see [`UserDataDecoderRegistry::register_for_game`]).
+    #[inline]
+    pub fn game_dir(&self) -> Option<&str> {
+        self.game_dir.as_deref()
+    }
+
+    /// sets game of the demo. decoders of existing tables are re-assigned.
+    pub fn set_game_dir(&mut self, game_dir: &str) {
+        // NOTE: some servers report full path to the game dir.
+        let game_dir = game_dir
+            .trim_end_matches(['/', '\\'])
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(game_dir);
+        if self.game_dir.as_deref() == Some(game_dir) {
+            return;
+        }
+        self.game_dir = Some(game_dir.into());
+        self.update_user_data_decoders();
+    }
+
+    fn update_user_data_decoders(&mut self) {
+        for table in self.tables.iter_mut() {
+            table.set_user_data_decoder(
+                self.user_data_decoders
+                    .get_for_game(self.game_dir.as_deref(), &table.name)
+                    .copied(),
+            );
+        }
+    }
+
     // TODO: rename to iter?
     #[inline]
     pub fn tables(&self) -> impl Iterator<Item = &StringTable> {
         self.tables.iter()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use valveprotos::common::{CDemoPacket, CMsgPlayerInfo};
+    use valveprotos::prost::Message;
+
+    use super::*;
+
+    fn make_full_update(table_name: &str, data: Vec<u8>) -> CDemoStringTables {
+        CDemoStringTables {
+            tables: vec![c_demo_string_tables::TableT {
+                table_name: Some(table_name.to_string()),
+                items: vec![c_demo_string_tables::ItemsT {
+                    str: Some("0".to_string()),
+                    data: Some(data),
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_get_decoded() -> anyhow::Result<()> {
+        let player_info = CMsgPlayerInfo {
+            name: Some("blukai".to_string()),
+            ..Default::default()
+        };
+
+        let mut string_tables = StringTableContainer::default();
+        string_tables.create_string_table_mut("userinfo", false, 0, 0, 0, true);
+        string_tables.create_string_table_mut("custom", false, 0, 0, 0, true);
+        string_tables.do_full_update(&make_full_update("userinfo", player_info.encode_to_vec()));
+        string_tables.do_full_update(&make_full_update("custom", player_info.encode_to_vec()));
+
+        let userinfo = string_tables
+            .find_table("userinfo")
+            .ok_or_else(|| anyhow::anyhow!("userinfo table is missing"))?;
+        assert_eq!(
+            userinfo.get_decoded::<CMsgPlayerInfo>(&0)?,
+            Some(player_info.clone())
+        );
+        assert_eq!(userinfo.get_decoded::<CMsgPlayerInfo>(&1)?, None);
+        assert!(matches!(
+            userinfo.get_decoded::<CDemoPacket>(&0),
+            Err(UserDataDecodeError::TypeMismatch { .. })
+        ));
+
+        let custom = string_tables
+            .find_table("custom")
+            .ok_or_else(|| anyhow::anyhow!("custom table is missing"))?;
+        assert!(matches!(
+            custom.get_decoded::<CMsgPlayerInfo>(&0),
+            Err(UserDataDecodeError::NoDecoder { .. })
+        ));
+
+        string_tables.register_user_data_decoder::<CMsgPlayerInfo>("custom");
+        let custom = string_tables
+            .find_table("custom")
+            .ok_or_else(|| anyhow::anyhow!("custom table is missing"))?;
+        assert_eq!(custom.get_decoded::<CMsgPlayerInfo>(&0)?, Some(player_info));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone() -> anyhow::Result<()> {
+        let mut string_tables = StringTableContainer::default();
+        let table = string_tables.create_string_table_mut("custom", false, 0, 0, 0, true);
+        let mut br = BitReader::new(&[]);
+        table.parse_update(&mut br, 0)?;
+        assert!(br.is_overflowed().is_ok());
+        string_tables.do_full_update(&make_full_update("custom", vec![1, 2, 3]));
+
+        let clone = string_tables.clone();
+        let (Some(table), Some(clone)) = (string_tables.get_table(0), clone.get_table(0)) else {
+            anyhow::bail!("custom table is missing");
+        };
+        assert_eq!(table.user_data_buf.len(), MAX_USERDATA_SIZE);
+        assert_eq!(clone.user_data_buf.capacity(), 0);
+        assert_eq!(clone.user_data_uncompressed_buf.capacity(), 0);
+        assert!(clone.updated_items().is_empty());
+
+        let (Some(item), Some(cloned_item)) = (table.get_item(&0), clone.get_item(&0)) else {
+            anyhow::bail!("item is missing");
+        };
+        assert!(matches!(
+            (&item.user_data, &cloned_item.user_data),
+            (Some(lhs), Some(rhs)) if Arc::ptr_eq(lhs, rhs)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_game_specific_decoder() -> anyhow::Result<()> {
+        let player_info = CMsgPlayerInfo {
+            name: Some("blukai".to_string()),
+            ..Default::default()
+        };
+
+        let mut string_tables = StringTableContainer::default();
+        string_tables.create_string_table_mut("custom", false, 0, 0, 0, true);
+        string_tables.do_full_update(&make_full_update("custom", player_info.encode_to_vec()));
+
+        let mut registry = UserDataDecoderRegistry::empty();
+        registry.register_for_game::<CMsgPlayerInfo>("dota", "custom");
+        registry.register::<CDemoPacket>("custom");
+        string_tables.user_data_decoders = registry;
+
+        // NOTE: decoder is picked once game is known.
+        string_tables.set_game_dir("citadel");
+        let custom = string_tables
+            .find_table("custom")
+            .ok_or_else(|| anyhow::anyhow!("custom table is missing"))?;
+        assert!(matches!(
+            custom.get_decoded::<CMsgPlayerInfo>(&0),
+            Err(UserDataDecodeError::TypeMismatch { .. })
+        ));
+
+        string_tables.set_game_dir("/home/steam/dota 2 beta/game/dota");
+        assert_eq!(string_tables.game_dir(), Some("dota"));
+        let custom = string_tables
+            .find_table("custom")
+            .ok_or_else(|| anyhow::anyhow!("custom table is missing"))?;
+        assert_eq!(custom.get_decoded::<CMsgPlayerInfo>(&0)?, Some(player_info));
+
+        Ok(())
+    }
+}
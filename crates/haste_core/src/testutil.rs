@@ -0,0 +1,263 @@
+// NOTE: helpers for building test data. there are no encoders in the crate (except the demo
+// writer), these are just enough to produce inputs that decoders accept.
+
+use std::fs;
+use std::io::{self, Cursor, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use valveprotos::common::{
+    CDemoClassInfo, CDemoFileInfo, CDemoFullPacket, CDemoPacket, CDemoSendTables,
+    CsvcMsgFlattenedSerializer, CsvcMsgPacketEntities, EDemoCommands,
+    ProtoFlattenedSerializerFieldT, ProtoFlattenedSerializerT, SvcMessages, c_demo_class_info,
+};
+use valveprotos::prost::Message;
+
+use crate::demofile::{DemoWriter, WriteCmdError};
+use crate::fieldpath;
+
+/// writes bits in the order in which [`crate::bitreader::BitReader`] reads them.
+#[derive(Default)]
+pub(crate) struct BitWriter {
+    bits: Vec<bool>,
+}
+
+impl BitWriter {
+    pub(crate) fn write_bits(&mut self, value: u64, num_bits: usize) {
+        // NOTE: bits are read starting from the least significant one.
+        self.bits
+            .extend((0..num_bits).map(|i| (value >> i) & 1 == 1));
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes.iter() {
+            self.write_bits(*byte as u64, 8);
+        }
+    }
+
+    pub(crate) fn write_uvarint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.write_bits((value & 0x7f) | 0x80, 8);
+            value >>= 7;
+        }
+        self.write_bits(value, 8);
+    }
+
+    pub(crate) fn write_varint(&mut self, value: i64) {
+        self.write_uvarint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    /// see [`crate::bitreader::BitReader::read_ubitvar`].
+    pub(crate) fn write_ubitvar(&mut self, value: u32) {
+        let value = value as u64;
+        match value >> 4 {
+            0 => self.write_bits(value, 6),
+            rest if rest < (1 << 4) => {
+                self.write_bits((value & 15) | 16, 6);
+                self.write_bits(rest, 4);
+            }
+            rest if rest < (1 << 8) => {
+                self.write_bits((value & 15) | 32, 6);
+                self.write_bits(rest, 8);
+            }
+            rest => {
+                self.write_bits((value & 15) | 48, 6);
+                self.write_bits(rest, 28);
+            }
+        }
+    }
+
+    /// see [`fieldpath::write_field_op`].
+    pub(crate) fn write_field_op(&mut self, op_index: usize) {
+        fieldpath::write_field_op(&mut self.bits, op_index);
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.bits.len().div_ceil(8)];
+        for (i, bit) in self.bits.iter().enumerate() {
+            bytes[i / 8] |= (*bit as u8) << (i % 8);
+        }
+        bytes
+    }
+}
+
+/// field of a serializer in [`make_send_tables`]: name, type and (optional) encoder.
+pub(crate) type FieldSpec<'a> = (&'a str, &'a str, Option<&'a str>);
+
+fn push_symbol(symbols: &mut Vec<String>, symbol: &str) -> i32 {
+    match symbols.iter().position(|existing| existing == symbol) {
+        Some(i) => i as i32,
+        None => {
+            symbols.push(symbol.to_string());
+            symbols.len() as i32 - 1
+        }
+    }
+}
+
+/// send tables with the given serializers. field whose type is the name of a serializer listed
+/// before it is of that serializer's type (serializers must be listed before serializers that
+/// reference them).
+pub(crate) fn make_send_tables(serializers: &[(&str, &[FieldSpec])]) -> CDemoSendTables {
+    let mut msg = CsvcMsgFlattenedSerializer::default();
+    for (i, (serializer_name, fields)) in serializers.iter().enumerate() {
+        let mut fields_index = Vec::with_capacity(fields.len());
+        for (var_name, var_type, var_encoder) in fields.iter() {
+            let field_serializer_name_sym = serializers[..i]
+                .iter()
+                .any(|(name, _)| name == var_type)
+                .then(|| push_symbol(&mut msg.symbols, var_type));
+            let field = ProtoFlattenedSerializerFieldT {
+                var_name_sym: Some(push_symbol(&mut msg.symbols, var_name)),
+                var_type_sym: Some(push_symbol(&mut msg.symbols, var_type)),
+                var_encoder_sym: var_encoder
+                    .map(|var_encoder| push_symbol(&mut msg.symbols, var_encoder)),
+                field_serializer_name_sym,
+                ..Default::default()
+            };
+            fields_index.push(msg.fields.len() as i32);
+            msg.fields.push(field);
+        }
+        msg.serializers.push(ProtoFlattenedSerializerT {
+            serializer_name_sym: Some(push_symbol(&mut msg.symbols, serializer_name)),
+            fields_index,
+            ..Default::default()
+        });
+    }
+    // NOTE: send tables' data is a length-prefixed message.
+    CDemoSendTables {
+        data: Some(msg.encode_length_delimited_to_vec()),
+    }
+}
+
+/// class 0 is `CFoo`, tests pair it with `CFoo { m_iHealth: int32 }` (see [`make_send_tables`]);
+/// class 1 is never instantiated, it exists so that class ids occupy a bit.
+pub(crate) fn make_class_info() -> CDemoClassInfo {
+    let class = |class_id: i32, network_name: &str| c_demo_class_info::ClassT {
+        class_id: Some(class_id),
+        network_name: Some(network_name.to_string()),
+        ..Default::default()
+    };
+    CDemoClassInfo {
+        classes: vec![class(0, "CFoo"), class(1, "CBar")],
+    }
+}
+
+/// `SvcPacketEntities` operation on an entity of class 0 (see [`make_class_info`]).
+pub(crate) enum EntityOp {
+    Create(i64),
+    Update(i64),
+    Delete,
+}
+
+fn write_single_field(bw: &mut BitWriter, value: i64) {
+    bw.write_field_op(fieldpath::FIELDOP_PLUS_ONE);
+    bw.write_field_op(fieldpath::FIELDOP_FIELD_PATH_ENCODE_FINISH);
+    bw.write_varint(value);
+}
+
+/// packet that contains a single `SvcPacketEntities` message. ops must be ordered by entity index.
+pub(crate) fn make_packet_entities(ops: &[(i32, EntityOp)]) -> CDemoPacket {
+    let mut bw = BitWriter::default();
+    let mut prev_index = -1;
+    for (index, op) in ops.iter() {
+        bw.write_ubitvar((index - prev_index - 1) as u32);
+        prev_index = *index;
+        match op {
+            EntityOp::Create(value) => {
+                // NOTE: delta header, class id, serial, unknown varint.
+                bw.write_bits(0b10, 2);
+                bw.write_bits(0, 1);
+                bw.write_bits(0, 17);
+                bw.write_uvarint(0);
+                write_single_field(&mut bw, *value);
+            }
+            EntityOp::Update(value) => {
+                bw.write_bits(0b00, 2);
+                write_single_field(&mut bw, *value);
+            }
+            EntityOp::Delete => bw.write_bits(0b11, 2),
+        }
+    }
+    let msg = CsvcMsgPacketEntities {
+        updated_entries: Some(ops.len() as i32),
+        entity_data: Some(bw.into_bytes()),
+        ..Default::default()
+    }
+    .encode_to_vec();
+
+    make_packet(&[(SvcMessages::SvcPacketEntities as u32, &msg)])
+}
+
+/// packet that contains the given (type, encoded message) pairs.
+pub(crate) fn make_packet(messages: &[(u32, &[u8])]) -> CDemoPacket {
+    let mut bw = BitWriter::default();
+    for (packet_type, msg) in messages.iter() {
+        bw.write_ubitvar(*packet_type);
+        bw.write_uvarint(msg.len() as u64);
+        bw.write_bytes(msg);
+    }
+    CDemoPacket {
+        data: Some(bw.into_bytes()),
+    }
+}
+
+/// writes an empty packet for each tick. if `full_packet_interval` is set, ticks that are its
+/// multiples are preceded by an empty full packet.
+pub(crate) fn write_packets<W: Write + Seek>(
+    demo_writer: &mut DemoWriter<W>,
+    ticks: impl IntoIterator<Item = i32>,
+    full_packet_interval: Option<i32>,
+) -> Result<(), WriteCmdError> {
+    for tick in ticks {
+        if full_packet_interval.is_some_and(|interval| tick % interval == 0) {
+            demo_writer.write_cmd_message(
+                EDemoCommands::DemFullPacket,
+                tick,
+                &CDemoFullPacket::default(),
+                false,
+            )?;
+        }
+        demo_writer.write_cmd_message(
+            EDemoCommands::DemPacket,
+            tick,
+            &CDemoPacket::default(),
+            false,
+        )?;
+    }
+    Ok(())
+}
+
+/// demo that consists of nothing but packets (see [`write_packets`]).
+pub(crate) fn make_packets_demo(
+    ticks: impl IntoIterator<Item = i32>,
+    full_packet_interval: Option<i32>,
+) -> Result<Vec<u8>, WriteCmdError> {
+    let mut demo_writer = DemoWriter::start_writing(Cursor::new(Vec::new()))?;
+    write_packets(&mut demo_writer, ticks, full_packet_interval)?;
+    let cursor = demo_writer.finish(&CDemoFileInfo::default())?;
+    Ok(cursor.into_inner())
+}
+
+/// directory that is removed (with everything in it) when dropped.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// NOTE: `name` must be unique across tests because they run in parallel.
+    pub(crate) fn new(name: &str) -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("haste-{name}-{}", std::process::id()));
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // NOTE: there's nothing to do about a failure; leftovers in temp dir are harmless.
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::{Context as _, Result};
+use haste::batch::BatchRunner;
+use haste::parser::{Context, Visitor};
+
+#[derive(Default)]
+struct MyVisitor {
+    last_tick: i32,
+}
+
+impl Visitor for MyVisitor {
+    fn on_tick_end(&mut self, ctx: &Context) -> Result<()> {
+        self.last_tick = ctx.tick();
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let dirpath = args.get(1).context("usage: batch <dirpath>")?;
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dirpath)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "dem"))
+        .collect();
+    paths.sort();
+
+    let start = Instant::now();
+    let items = BatchRunner::new().run(&paths, |_path| MyVisitor::default());
+    println!("parsing {} files took {:?}", items.len(), start.elapsed());
+
+    for item in items {
+        match item.result {
+            Ok(visitor) => println!("{}: {} ticks", item.path.display(), visitor.last_tick),
+            Err(err) => println!("{}: error: {err}", item.path.display()),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use anyhow::{Context as _, Result};
+use haste::demofile::DemoFile;
+use haste::entities::{
+    cs2_coord_from_cell, fkey_from_path, Entity, EntityEvent, EntityFilter, UpdatedField,
+};
+use haste::fxhash;
+use haste::gameevents::{GameEvent, PlayerSlot};
+use haste::parser::{Context, Parser, Visitor};
+
+const CX: u64 = fkey_from_path(&["CBodyComponent", "m_cellX"]);
+const CY: u64 = fkey_from_path(&["CBodyComponent", "m_cellY"]);
+const CZ: u64 = fkey_from_path(&["CBodyComponent", "m_cellZ"]);
+
+const VX: u64 = fkey_from_path(&["CBodyComponent", "m_vecX"]);
+const VY: u64 = fkey_from_path(&["CBodyComponent", "m_vecY"]);
+const VZ: u64 = fkey_from_path(&["CBodyComponent", "m_vecZ"]);
+
+const POSITION_KEYS: [u64; 6] = [CX, CY, CZ, VX, VY, VZ];
+
+const PLAYER_NAME_KEY: u64 = fkey_from_path(&["m_iszPlayerName"]);
+
+const CS2_PLAYERPAWN_ENTITY: u64 = fxhash::hash_bytes(b"CCSPlayerPawn");
+const CS2_PLAYERCONTROLLER_ENTITY: u64 = fxhash::hash_bytes(b"CCSPlayerController");
+
+fn get_entity_coord(entity: &Entity, cell_key: &u64, vec_key: &u64) -> Option<f32> {
+    let cell: u16 = entity.get_value(cell_key)?;
+    let vec: f32 = entity.get_value(vec_key)?;
+    let coord = cs2_coord_from_cell(cell, vec);
+    Some(coord)
+}
+
+fn get_entity_position(entity: &Entity) -> Option<[f32; 3]> {
+    let x = get_entity_coord(entity, &CX, &VX)?;
+    let y = get_entity_coord(entity, &CY, &VY)?;
+    let z = get_entity_coord(entity, &CZ, &VZ)?;
+
+    Some([x, y, z])
+}
+
+// NOTE: user ids of game events are player slots; controller of a player lives at slot + 1.
+fn get_player_name(ctx: &Context, userid: Option<PlayerSlot>) -> Option<String> {
+    let controller = ctx.entities()?.get(&userid?.controller_index())?;
+    let name: Box<[u8]> = controller.get_value(&PLAYER_NAME_KEY)?;
+    Some(String::from_utf8_lossy(&name).into_owned())
+}
+
+#[derive(Default, Debug)]
+struct MyVisitor;
+
+impl Visitor for MyVisitor {
+    fn on_entity(
+        &mut self,
+        ctx: &Context,
+        event: EntityEvent,
+        entity: &Entity,
+        updated_fields: &[UpdatedField],
+    ) -> Result<()> {
+        if event != EntityEvent::Updated || !entity.serializer_name_heq(CS2_PLAYERPAWN_ENTITY) {
+            return Ok(());
+        }
+
+        // NOTE: there's no need to track positions from previous ticks; parser supplies a list of
+        // fields that were updated.
+        let did_move = updated_fields
+            .iter()
+            .any(|updated_field| POSITION_KEYS.contains(&updated_field.key));
+        if !did_move {
+            return Ok(());
+        }
+
+        if let Some(position) = get_entity_position(entity) {
+            eprintln!(
+                "{:>6}: pawn {} moved to {:?}",
+                ctx.tick(),
+                entity.index(),
+                position
+            );
+        }
+
+        Ok(())
+    }
+
+    fn on_game_event(&mut self, ctx: &Context, game_event: &GameEvent) -> Result<()> {
+        if game_event.name() != "player_death" {
+            return Ok(());
+        }
+
+        let victim = get_player_name(ctx, game_event.get_value("userid"));
+        let attacker = get_player_name(ctx, game_event.get_value("attacker"));
+        let weapon: Option<String> = game_event.get_value("weapon");
+        let headshot: Option<bool> = game_event.get_value("headshot");
+        eprintln!(
+            "{:>6}: {} killed {} with {}{}",
+            ctx.tick(),
+            attacker.as_deref().unwrap_or("<world>"),
+            victim.as_deref().unwrap_or("<unknown>"),
+            weapon.as_deref().unwrap_or("<unknown>"),
+            if headshot.unwrap_or_default() {
+                " (headshot)"
+            } else {
+                ""
+            },
+        );
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let filepath = args.get(1).context("usage: cs2-players <filepath>")?;
+    let file = File::open(filepath)?;
+    let buf_reader = BufReader::new(file);
+    let demo_file = DemoFile::start_reading(buf_reader)?;
+    let mut parser = Parser::from_stream_with_visitor(demo_file, MyVisitor)?;
+    // NOTE: only positions of player pawns and names of players are interesting; everything else
+    // does not need to be stored.
+    parser.set_entity_filter(Some(
+        EntityFilter::default()
+            .with_serializer_fields(CS2_PLAYERPAWN_ENTITY, &POSITION_KEYS)
+            .with_serializer_fields(CS2_PLAYERCONTROLLER_ENTITY, &[PLAYER_NAME_KEY]),
+    ));
+    parser.run_to_end()?;
+    Ok(())
+}
@@ -6,7 +6,7 @@ use std::io::BufReader;
 use anyhow::{Context as _, Result};
 use haste::demofile::DemoFile;
 use haste::demostream::CmdHeader;
-use haste::entities::{fkey_from_path, DeltaHeader, Entity};
+use haste::entities::{fkey_from_path, Entity, EntityEvent, UpdatedField};
 use haste::fxhash;
 use haste::parser::{Context, Parser, Visitor};
 use haste::valveprotos::common::{CnetMsgTick, EDemoCommands, NetMessages};
@@ -87,8 +87,9 @@ impl Visitor for MyVisitor {
     fn on_entity(
         &mut self,
         _ctx: &Context,
-        _delta_header: DeltaHeader,
+        _event: EntityEvent,
         entity: &Entity,
+        _updated_fields: &[UpdatedField],
     ) -> Result<()> {
         if entity.serializer_name_heq(DEADLOCK_GAMERULES_ENTITY) {
             self.handle_game_rules(entity)?;
@@ -109,5 +110,6 @@ fn main() -> Result<()> {
     let buf_reader = BufReader::new(file);
     let demo_file = DemoFile::start_reading(buf_reader)?;
     let mut parser = Parser::from_stream_with_visitor(demo_file, MyVisitor::default())?;
-    parser.run_to_end()
+    parser.run_to_end()?;
+    Ok(())
 }
@@ -3,20 +3,21 @@ use std::io::BufReader;
 
 use anyhow::{Context as _, Result};
 use haste::demofile::DemoFile;
+use haste::messagehandler::MessageDispatcher;
 use haste::parser::{Context, Parser, Visitor};
-use haste::valveprotos::dota2::{CdotaUserMsgChatMessage, EDotaUserMessages};
-use haste::valveprotos::prost::Message;
+use haste::valveprotos::dota2::CdotaUserMsgChatMessage;
 
 struct MyVisitor;
 
-impl Visitor for MyVisitor {
-    fn on_packet(&mut self, _ctx: &Context, packet_type: u32, data: &[u8]) -> Result<()> {
-        if packet_type == EDotaUserMessages::DotaUmChatMessage as u32 {
-            let msg = CdotaUserMsgChatMessage::decode(data)?;
-            eprintln!("{:?}", msg);
-        }
-        Ok(())
-    }
+impl Visitor for MyVisitor {}
+
+fn chat_message(
+    _visitor: &mut MyVisitor,
+    _ctx: &Context,
+    msg: &CdotaUserMsgChatMessage,
+) -> Result<()> {
+    eprintln!("{:?}", msg);
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -25,6 +26,8 @@ fn main() -> Result<()> {
     let file = File::open(filepath)?;
     let buf_reader = BufReader::new(file);
     let demo_file = DemoFile::start_reading(buf_reader)?;
-    let mut parser = Parser::from_stream_with_visitor(demo_file, MyVisitor)?;
-    parser.run_to_end()
+    let visitor = MessageDispatcher::new(MyVisitor).with(chat_message);
+    let mut parser = Parser::from_stream_with_visitor(demo_file, visitor)?;
+    parser.run_to_end()?;
+    Ok(())
 }
@@ -0,0 +1,94 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use anyhow::{Context as _, Result};
+use haste::combatlog::{self, CombatLogEntry, CombatLogKind};
+use haste::demofile::DemoFile;
+use haste::messagehandler::MessageDispatcher;
+use haste::parser::{Context, Parser, Visitor};
+use haste::valveprotos::dota2::{CMsgDotaCombatLogEntry, CdotaUserMsgCombatLogBulkData};
+
+struct MyVisitor;
+
+impl Visitor for MyVisitor {}
+
+fn print_entry(entry: &CombatLogEntry) {
+    let attacker = entry.attacker_name.unwrap_or("?");
+    let target = entry.target_name.unwrap_or("?");
+    let inflictor = entry.inflictor_name.unwrap_or("?");
+    match entry.kind {
+        CombatLogKind::Damage => println!(
+            "[{:.2}] {attacker} hits {target} with {inflictor} for {}",
+            entry.game_time, entry.value
+        ),
+        CombatLogKind::Heal => println!(
+            "[{:.2}] {attacker}'s {inflictor} heals {target} for {}",
+            entry.game_time, entry.value
+        ),
+        CombatLogKind::ModifierAdd => println!(
+            "[{:.2}] {target} receives {inflictor} buff/debuff from {attacker}",
+            entry.game_time
+        ),
+        CombatLogKind::ModifierRemove => println!(
+            "[{:.2}] {target} loses {inflictor} buff/debuff",
+            entry.game_time
+        ),
+        CombatLogKind::Death => {
+            println!("[{:.2}] {target} is killed by {attacker}", entry.game_time)
+        }
+        CombatLogKind::Ability => {
+            println!("[{:.2}] {attacker} casts {inflictor}", entry.game_time)
+        }
+        CombatLogKind::Item => println!("[{:.2}] {attacker} uses {inflictor}", entry.game_time),
+        CombatLogKind::Gold => {
+            println!(
+                "[{:.2}] {target} gets {} gold",
+                entry.game_time, entry.value
+            )
+        }
+        CombatLogKind::Xp => println!("[{:.2}] {target} gains {} xp", entry.game_time, entry.value),
+        CombatLogKind::Purchase => println!(
+            "[{:.2}] {target} buys {}",
+            entry.game_time,
+            entry.value_name.unwrap_or("?")
+        ),
+        CombatLogKind::Other(_) => {}
+    }
+}
+
+fn combat_log_entry(
+    _visitor: &mut MyVisitor,
+    ctx: &Context,
+    msg: &CMsgDotaCombatLogEntry,
+) -> Result<()> {
+    if let Some(string_tables) = ctx.string_tables() {
+        print_entry(&CombatLogEntry::resolve(string_tables, msg));
+    }
+    Ok(())
+}
+
+fn combat_log_bulk_data(
+    _visitor: &mut MyVisitor,
+    ctx: &Context,
+    msg: &CdotaUserMsgCombatLogBulkData,
+) -> Result<()> {
+    if let Some(string_tables) = ctx.string_tables() {
+        combatlog::resolve_entries(string_tables, &msg.combat_entries)
+            .for_each(|entry| print_entry(&entry));
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let filepath = args.get(1).context("usage: dota2-combatlog <filepath>")?;
+    let file = File::open(filepath)?;
+    let buf_reader = BufReader::new(file);
+    let demo_file = DemoFile::start_reading(buf_reader)?;
+    let visitor = MessageDispatcher::new(MyVisitor)
+        .with(combat_log_entry)
+        .with(combat_log_bulk_data);
+    let mut parser = Parser::from_stream_with_visitor(demo_file, visitor)?;
+    parser.run_to_end()?;
+    Ok(())
+}
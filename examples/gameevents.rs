@@ -0,0 +1,31 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use anyhow::{Context as _, Result};
+use haste::demofile::DemoFile;
+use haste::gameevents::GameEvent;
+use haste::parser::{Context, Parser, Visitor};
+
+struct MyVisitor;
+
+impl Visitor for MyVisitor {
+    fn on_game_event(&mut self, ctx: &Context, game_event: &GameEvent) -> Result<()> {
+        eprint!("{:>6}: {}", ctx.tick(), game_event.name());
+        for (key, value) in game_event.iter() {
+            eprint!(" {key}={value:?}");
+        }
+        eprintln!();
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let filepath = args.get(1).context("usage: gameevents <filepath>")?;
+    let file = File::open(filepath)?;
+    let buf_reader = BufReader::new(file);
+    let demo_file = DemoFile::start_reading(buf_reader)?;
+    let mut parser = Parser::from_stream_with_visitor(demo_file, MyVisitor)?;
+    parser.run_to_end()?;
+    Ok(())
+}
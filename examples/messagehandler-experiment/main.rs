@@ -3,14 +3,12 @@ use std::fs::File;
 use std::io::BufReader;
 
 use anyhow::{Context as _, Result};
-use handler::HandlerVisitor;
 use haste::demofile::DemoFile;
 use haste::entities::{self, Entity};
-use haste::parser::{Context, Parser};
+use haste::messagehandler::MessageDispatcher;
+use haste::parser::{Context, Parser, Visitor};
 use haste::stringtables::StringTable;
-use haste::valveprotos::deadlock::{CCitadelUserMsgHeroKilled, CitadelUserMessageIds};
-
-mod handler;
+use haste::valveprotos::deadlock::CCitadelUserMsgHeroKilled;
 
 fn get_entity_name<'a>(entity: &'a Entity, entity_names: &'a StringTable) -> Option<&'a str> {
     const NAME_STRINGTABLE_INDEX_KEY: u64 =
@@ -32,6 +30,8 @@ struct State {
     hero_scores: HashMap<String, Score>,
 }
 
+impl Visitor for State {}
+
 fn hero_killed(state: &mut State, ctx: &Context, msg: &CCitadelUserMsgHeroKilled) -> Result<()> {
     let entities = ctx.entities().unwrap();
 
@@ -73,18 +73,14 @@ fn main() -> Result<()> {
     let buf_reader = BufReader::new(file);
     let demo_file = DemoFile::start_reading(buf_reader)?;
 
-    let state = State::default();
-    let mut visitor = HandlerVisitor::with_state(state).with(
-        CitadelUserMessageIds::KEUserMsgHeroKilled as u32,
-        hero_killed,
-    );
+    let visitor = MessageDispatcher::new(State::default()).with(hero_killed);
 
-    let mut parser = Parser::from_stream_with_visitor(demo_file, &mut visitor)?;
+    let mut parser = Parser::from_stream_with_visitor(demo_file, visitor)?;
     parser.run_to_end()?;
 
     println!();
 
-    for (hero, score) in visitor.state().hero_scores.iter() {
+    for (hero, score) in parser.visitor().visitor().hero_scores.iter() {
         println!(
             "{} got {} kills and died {} times",
             hero, score.kills, score.deaths
@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::time::Instant;
+
+use anyhow::{Context as _, Result};
+use haste::demofile::DemoFile;
+use haste::gameevents::GameEvent;
+use haste::parallel::ParallelParser;
+use haste::parser::{Context, Visitor};
+
+#[derive(Default)]
+struct MyVisitor {
+    game_events: Vec<(i32, String)>,
+}
+
+impl Visitor for MyVisitor {
+    fn on_game_event(&mut self, ctx: &Context, game_event: &GameEvent) -> Result<()> {
+        self.game_events
+            .push((ctx.tick(), game_event.name().to_string()));
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let filepath = args.get(1).context("usage: parallel <filepath>")?;
+
+    let start = Instant::now();
+    let parallel_parser = ParallelParser::new(|| {
+        let file = File::open(filepath)?;
+        let buf_reader = BufReader::new(file);
+        Ok(DemoFile::start_reading(buf_reader)?)
+    });
+    // NOTE: visitors are merged in segment order, thus concatenating their output preserves tick
+    // order.
+    let visitor = parallel_parser.run_and_merge(
+        |_segment| MyVisitor::default(),
+        |mut acc, next| {
+            acc.game_events.extend(next.game_events);
+            acc
+        },
+    )?;
+    println!("parsing took {:?}", start.elapsed());
+
+    let game_events = visitor.game_events;
+    for (tick, name) in game_events.iter() {
+        println!("{tick:>6}: {name}");
+    }
+
+    Ok(())
+}
@@ -1,11 +1,13 @@
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
 use std::time::Instant;
 
 use anyhow::{Context, Result};
 use haste::demofile::DemoFile;
-use haste::demostream::DemoStream;
+use haste::demostream::SeekableDemoStream;
+use haste::keyframes::KeyframeIndex;
 use haste::parser::Parser;
+use haste::snapshots::SnapshotConfig;
 use rand::Rng;
 
 const N_SEEKS: u64 = 1000;
@@ -18,6 +20,28 @@ fn main() -> Result<()> {
     let demo_file = DemoFile::start_reading(buf_reader)?;
     let mut parser = Parser::from_stream(demo_file)?;
 
+    // NOTE: keyframe index is stored next to the demo file; it'll be re-used by subsequent runs.
+    let sidecar_filepath = format!("{filepath}.keyframes");
+    let keyframe_index = File::open(&sidecar_filepath)
+        .ok()
+        .and_then(|file| KeyframeIndex::read_from(BufReader::new(file)).ok());
+    // NOTE: stale index (for example the demo was replaced) is rejected and rebuilt.
+    let is_reused = keyframe_index
+        .is_some_and(|keyframe_index| parser.set_keyframe_index(Some(keyframe_index)).is_ok());
+    if !is_reused {
+        let start = Instant::now();
+        let keyframe_index = parser.build_keyframe_index()?;
+        println!(
+            "building keyframe index ({} keyframes) took {:?}",
+            keyframe_index.len(),
+            start.elapsed()
+        );
+        keyframe_index.write_to(BufWriter::new(File::create(&sidecar_filepath)?))?;
+    }
+
+    // NOTE: snapshots make seeks that land close to previously visited ticks cheap.
+    parser.set_snapshot_config(Some(SnapshotConfig::default()));
+
     let mut rng = rand::thread_rng();
     let rng_range = -1..parser.demo_stream_mut().total_ticks()?;
 
@@ -42,5 +66,12 @@ fn main() -> Result<()> {
         elapsed / N_SEEKS
     );
 
+    let (snapshot_count, snapshot_size) = parser.snapshot_stats();
+    println!(
+        "{} snapshots occupy ~{}MiB",
+        snapshot_count,
+        snapshot_size / 1024 / 1024
+    );
+
     Ok(())
 }
@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::BufReader;
+
+use anyhow::{Context as _, Result};
+use haste::demofile::DemoFile;
+use haste::demostream::DemoStream;
+use haste::fieldvalue::FieldValueKind;
+use haste::flattenedserializers::{FlattenedSerializer, FlattenedSerializerContainer};
+use haste::valveprotos::common::{CDemoFileHeader, EDemoCommands};
+use haste::valveprotos::prost::Message;
+use haste_vartype::Expr;
+
+// NOTE: accessorgen reads flattened serializers of a sample replay and emits a rust module with
+// typed accessors for each serializer, e.g. `CCitadelPlayerPawn::m_iHealth(&Entity) ->
+// Option<i32>`. serializers change between game builds, thus the generated module is versioned by
+// build number; it's up to you to keep modules for builds that you care about.
+//
+// usage: accessorgen <filepath> [output]; when output is not specified the module is written to
+// stdout.
+
+// NOTE: field paths can't be deeper then that; see FieldPath.
+const MAX_DEPTH: usize = 7;
+
+struct Accessor {
+    path: Vec<String>,
+    var_type: String,
+    rust_type: &'static str,
+}
+
+fn rust_type(kind: FieldValueKind, var_type: &str) -> &'static str {
+    // NOTE: decoders produce 64 bit integers, narrower types are inferred from var type.
+    let ident = match haste_vartype::parse(var_type) {
+        Ok(Expr::Ident(ident)) => ident,
+        _ => "",
+    };
+    match kind {
+        FieldValueKind::I64 => match ident {
+            "int8" => "i8",
+            "int16" => "i16",
+            "int32" => "i32",
+            _ => "i64",
+        },
+        FieldValueKind::U64 => match ident {
+            "uint8" => "u8",
+            "uint16" => "u16",
+            "uint32" => "u32",
+            _ => "u64",
+        },
+        FieldValueKind::F32 => "f32",
+        FieldValueKind::Bool => "bool",
+        FieldValueKind::Vector3 | FieldValueKind::QAngle => "[f32; 3]",
+        FieldValueKind::Vector2 => "[f32; 2]",
+        FieldValueKind::Vector4 => "[f32; 4]",
+        FieldValueKind::String => "Box<[u8]>",
+    }
+}
+
+fn collect_accessors(
+    serializers: &FlattenedSerializerContainer,
+    serializer: &FlattenedSerializer,
+    path: &mut Vec<String>,
+    accessors: &mut Vec<Accessor>,
+) {
+    if path.len() >= MAX_DEPTH {
+        return;
+    }
+
+    for field in serializer.fields.iter() {
+        let Some(var_name) = serializers.resolve_symbol(field.var_name.hash) else {
+            continue;
+        };
+        let var_type = serializers
+            .resolve_symbol(field.var_type.hash)
+            .unwrap_or_default();
+
+        // NOTE: all elements of a fixed array share the same key, there's no way to access them
+        // individually. values of undecodable fields never arrive.
+        if field.is_fixed_array() || field.is_undecodable() {
+            continue;
+        }
+
+        path.push(var_name.to_string());
+
+        // NOTE: value of dynamic array is its length; elements are not addressable by key.
+        let kind = if field.is_dynamic_array() {
+            Some(FieldValueKind::U64)
+        } else {
+            field.value_kind()
+        };
+        if let Some(kind) = kind {
+            accessors.push(Accessor {
+                path: path.clone(),
+                var_type: var_type.to_string(),
+                rust_type: rust_type(kind, var_type),
+            });
+        }
+
+        if !field.is_dynamic_array() {
+            if let Some(field_serializer) = field.field_serializer.as_ref() {
+                collect_accessors(serializers, field_serializer, path, accessors);
+            }
+        }
+
+        path.pop();
+    }
+}
+
+fn sanitize_ident(ident: &str) -> String {
+    let ident: String = ident
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match ident.chars().next() {
+        Some(c) if c.is_ascii_digit() => format!("_{ident}"),
+        _ => ident,
+    }
+}
+
+fn generate(
+    file_header: Option<&CDemoFileHeader>,
+    serializers: &FlattenedSerializerContainer,
+) -> Result<String> {
+    let mut out = String::new();
+
+    let game = file_header
+        .and_then(|file_header| file_header.game.clone())
+        .unwrap_or_default();
+    let build_num = file_header
+        .and_then(|file_header| file_header.build_num)
+        .unwrap_or_default();
+
+    writeln!(out, "// @generated by accessorgen; do not edit.")?;
+    writeln!(out, "//")?;
+    writeln!(out, "// game: {game}, build: {build_num}")?;
+    writeln!(
+        out,
+        "#![allow(dead_code, non_snake_case, non_camel_case_types, clippy::all)]"
+    )?;
+    writeln!(out)?;
+    writeln!(out, "use haste::entities::{{fkey_from_path, Entity}};")?;
+    writeln!(out, "use haste::fxhash;")?;
+    writeln!(out)?;
+    writeln!(out, "pub const BUILD_NUM: i32 = {build_num};")?;
+
+    // NOTE: fields that were not fully understood are listed so that it's visible what is
+    // missing (undecodable fields) or may be typed incorrectly.
+    if !serializers.unrecognized_fields().is_empty() {
+        writeln!(out)?;
+        writeln!(out, "// unrecognized fields:")?;
+        for unrecognized_field in serializers.unrecognized_fields() {
+            writeln!(
+                out,
+                "// - {}::{} `{}` ({:?})",
+                unrecognized_field.serializer_name,
+                unrecognized_field.var_name,
+                unrecognized_field.var_type,
+                unrecognized_field.reason
+            )?;
+        }
+    }
+
+    let mut serializer_names: Vec<(&str, &FlattenedSerializer)> = serializers
+        .values()
+        .filter_map(|serializer| {
+            serializers
+                .resolve_symbol(serializer.serializer_name.hash)
+                .map(|serializer_name| (serializer_name, serializer.as_ref()))
+        })
+        .collect();
+    serializer_names.sort_unstable_by_key(|(serializer_name, _)| *serializer_name);
+
+    let mut struct_names: HashSet<String> = HashSet::new();
+    for (serializer_name, serializer) in serializer_names {
+        let struct_name = sanitize_ident(serializer_name);
+        if !struct_names.insert(struct_name.clone()) {
+            continue;
+        }
+
+        let mut accessors = Vec::new();
+        collect_accessors(serializers, serializer, &mut Vec::new(), &mut accessors);
+
+        writeln!(out)?;
+        writeln!(out, "pub struct {struct_name};")?;
+        writeln!(out)?;
+        writeln!(out, "impl {struct_name} {{")?;
+        writeln!(
+            out,
+            "    pub const SERIALIZER_NAME_HASH: u64 = fxhash::hash_bytes(b\"{serializer_name}\");"
+        )?;
+        writeln!(out)?;
+        writeln!(out, "    #[inline]")?;
+        writeln!(out, "    pub fn is(entity: &Entity) -> bool {{")?;
+        writeln!(
+            out,
+            "        entity.serializer_name_heq(Self::SERIALIZER_NAME_HASH)"
+        )?;
+        writeln!(out, "    }}")?;
+
+        let mut fn_names: HashSet<String> = HashSet::from(["is".to_string()]);
+        for accessor in accessors {
+            let fn_name = accessor
+                .path
+                .iter()
+                .map(|part| sanitize_ident(part))
+                .collect::<Vec<String>>()
+                .join("__");
+            if !fn_names.insert(fn_name.clone()) {
+                continue;
+            }
+
+            let path = accessor
+                .path
+                .iter()
+                .map(|part| format!("{part:?}"))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            writeln!(out)?;
+            writeln!(out, "    /// `{}`", accessor.var_type)?;
+            writeln!(out, "    #[inline]")?;
+            writeln!(
+                out,
+                "    pub fn {fn_name}(entity: &Entity) -> Option<{}> {{",
+                accessor.rust_type
+            )?;
+            writeln!(out, "        const KEY: u64 = fkey_from_path(&[{path}]);")?;
+            writeln!(out, "        entity.get_value(&KEY)")?;
+            writeln!(out, "    }}")?;
+        }
+
+        writeln!(out, "}}")?;
+    }
+
+    Ok(out)
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let filepath = args
+        .get(1)
+        .context("usage: accessorgen <filepath> [output]")?;
+
+    let file = File::open(filepath)?;
+    let buf_reader = BufReader::new(file);
+    let mut demo_file = DemoFile::start_reading(buf_reader)?;
+
+    let mut file_header: Option<CDemoFileHeader> = None;
+    let serializers = loop {
+        let cmd_header = demo_file.read_cmd_header()?;
+        match cmd_header.cmd {
+            EDemoCommands::DemFileHeader => {
+                let cmd_body = demo_file.read_cmd(&cmd_header)?;
+                file_header = Some(CDemoFileHeader::decode(cmd_body)?);
+            }
+            EDemoCommands::DemSendTables => {
+                let cmd_body = demo_file.read_cmd(&cmd_header)?;
+                let cmd = DemoFile::<BufReader<File>>::decode_cmd_send_tables(cmd_body)?;
+                // NOTE: fields that can't be decoded (for example after a game update introduced
+                // a new encoder) are skipped; accessors for the rest are still useful.
+                break FlattenedSerializerContainer::parse_tolerant(cmd)?;
+            }
+            _ => demo_file.skip_cmd(&cmd_header)?,
+        }
+    };
+
+    let out = generate(file_header.as_ref(), &serializers)?;
+    match args.get(2) {
+        Some(output) => std::fs::write(output, out)?,
+        None => print!("{out}"),
+    }
+
+    Ok(())
+}
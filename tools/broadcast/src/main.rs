@@ -1,9 +1,12 @@
 use std::fs::File;
-use std::io::{BufReader, Write};
+use std::io::{BufReader, BufWriter, Write};
 use std::time::Duration;
 
 use anyhow::{bail, Result};
-use haste::broadcast::{BroadcastFile, BroadcastHttp};
+use haste::broadcast::{
+    convert_live_to_demo, convert_to_demo, default_file_header, BroadcastFile, BroadcastHttp,
+    DemoConverter,
+};
 use haste::demostream::CmdHeader;
 use haste::parser::{Context, Parser, Visitor};
 
@@ -84,7 +87,8 @@ impl ParseCommand {
         let buf_reader = BufReader::new(file);
         let broadcast_file = BroadcastFile::start_reading(buf_reader);
         let mut parser = Parser::from_stream_with_visitor(broadcast_file, MyVisitor)?;
-        parser.run_to_end()
+        parser.run_to_end()?;
+        Ok(())
     }
 
     async fn execute(self) -> Result<()> {
@@ -100,11 +104,64 @@ impl ParseCommand {
     }
 }
 
+/// convert broadcast into demo file
+#[derive(argh::FromArgs)]
+#[argh(subcommand, name = "convert")]
+struct ConvertCommand {
+    /// broadcast url
+    #[argh(option)]
+    url: Option<String>,
+    /// read broadcast from the given file
+    #[argh(option)]
+    filepath: Option<String>,
+    /// write demo to the given file
+    #[argh(option)]
+    output: String,
+}
+
+impl ConvertCommand {
+    async fn convert_from_url(url: &str, output: File) -> Result<()> {
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(3))
+            .build()?;
+
+        let demo_stream = BroadcastHttp::start_streaming(http_client, url).await?;
+        // NOTE: recorded broadcasts contain only one full fragment; live streams allow to request
+        // full fragments periodically which makes it possible to seek in the output.
+        let converter = DemoConverter::new(BufWriter::new(output), default_file_header())?;
+        convert_live_to_demo(demo_stream, converter).await?;
+        Ok(())
+    }
+
+    fn convert_from_filepath(filepath: &str, output: File) -> Result<()> {
+        let file = File::open(filepath)?;
+        let buf_reader = BufReader::new(file);
+        let broadcast_file = BroadcastFile::start_reading(buf_reader);
+        convert_to_demo(broadcast_file, BufWriter::new(output))?;
+        Ok(())
+    }
+
+    async fn execute(self) -> Result<()> {
+        if let (Some(url), None) = (&self.url, &self.filepath) {
+            let output = File::create(&self.output)?;
+            return Self::convert_from_url(url, output).await;
+        }
+
+        if let (None, Some(filepath)) = (&self.url, &self.filepath) {
+            let output = File::create(&self.output)?;
+            return Self::convert_from_filepath(filepath, output);
+        }
+
+        bail!("invalid args; run {} help", env!("CARGO_PKG_NAME"));
+    }
+}
+
 #[derive(argh::FromArgs)]
 #[argh(subcommand)]
 enum SubCommands {
     Download(DownloadCommand),
     Parse(ParseCommand),
+    Convert(ConvertCommand),
 }
 
 impl SubCommands {
@@ -112,6 +169,7 @@ impl SubCommands {
         match self {
             SubCommands::Download(download) => download.execute().await,
             SubCommands::Parse(parse) => parse.execute().await,
+            SubCommands::Convert(convert) => convert.execute().await,
         }
     }
 }
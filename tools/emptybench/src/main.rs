@@ -17,5 +17,6 @@ fn main() -> Result<()> {
     let buf_reader = BufReader::new(file);
     let demo_file = DemoFile::start_reading(buf_reader)?;
     let mut parser = Parser::from_stream(demo_file)?;
-    parser.run_to_end()
+    parser.run_to_end()?;
+    Ok(())
 }